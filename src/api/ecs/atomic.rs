@@ -0,0 +1,96 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Replaces the file at `path` so that readers see either the old or the new contents, never a
+/// partial write. The previous contents are kept beside it with a `.bak` suffix.
+pub fn write_atomically(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    write_atomically_with(path, |file| file.write_all(bytes))
+}
+
+fn write_atomically_with(
+    path: &Path,
+    write: impl FnOnce(&mut File) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let temp_path = suffixed(path, "tmp");
+    let mut file = File::create(&temp_path)?;
+    if let Err(e) = write(&mut file).and_then(|_| file.sync_all()) {
+        drop(file);
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(e);
+    }
+    drop(file);
+    if path.exists() {
+        std::fs::copy(path, suffixed(path, "bak"))?;
+    }
+    std::fs::rename(&temp_path, path)?;
+    sync_parent(path)
+}
+
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Flushes the directory entry so the rename itself survives a crash.
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> std::io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => File::open(parent)?.sync_all(),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::ecs::scratch::ScratchDir;
+
+    #[test]
+    fn test_write_keeps_backup() {
+        let scratch = ScratchDir::create();
+        let dir = scratch.path();
+        let path = dir.join("lots.csv");
+        write_atomically(&path, b"old").unwrap();
+        write_atomically(&path, b"new").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        assert_eq!(std::fs::read(dir.join("lots.csv.bak")).unwrap(), b"old");
+        assert!(!dir.join("lots.csv.tmp").exists());
+    }
+
+    #[test]
+    fn test_interrupted_write_keeps_old_data() {
+        let scratch = ScratchDir::create();
+        let dir = scratch.path();
+        let path = dir.join("lots.csv");
+        write_atomically(&path, b"account,time\nfoo,2021").unwrap();
+        let interrupted = write_atomically_with(&path, |file| {
+            file.write_all(b"account,ti")?;
+            Err(std::io::Error::other("disk full"))
+        });
+        assert!(interrupted.is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"account,time\nfoo,2021");
+        assert!(!dir.join("lots.csv.tmp").exists());
+    }
+
+    #[test]
+    fn test_stale_temp_file_is_replaced() {
+        let scratch = ScratchDir::create();
+        let dir = scratch.path();
+        let path = dir.join("products.csv");
+        write_atomically(&path, b"old").unwrap();
+        // A crash after writing the temp file but before the rename leaves it behind.
+        std::fs::write(dir.join("products.csv.tmp"), b"partial").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"old");
+        write_atomically(&path, b"new").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        assert!(!dir.join("products.csv.tmp").exists());
+    }
+}
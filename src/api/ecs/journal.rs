@@ -0,0 +1,582 @@
+use crate::api::ecs::Eid;
+use crate::data::account::{account_name, Account};
+use crate::data::market::{Product, SharePrice};
+use crate::data::portfolio::Lot;
+use crate::data::realized::RealizedGain;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub lots: HashMap<Eid, Lot>,
+    pub products: Vec<Product>,
+    pub realized: HashMap<Eid, RealizedGain>,
+    #[serde(default)]
+    pub accounts: HashMap<Eid, Account>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceChange {
+    pub symbol: String,
+    pub before: SharePrice,
+    pub after: SharePrice,
+}
+
+/// A change to a user's data, holding enough of the old state to revert it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// The state found when the journal was started, or after data was migrated outside it.
+    Baseline {
+        snapshot: Snapshot,
+    },
+    LotAdded {
+        eid: Eid,
+        lot: Lot,
+    },
+    LotUpdated {
+        eid: Eid,
+        before: Lot,
+        after: Lot,
+    },
+    LotDropped {
+        eid: Eid,
+        lot: Lot,
+    },
+    LotsImported {
+        lots: HashMap<Eid, Lot>,
+    },
+    LotsSold {
+        before: HashMap<Eid, Lot>,
+        after: HashMap<Eid, Lot>,
+        realized: HashMap<Eid, RealizedGain>,
+    },
+    PricesUpdated {
+        changes: Vec<PriceChange>,
+    },
+    ProductAdded {
+        product: Product,
+    },
+    ProductUpdated {
+        before: Product,
+        after: Product,
+    },
+    ProductDropped {
+        position: usize,
+        product: Product,
+    },
+    AccountAdded {
+        eid: Eid,
+        account: Account,
+    },
+    AccountUpdated {
+        eid: Eid,
+        before: Account,
+        after: Account,
+    },
+    AccountDropped {
+        eid: Eid,
+        account: Account,
+    },
+    /// Reverts the entry with the given sequence number.
+    Undone {
+        entry: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub seq: usize,
+    pub time: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+#[derive(Error, Debug)]
+pub enum JournalError {
+    #[error("IO error: {0}")]
+    IOError(#[from] std::io::Error),
+
+    #[error("Journal line {line}: {error}")]
+    ParseError {
+        line: usize,
+        error: serde_json::Error,
+    },
+
+    #[error("Serde error: {0}")]
+    SerdeJsonError(#[from] serde_json::Error),
+
+    #[error("Journal entry {0} does not exist")]
+    UnknownEntry(usize),
+}
+
+impl Event {
+    pub fn describe(&self, accounts: &HashMap<Eid, Account>) -> String {
+        let name = |eid: &Eid| account_name(accounts, eid);
+        match self {
+            Event::Baseline { snapshot } => format!(
+                "Started journal with {} lots and {} products",
+                snapshot.lots.len(),
+                snapshot.products.len()
+            ),
+            Event::LotAdded { lot, .. } => {
+                format!(
+                    "Added {} {} in {}",
+                    lot.quantity,
+                    lot.product,
+                    name(&lot.account)
+                )
+            }
+            Event::LotUpdated { after, .. } => {
+                format!("Edited {} lot in {}", after.product, name(&after.account))
+            }
+            Event::LotDropped { lot, .. } => {
+                format!(
+                    "Deleted {} {} in {}",
+                    lot.quantity,
+                    lot.product,
+                    name(&lot.account)
+                )
+            }
+            Event::LotsImported { lots } => format!("Imported {} lots", lots.len()),
+            Event::LotsSold { realized, .. } => {
+                let quantity = realized.values().map(|gain| gain.quantity).sum::<Decimal>();
+                let products = realized
+                    .values()
+                    .map(|gain| gain.product.as_str())
+                    .collect::<HashSet<_>>();
+                let mut products = products.into_iter().collect::<Vec<_>>();
+                products.sort();
+                format!("Sold {} {}", quantity, products.join(", "))
+            }
+            Event::PricesUpdated { changes } => format!("Updated {} prices", changes.len()),
+            Event::ProductAdded { product } => format!("Added product {}", product.symbol()),
+            Event::ProductUpdated { after, .. } => format!("Edited product {}", after.symbol()),
+            Event::ProductDropped { product, .. } => {
+                format!("Deleted product {}", product.symbol())
+            }
+            Event::AccountAdded { account, .. } => format!("Added account {}", account.name),
+            Event::AccountUpdated { after, .. } => format!("Edited account {}", after.name),
+            Event::AccountDropped { account, .. } => format!("Deleted account {}", account.name),
+            Event::Undone { entry: seq } => format!("Undid change #{}", seq),
+        }
+    }
+
+    pub fn apply(&self, snapshot: &mut Snapshot) {
+        match self {
+            Event::Baseline { snapshot: baseline } => *snapshot = baseline.clone(),
+            Event::LotAdded { eid, lot } => {
+                snapshot.lots.insert(eid.clone(), lot.clone());
+            }
+            Event::LotUpdated { eid, after, .. } => {
+                snapshot.lots.insert(eid.clone(), after.clone());
+            }
+            Event::LotDropped { eid, .. } => {
+                snapshot.lots.remove(eid);
+            }
+            Event::LotsImported { lots } => snapshot.lots.extend(lots.clone()),
+            Event::LotsSold {
+                before,
+                after,
+                realized,
+            } => {
+                for eid in before.keys() {
+                    snapshot.lots.remove(eid);
+                }
+                snapshot.lots.extend(after.clone());
+                snapshot.realized.extend(realized.clone());
+            }
+            Event::PricesUpdated { changes } => set_prices(snapshot, changes, |c| &c.after),
+            Event::ProductAdded { product } => snapshot.products.push(product.clone()),
+            Event::ProductUpdated { before, after } => replace_product(snapshot, before, after),
+            Event::ProductDropped { product, .. } => {
+                snapshot.products.retain(|p| p.symbol() != product.symbol());
+            }
+            Event::AccountAdded { eid, account } => {
+                snapshot.accounts.insert(eid.clone(), account.clone());
+            }
+            Event::AccountUpdated { eid, after, .. } => {
+                snapshot.accounts.insert(eid.clone(), after.clone());
+            }
+            Event::AccountDropped { eid, .. } => {
+                snapshot.accounts.remove(eid);
+            }
+            Event::Undone { .. } => {}
+        }
+    }
+
+    pub fn revert(&self, snapshot: &mut Snapshot) {
+        match self {
+            Event::Baseline { .. } => *snapshot = Snapshot::default(),
+            Event::LotAdded { eid, .. } => {
+                snapshot.lots.remove(eid);
+            }
+            Event::LotUpdated { eid, before, .. } => {
+                snapshot.lots.insert(eid.clone(), before.clone());
+            }
+            Event::LotDropped { eid, lot } => {
+                snapshot.lots.insert(eid.clone(), lot.clone());
+            }
+            Event::LotsImported { lots } => {
+                for eid in lots.keys() {
+                    snapshot.lots.remove(eid);
+                }
+            }
+            Event::LotsSold {
+                before, realized, ..
+            } => {
+                snapshot.lots.extend(before.clone());
+                for eid in realized.keys() {
+                    snapshot.realized.remove(eid);
+                }
+            }
+            Event::PricesUpdated { changes } => set_prices(snapshot, changes, |c| &c.before),
+            Event::ProductAdded { product } => {
+                snapshot.products.retain(|p| p.symbol() != product.symbol());
+            }
+            Event::ProductUpdated { before, after } => replace_product(snapshot, after, before),
+            Event::ProductDropped { position, product } => {
+                let position = (*position).min(snapshot.products.len());
+                snapshot.products.insert(position, product.clone());
+            }
+            Event::AccountAdded { eid, .. } => {
+                snapshot.accounts.remove(eid);
+            }
+            Event::AccountUpdated { eid, before, .. } => {
+                snapshot.accounts.insert(eid.clone(), before.clone());
+            }
+            Event::AccountDropped { eid, account } => {
+                snapshot.accounts.insert(eid.clone(), account.clone());
+            }
+            Event::Undone { .. } => {}
+        }
+    }
+}
+
+fn set_prices(
+    snapshot: &mut Snapshot,
+    changes: &[PriceChange],
+    price: impl Fn(&PriceChange) -> &SharePrice,
+) {
+    for product in snapshot.products.iter_mut() {
+        if let Some(change) = changes.iter().find(|c| c.symbol == product.symbol()) {
+            product.set_share_price(price(change).clone());
+        }
+    }
+}
+
+fn replace_product(snapshot: &mut Snapshot, old: &Product, new: &Product) {
+    if let Some(product) = snapshot
+        .products
+        .iter_mut()
+        .find(|p| p.symbol() == old.symbol())
+    {
+        *product = new.clone();
+    }
+}
+
+pub fn read_journal(path: &Path) -> Result<Vec<JournalEntry>, JournalError> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let text = std::fs::read_to_string(path)?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|error| JournalError::ParseError {
+                line: index + 1,
+                error,
+            })
+        })
+        .collect()
+}
+
+/// How many bytes `last_seq` reads at a time, working back from the end of the journal.
+const TAIL_CHUNK: u64 = 4096;
+
+/// The sequence number of the last entry, found by reading back from the end of the journal
+/// so that appending does not parse every entry.
+fn last_seq(path: &Path) -> Result<Option<usize>, JournalError> {
+    #[derive(Deserialize)]
+    struct Seq {
+        seq: usize,
+    }
+    let mut file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut end = file.metadata()?.len();
+    let mut tail = Vec::new();
+    let line = loop {
+        let text = tail.trim_ascii_end();
+        if let Some(newline) = text.iter().rposition(|&b| b == b'\n') {
+            break &text[newline + 1..];
+        }
+        if end == 0 {
+            break text;
+        }
+        let start = end.saturating_sub(TAIL_CHUNK);
+        let mut chunk = vec![0; (end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut chunk)?;
+        chunk.append(&mut tail);
+        tail = chunk;
+        end = start;
+    };
+    if line.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_slice::<Seq>(line)?.seq))
+}
+
+/// Appends an event, first recording `current` as the baseline if the journal is new.
+pub fn append_event(
+    path: &Path,
+    current: impl FnOnce() -> Snapshot,
+    event: Event,
+) -> Result<JournalEntry, JournalError> {
+    let last = last_seq(path)?;
+    let mut next_seq = last.map_or(0, |seq| seq + 1);
+    let mut lines = String::new();
+    if last.is_none() {
+        let baseline = JournalEntry {
+            seq: 0,
+            time: Utc::now(),
+            event: Event::Baseline {
+                snapshot: current(),
+            },
+        };
+        lines.push_str(&serde_json::to_string(&baseline)?);
+        lines.push('\n');
+        next_seq = 1;
+    }
+    let entry = JournalEntry {
+        seq: next_seq,
+        time: Utc::now(),
+        event,
+    };
+    lines.push_str(&serde_json::to_string(&entry)?);
+    lines.push('\n');
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    file.write_all(lines.as_bytes())?;
+    file.sync_all()?;
+    Ok(entry)
+}
+
+/// A journal entry as shown to the user.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangeItem {
+    pub seq: usize,
+    pub time: DateTime<Utc>,
+    pub description: String,
+    pub undone: bool,
+}
+
+pub fn change_items(entries: &[JournalEntry], accounts: &HashMap<Eid, Account>) -> Vec<ChangeItem> {
+    let undone = undone_seqs(entries);
+    entries
+        .iter()
+        .map(|entry| ChangeItem {
+            seq: entry.seq,
+            time: entry.time,
+            description: entry.event.describe(accounts),
+            undone: undone.contains(&entry.seq),
+        })
+        .collect()
+}
+
+fn undone_seqs(entries: &[JournalEntry]) -> HashSet<usize> {
+    entries
+        .iter()
+        .filter_map(|entry| match entry.event {
+            Event::Undone { entry: seq } => Some(seq),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Finds the newest change that has not already been undone. Changes before the newest baseline
+/// cannot be undone, since the data may have been migrated since.
+pub fn last_undoable(entries: &[JournalEntry]) -> Option<&JournalEntry> {
+    let undone = undone_seqs(entries);
+    entries
+        .iter()
+        .rev()
+        .take_while(|entry| !matches!(entry.event, Event::Baseline { .. }))
+        .find(|entry| !undone.contains(&entry.seq) && !matches!(entry.event, Event::Undone { .. }))
+}
+
+/// Rebuilds state by applying every entry from the start of the journal.
+pub fn replay(entries: &[JournalEntry]) -> Result<Snapshot, JournalError> {
+    let by_seq = entries
+        .iter()
+        .map(|entry| (entry.seq, entry))
+        .collect::<HashMap<_, _>>();
+    let mut snapshot = Snapshot::default();
+    for entry in entries {
+        match entry.event {
+            Event::Undone { entry: seq } => by_seq
+                .get(&seq)
+                .ok_or(JournalError::UnknownEntry(seq))?
+                .event
+                .revert(&mut snapshot),
+            ref event => event.apply(&mut snapshot),
+        }
+    }
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::ecs::scratch::ScratchDir;
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+
+    fn lot(quantity: Decimal) -> Lot {
+        Lot {
+            account: "foo".into(),
+            time: Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap(),
+            product: "AAPL".into(),
+            quantity,
+            unit_cost: None,
+            fees: None,
+        }
+    }
+
+    fn price(height: Decimal) -> SharePrice {
+        SharePrice {
+            height,
+            time: Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap(),
+        }
+    }
+
+    fn baseline() -> Snapshot {
+        Snapshot {
+            lots: HashMap::from([(Eid::from("l0"), lot(dec!(1)))]),
+            products: vec![Product::Etf {
+                symbol: "AAPL".into(),
+                name: "Apple".into(),
+                aliases: Vec::new(),
+                currency: "USD".into(),
+                share_price: price(dec!(100)),
+            }],
+            realized: HashMap::new(),
+            accounts: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_replay_and_undo() {
+        let scratch = ScratchDir::create();
+        let path = scratch.path().join("journal.jsonl");
+        append_event(
+            &path,
+            baseline,
+            Event::LotAdded {
+                eid: Eid::from("l1"),
+                lot: lot(dec!(2)),
+            },
+        )
+        .unwrap();
+        append_event(
+            &path,
+            baseline,
+            Event::PricesUpdated {
+                changes: vec![PriceChange {
+                    symbol: "AAPL".into(),
+                    before: price(dec!(100)),
+                    after: price(dec!(110)),
+                }],
+            },
+        )
+        .unwrap();
+        let entries = read_journal(&path).unwrap();
+        assert_eq!(entries.len(), 3);
+        let replayed = replay(&entries).unwrap();
+        assert_eq!(replayed.lots.len(), 2);
+        assert_eq!(replayed.products[0].share_price().height, dec!(110));
+
+        let undoable = last_undoable(&entries).unwrap().seq;
+        assert_eq!(undoable, 2);
+        append_event(&path, baseline, Event::Undone { entry: undoable }).unwrap();
+        let entries = read_journal(&path).unwrap();
+        assert_eq!(last_undoable(&entries).unwrap().seq, 1);
+        let replayed = replay(&entries).unwrap();
+        assert_eq!(replayed.products[0].share_price().height, dec!(100));
+        assert_eq!(replayed.lots.len(), 2);
+    }
+
+    #[test]
+    fn test_undo_stops_at_baseline() {
+        let entry = |seq, event| JournalEntry {
+            seq,
+            time: Utc::now(),
+            event,
+        };
+        let mut entries = vec![
+            entry(
+                0,
+                Event::Baseline {
+                    snapshot: baseline(),
+                },
+            ),
+            entry(
+                1,
+                Event::LotAdded {
+                    eid: Eid::from("l1"),
+                    lot: lot(dec!(2)),
+                },
+            ),
+            entry(
+                2,
+                Event::Baseline {
+                    snapshot: baseline(),
+                },
+            ),
+        ];
+        assert!(last_undoable(&entries).is_none());
+
+        entries.remove(0);
+        entries.push(entry(
+            3,
+            Event::LotAdded {
+                eid: Eid::from("l3"),
+                lot: lot(dec!(3)),
+            },
+        ));
+        entries.push(entry(4, Event::Undone { entry: 3 }));
+        assert!(last_undoable(&entries).is_none());
+        let replayed = replay(&entries).unwrap();
+        assert_eq!(replayed.lots.len(), 1);
+    }
+
+    #[test]
+    fn test_append_continues_from_last_entry() {
+        let scratch = ScratchDir::create();
+        let path = scratch.path().join("journal.jsonl");
+        let lots = (0..100)
+            .map(|i| (Eid::from(format!("l{}", i).as_str()), lot(dec!(1))))
+            .collect::<HashMap<_, _>>();
+        append_event(&path, baseline, Event::LotsImported { lots }).unwrap();
+        assert!(std::fs::metadata(&path).unwrap().len() > TAIL_CHUNK);
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"\n\n").unwrap();
+        let entry = append_event(&path, baseline, Event::Undone { entry: 1 }).unwrap();
+        assert_eq!(entry.seq, 2);
+        assert_eq!(read_journal(&path).unwrap().len(), 3);
+    }
+}
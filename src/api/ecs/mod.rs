@@ -1,16 +1,49 @@
 use crate::data;
-use crate::data::market::Product;
-use crate::data::portfolio::Lot;
+use crate::data::account::{Account, AccountError, AccountKind};
+use crate::data::fx::{CurrencySettings, FxRate, FxTable};
+use crate::data::history::PriceHistory;
+use crate::data::market::{Product, ProductError, SharePrice};
+use crate::data::net_worth::{NetWorthReport, NetWorthSnapshot};
+use crate::data::portfolio::{Lot, LotError};
+use crate::data::realized::RealizedGain;
+use crate::data::sale::{ReliefMethod, Sale, SaleError};
+use crate::data::tax::TaxSettings;
+use crate::data::term;
+use crate::data::term::{IdenticalProducts, WashSale};
 use crate::data::yf::MarketPrice;
-use crate::data::{market, portfolio};
+use chrono::Utc;
+use dioxus::logger::tracing::warn;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use thiserror::Error;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) mod atomic;
+pub mod journal;
+#[cfg(test)]
+pub(crate) mod scratch;
+use journal::{Event, JournalEntry, JournalError, PriceChange, Snapshot};
+pub mod storage;
+use storage::{Storage, StorageError, StorageKind};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct Eid(String);
 
+impl Eid {
+    pub fn random() -> Self {
+        Eid(uuid::Uuid::new_v4().to_string())
+    }
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Eid {
+    fn from(value: &str) -> Self {
+        Eid(value.to_string())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LotItem(pub Lot, pub Eid);
 
@@ -20,11 +53,29 @@ impl LotItem {
     }
 }
 
+/// What `Ecs::update_prices` did with a batch of quotes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PriceUpdate {
+    /// Every quoted product with its price before and after, including unchanged ones.
+    pub matched: Vec<PriceChange>,
+    /// Quoted symbols that are neither a product's symbol nor one of its aliases.
+    pub unmatched: Vec<String>,
+    /// Products that received no quote.
+    pub unpriced: Vec<String>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Ecs {
     pub data_path: PathBuf,
+    pub storage: StorageKind,
+    pub accounts: HashMap<Eid, Account>,
     pub lots: HashMap<Eid, Lot>,
     pub products: Vec<Product>,
+    pub realized: HashMap<Eid, RealizedGain>,
+    pub identical: IdenticalProducts,
+    pub tax: TaxSettings,
+    pub fx: FxTable,
+    pub currency: CurrencySettings,
 }
 
 #[derive(Error, Debug)]
@@ -33,29 +84,319 @@ pub enum DropError {
     WriteError(#[from] WriteError),
 }
 
+#[derive(Error, Debug)]
+pub enum InsertError {
+    #[error("Invalid lot: {0}")]
+    InvalidLot(#[from] LotError),
+
+    #[error("Unknown product: {0}")]
+    UnknownProduct(String),
+
+    #[error("Unknown account: {0:?}")]
+    UnknownAccount(Eid),
+
+    #[error("Write error: {0}")]
+    WriteError(#[from] WriteError),
+}
+
+#[derive(Error, Debug)]
+pub enum UpdateError {
+    #[error("Unknown lot: {0:?}")]
+    UnknownLot(Eid),
+
+    #[error("Invalid lot: {0}")]
+    InvalidLot(#[from] LotError),
+
+    #[error("Unknown product: {0}")]
+    UnknownProduct(String),
+
+    #[error("Unknown account: {0:?}")]
+    UnknownAccount(Eid),
+
+    #[error("Write error: {0}")]
+    WriteError(#[from] WriteError),
+}
+
+#[derive(Error, Debug)]
+pub enum ProductChangeError {
+    #[error("Unknown product: {0}")]
+    UnknownProduct(String),
+
+    #[error("Invalid product: {0}")]
+    InvalidProduct(#[from] ProductError),
+
+    #[error("A product with symbol {0} already exists")]
+    DuplicateSymbol(String),
+
+    #[error("The symbol of {0} cannot be changed")]
+    SymbolChanged(String),
+
+    #[error("{0} already names {1}")]
+    AliasConflict(String, String),
+
+    #[error("{0} is held in {1} lots")]
+    InUse(String, usize),
+
+    #[error("Write error: {0}")]
+    WriteError(#[from] WriteError),
+}
+
+#[derive(Error, Debug)]
+pub enum AccountChangeError {
+    #[error("Unknown account: {0:?}")]
+    UnknownAccount(Eid),
+
+    #[error("Invalid account: {0}")]
+    InvalidAccount(#[from] AccountError),
+
+    #[error("An account named {0} already exists")]
+    DuplicateName(String),
+
+    #[error("Account {0} still has lots or realized gains")]
+    InUse(String),
+
+    #[error("Write error: {0}")]
+    WriteError(#[from] WriteError),
+}
+
+impl Ecs {
+    pub fn query_accounts(&self) -> HashMap<Eid, Account> {
+        self.accounts.clone()
+    }
+    fn check_account(
+        &self,
+        eid: Option<&Eid>,
+        account: &Account,
+    ) -> Result<(), AccountChangeError> {
+        account.validate()?;
+        let name = account.name.trim();
+        let taken = self
+            .accounts
+            .iter()
+            .any(|(other, a)| Some(other) != eid && a.name.trim().eq_ignore_ascii_case(name));
+        if taken {
+            return Err(AccountChangeError::DuplicateName(name.to_string()));
+        }
+        Ok(())
+    }
+    pub fn insert_account(&mut self, account: Account) -> Result<Eid, AccountChangeError> {
+        self.check_account(None, &account)?;
+        let eid = Eid::random();
+        let mut accounts = self.accounts.clone();
+        accounts.insert(eid.clone(), account.clone());
+        self.storage()?
+            .save_accounts(&accounts, std::slice::from_ref(&eid))?;
+        self.record(Event::AccountAdded {
+            eid: eid.clone(),
+            account,
+        });
+        self.accounts = accounts;
+        Ok(eid)
+    }
+    pub fn update_account(&mut self, eid: Eid, account: Account) -> Result<(), AccountChangeError> {
+        let Some(before) = self.accounts.get(&eid).cloned() else {
+            return Err(AccountChangeError::UnknownAccount(eid));
+        };
+        self.check_account(Some(&eid), &account)?;
+        let mut accounts = self.accounts.clone();
+        accounts.insert(eid.clone(), account.clone());
+        self.storage()?
+            .save_accounts(&accounts, std::slice::from_ref(&eid))?;
+        self.record(Event::AccountUpdated {
+            eid,
+            before,
+            after: account,
+        });
+        self.accounts = accounts;
+        // The account's kind decides how its lots are taxed.
+        self.record_net_worth();
+        Ok(())
+    }
+    pub fn drop_account(&mut self, eid: Eid) -> Result<(), AccountChangeError> {
+        let Some(account) = self.accounts.get(&eid).cloned() else {
+            return Err(AccountChangeError::UnknownAccount(eid));
+        };
+        let in_use = self.lots.values().any(|lot| lot.account == eid)
+            || self.realized.values().any(|gain| gain.account == eid);
+        if in_use {
+            return Err(AccountChangeError::InUse(account.name));
+        }
+        let mut accounts = self.accounts.clone();
+        accounts.remove(&eid);
+        self.storage()?
+            .save_accounts(&accounts, std::slice::from_ref(&eid))?;
+        self.record(Event::AccountDropped { eid, account });
+        self.accounts = accounts;
+        Ok(())
+    }
+}
+
 impl Ecs {
     pub fn query_products(&self) -> Vec<Product> {
         self.products.clone()
     }
-    pub fn update_prices(&mut self, prices: impl AsRef<[MarketPrice]>) -> Result<(), DropError> {
+    pub fn price_history(&self) -> Result<PriceHistory, ReadError> {
+        self.storage.open(&self.data_path)?.load_price_history()
+    }
+    /// Sets each product's price from the quotes naming it by symbol or alias, and reports which
+    /// products and quotes were matched.
+    pub fn update_prices(
+        &mut self,
+        prices: impl AsRef<[MarketPrice]>,
+    ) -> Result<PriceUpdate, DropError> {
         let prices = prices.as_ref();
         let prices = prices
             .iter()
-            .map(|p| (p.symbol.clone(), p.share_price.clone()))
+            .map(|p| (p.symbol.to_uppercase(), p.share_price.clone()))
             .collect::<HashMap<_, _>>();
         let mut products = self.products.clone();
+        let mut update = PriceUpdate::default();
+        let mut matched = HashSet::new();
         for product in products.iter_mut() {
-            let symbol = product.symbol();
-            let price = if let Some(price) = prices.get(symbol) {
-                Some(price.clone())
-            } else {
-                prices.get(&format!("{}-USD", symbol)).cloned()
-            };
-            if let Some(price) = price {
-                product.set_share_price(price.clone());
+            let quoted = product
+                .symbols()
+                .map(str::to_uppercase)
+                .find(|quoted| prices.contains_key(quoted));
+            let price = quoted.map(|quoted| {
+                let price = prices[&quoted].clone();
+                matched.insert(quoted);
+                price
+            });
+            match price {
+                Some(price) => {
+                    update.matched.push(PriceChange {
+                        symbol: product.symbol().to_string(),
+                        before: product.share_price().clone(),
+                        after: price.clone(),
+                    });
+                    product.set_share_price(price);
+                }
+                None => update.unpriced.push(product.symbol().to_string()),
             }
         }
-        write_products(products.clone(), &self.data_path)?;
+        let changes = update
+            .matched
+            .iter()
+            .filter(|change| change.before != change.after)
+            .cloned()
+            .collect::<Vec<_>>();
+        let changed = changes
+            .iter()
+            .map(|c| c.symbol.as_str())
+            .collect::<Vec<_>>();
+        let storage = self.storage()?;
+        storage.save_products(&products, &changed)?;
+        // The prices being replaced go in too, so the history starts with the first known quote.
+        let quotes = self
+            .products
+            .iter()
+            .chain(products.iter())
+            .map(|product| MarketPrice {
+                symbol: product.symbol().to_string(),
+                share_price: product.share_price().clone(),
+            })
+            .collect::<Vec<_>>();
+        storage.append_prices(&quotes)?;
+        if !changes.is_empty() {
+            self.record(Event::PricesUpdated { changes });
+        }
+        self.products = products;
+        self.record_net_worth();
+        update.unmatched = prices
+            .into_keys()
+            .filter(|symbol| !matched.contains(symbol))
+            .collect();
+        update.unmatched.sort();
+        Ok(update)
+    }
+    /// Rejects a product whose symbol or aliases already name a different product, so that every
+    /// quote matches at most one product.
+    fn check_aliases(&self, product: &Product) -> Result<(), ProductChangeError> {
+        for name in product.symbols() {
+            if let Some(other) = self
+                .products
+                .iter()
+                .find(|other| other.symbol() != product.symbol() && other.matches_symbol(name))
+            {
+                return Err(ProductChangeError::AliasConflict(
+                    name.to_string(),
+                    other.symbol().to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+    pub fn insert_product(&mut self, product: Product) -> Result<(), ProductChangeError> {
+        product.validate()?;
+        if self.has_product(product.symbol()) {
+            return Err(ProductChangeError::DuplicateSymbol(
+                product.symbol().to_string(),
+            ));
+        }
+        self.check_aliases(&product)?;
+        let mut products = self.products.clone();
+        products.push(product.clone());
+        let storage = self.storage()?;
+        storage.save_products(&products, &[product.symbol()])?;
+        storage.append_prices(&[MarketPrice {
+            symbol: product.symbol().to_string(),
+            share_price: product.share_price().clone(),
+        }])?;
+        self.record(Event::ProductAdded { product });
+        self.products = products;
+        self.record_net_worth();
+        Ok(())
+    }
+    /// Replaces the product with the given symbol. The symbol itself cannot change, since lots
+    /// and realized gains refer to products by symbol.
+    pub fn update_product(
+        &mut self,
+        symbol: &str,
+        product: Product,
+    ) -> Result<(), ProductChangeError> {
+        let Some(position) = self.products.iter().position(|p| p.symbol() == symbol) else {
+            return Err(ProductChangeError::UnknownProduct(symbol.to_string()));
+        };
+        if product.symbol() != symbol {
+            return Err(ProductChangeError::SymbolChanged(symbol.to_string()));
+        }
+        product.validate()?;
+        self.check_aliases(&product)?;
+        let before = self.products[position].clone();
+        let mut products = self.products.clone();
+        products[position] = product.clone();
+        let storage = self.storage()?;
+        storage.save_products(&products, &[symbol])?;
+        if before.share_price() != product.share_price() {
+            storage.append_prices(&[MarketPrice {
+                symbol: symbol.to_string(),
+                share_price: product.share_price().clone(),
+            }])?;
+        }
+        self.record(Event::ProductUpdated {
+            before,
+            after: product,
+        });
+        self.products = products;
+        self.record_net_worth();
+        Ok(())
+    }
+    pub fn drop_product(&mut self, symbol: &str) -> Result<(), ProductChangeError> {
+        let Some(position) = self.products.iter().position(|p| p.symbol() == symbol) else {
+            return Err(ProductChangeError::UnknownProduct(symbol.to_string()));
+        };
+        let lots = self
+            .lots
+            .values()
+            .filter(|lot| lot.product == symbol)
+            .count();
+        if lots > 0 {
+            return Err(ProductChangeError::InUse(symbol.to_string(), lots));
+        }
+        let mut products = self.products.clone();
+        let product = products.remove(position);
+        self.storage()?.save_products(&products, &[symbol])?;
+        self.record(Event::ProductDropped { position, product });
         self.products = products;
         Ok(())
     }
@@ -71,32 +412,452 @@ impl Ecs {
             .map(|(eid, lot)| LotItem(lot.clone(), eid.clone()))
             .collect()
     }
+    fn has_product(&self, symbol: &str) -> bool {
+        self.products.iter().any(|p| p.symbol() == symbol)
+    }
+    pub fn insert_lot(&mut self, lot: Lot) -> Result<Eid, InsertError> {
+        lot.validate()?;
+        if !self.has_product(&lot.product) {
+            return Err(InsertError::UnknownProduct(lot.product));
+        }
+        if !self.accounts.contains_key(&lot.account) {
+            return Err(InsertError::UnknownAccount(lot.account));
+        }
+        let eid = Eid::random();
+        let mut lots = self.lots.clone();
+        lots.insert(eid.clone(), lot.clone());
+        self.storage()?
+            .save_lots(&lots, std::slice::from_ref(&eid))?;
+        self.record(Event::LotAdded {
+            eid: eid.clone(),
+            lot,
+        });
+        self.lots = lots;
+        self.record_net_worth();
+        Ok(eid)
+    }
+    /// Adds several lots as one change, or none of them if any is invalid.
+    pub fn insert_lots(&mut self, new_lots: Vec<Lot>) -> Result<Vec<Eid>, InsertError> {
+        for lot in new_lots.iter() {
+            lot.validate()?;
+            if !self.has_product(&lot.product) {
+                return Err(InsertError::UnknownProduct(lot.product.clone()));
+            }
+            if !self.accounts.contains_key(&lot.account) {
+                return Err(InsertError::UnknownAccount(lot.account.clone()));
+            }
+        }
+        let added = new_lots
+            .into_iter()
+            .map(|lot| (Eid::random(), lot))
+            .collect::<HashMap<_, _>>();
+        let eids = added.keys().cloned().collect::<Vec<_>>();
+        let mut lots = self.lots.clone();
+        lots.extend(added.clone());
+        self.storage()?.save_lots(&lots, &eids)?;
+        self.record(Event::LotsImported { lots: added });
+        self.lots = lots;
+        self.record_net_worth();
+        Ok(eids)
+    }
+    pub fn update_lot(&mut self, eid: Eid, lot: Lot) -> Result<(), UpdateError> {
+        let Some(before) = self.lots.get(&eid).cloned() else {
+            return Err(UpdateError::UnknownLot(eid));
+        };
+        lot.validate()?;
+        if !self.has_product(&lot.product) {
+            return Err(UpdateError::UnknownProduct(lot.product));
+        }
+        if !self.accounts.contains_key(&lot.account) {
+            return Err(UpdateError::UnknownAccount(lot.account));
+        }
+        let mut lots = self.lots.clone();
+        lots.insert(eid.clone(), lot.clone());
+        self.storage()?
+            .save_lots(&lots, std::slice::from_ref(&eid))?;
+        self.record(Event::LotUpdated {
+            eid,
+            before,
+            after: lot,
+        });
+        self.lots = lots;
+        self.record_net_worth();
+        Ok(())
+    }
     pub fn drop_lot(&mut self, eid: Eid) -> Result<(), DropError> {
         let mut lots = self.lots.clone();
-        lots.remove(&eid);
-        write_lots(lots.clone(), &self.data_path)?;
+        let dropped = lots.remove(&eid);
+        self.storage()?
+            .save_lots(&lots, std::slice::from_ref(&eid))?;
+        if let Some(lot) = dropped {
+            self.record(Event::LotDropped { eid, lot });
+        }
         self.lots = lots;
+        self.record_net_worth();
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SellError {
+    #[error("Sale error: {0}")]
+    SaleError(#[from] SaleError),
+
+    #[error("Write error: {0}")]
+    WriteError(#[from] WriteError),
+}
+
+impl Ecs {
+    pub fn query_realized(&self) -> HashMap<Eid, RealizedGain> {
+        self.realized.clone()
+    }
+    pub fn wash_sales(&self) -> Vec<WashSale> {
+        term::wash_sales(&self.realized, &self.lots, &self.accounts, &self.identical)
+    }
+    /// Relieves lots for a sale and records the realized gains, returning their ids.
+    pub fn sell(&mut self, sale: Sale, method: ReliefMethod) -> Result<Vec<Eid>, SellError> {
+        let outcome = sale.relieve(&self.lots, &method)?;
+        let mut realized = self.realized.clone();
+        let mut sold = HashMap::new();
+        for gain in outcome.realized {
+            sold.insert(Eid::random(), gain);
+        }
+        realized.extend(sold.clone());
+        let before = sold
+            .values()
+            .map(|gain| (gain.lot.clone(), self.lots[&gain.lot].clone()))
+            .collect::<HashMap<_, _>>();
+        let after = before
+            .keys()
+            .filter_map(|eid| Some((eid.clone(), outcome.lots.get(eid)?.clone())))
+            .collect::<HashMap<_, _>>();
+        let eids = sold.keys().cloned().collect::<Vec<_>>();
+        let relieved = before.keys().cloned().collect::<Vec<_>>();
+        let storage = self.storage()?;
+        // Lots are relieved first and put back if the gains cannot be saved, so a failed sale
+        // never leaves realized gains for shares that are still held.
+        storage.save_lots(&outcome.lots, &relieved)?;
+        if let Err(e) = storage.save_realized(&realized, &eids) {
+            storage.save_lots(&self.lots, &relieved)?;
+            return Err(e.into());
+        }
+        self.record(Event::LotsSold {
+            before,
+            after,
+            realized: sold,
+        });
+        self.realized = realized;
+        self.lots = outcome.lots;
+        self.record_net_worth();
+        Ok(eids)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum UndoError {
+    #[error("Journal error: {0}")]
+    JournalError(#[from] JournalError),
+
+    #[error("Write error: {0}")]
+    WriteError(#[from] WriteError),
+}
+
+impl Ecs {
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            lots: self.lots.clone(),
+            products: self.products.clone(),
+            realized: self.realized.clone(),
+            accounts: self.accounts.clone(),
+        }
+    }
+    /// Journals a change. Call after writing the change but before updating `self`, so a new
+    /// journal's baseline is the state the change was made to. The change is already stored by
+    /// then, so a journal that cannot be written is logged rather than failing it.
+    fn record(&self, event: Event) {
+        let path = journal_path(&self.data_path);
+        if let Err(e) = journal::append_event(&path, || self.snapshot(), event) {
+            warn!(
+                "Failed to journal a change to {}: {}",
+                self.data_path.display(),
+                e
+            );
+        }
+    }
+    fn storage(&self) -> Result<Box<dyn Storage>, WriteError> {
+        Ok(self.storage.open(&self.data_path)?)
+    }
+    fn restore(&mut self, snapshot: Snapshot) -> Result<(), WriteError> {
+        let storage = self.storage()?;
+        let accounts = changed_keys(&self.accounts, &snapshot.accounts);
+        storage.save_accounts(&snapshot.accounts, &accounts)?;
+        let lots = changed_keys(&self.lots, &snapshot.lots);
+        storage.save_lots(&snapshot.lots, &lots)?;
+        let symbols = self
+            .products
+            .iter()
+            .chain(snapshot.products.iter())
+            .map(|p| p.symbol())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        storage.save_products(&snapshot.products, &symbols)?;
+        let realized = changed_keys(&self.realized, &snapshot.realized);
+        storage.save_realized(&snapshot.realized, &realized)?;
+        self.accounts = snapshot.accounts;
+        self.lots = snapshot.lots;
+        self.products = snapshot.products;
+        self.realized = snapshot.realized;
+        self.record_net_worth();
+        Ok(())
+    }
+    /// Adds a net worth snapshot when the total or its currency differs from the last one
+    /// recorded. The change it follows is already stored, so a snapshot that cannot be written is
+    /// logged rather than failing it.
+    fn record_net_worth(&self) {
+        if let Err(e) = self.append_net_worth() {
+            warn!(
+                "Failed to record net worth for {}: {}",
+                self.data_path.display(),
+                e
+            );
+        }
+    }
+    fn append_net_worth(&self) -> Result<(), WriteError> {
+        let storage = self.storage()?;
+        let report = NetWorthReport::new(
+            &self.lots(),
+            &self.products,
+            &self.accounts,
+            &self.tax,
+            &self.fx,
+            &self.currency.reporting_currency,
+        );
+        let snapshot = NetWorthSnapshot {
+            time: Utc::now(),
+            pre_tax: report.pre_tax,
+            currency: report.currency,
+        };
+        let history = storage.load_net_worth_history()?;
+        if history.last().is_some_and(|last| {
+            (last.pre_tax, &last.currency) == (snapshot.pre_tax, &snapshot.currency)
+        }) {
+            return Ok(());
+        }
+        storage.append_net_worth(&snapshot)?;
+        Ok(())
+    }
+    pub fn update_tax_settings(&mut self, tax: TaxSettings) -> Result<(), WriteError> {
+        self.storage()?.save_tax_settings(&tax)?;
+        self.tax = tax;
+        Ok(())
+    }
+    /// Records exchange rates, keeping any newer rate already known for a pair, and returns the
+    /// rates that changed.
+    pub fn update_fx_rates(&mut self, rates: Vec<FxRate>) -> Result<Vec<FxRate>, WriteError> {
+        let mut fx = self.fx.clone();
+        let updated = rates
+            .into_iter()
+            .filter(|rate| fx.insert(rate.clone()))
+            .collect::<Vec<_>>();
+        if updated.is_empty() {
+            return Ok(updated);
+        }
+        self.storage()?.save_fx_rates(&fx)?;
+        self.fx = fx;
+        self.record_net_worth();
+        Ok(updated)
+    }
+    pub fn update_currency_settings(
+        &mut self,
+        currency: CurrencySettings,
+    ) -> Result<(), WriteError> {
+        self.storage()?.save_currency_settings(&currency)?;
+        self.currency = currency;
+        Ok(())
+    }
+    pub fn net_worth_history(&self) -> Result<Vec<NetWorthSnapshot>, ReadError> {
+        self.storage.open(&self.data_path)?.load_net_worth_history()
+    }
+    pub fn journal(&self) -> Result<Vec<JournalEntry>, JournalError> {
+        journal::read_journal(&journal_path(&self.data_path))
+    }
+    /// Reverts the newest change not yet undone and returns it, or `None` if there is none.
+    pub fn undo_last(&mut self) -> Result<Option<JournalEntry>, UndoError> {
+        let entries = self.journal()?;
+        let Some(entry) = journal::last_undoable(&entries).cloned() else {
+            return Ok(None);
+        };
+        let mut snapshot = self.snapshot();
+        entry.event.revert(&mut snapshot);
+        self.restore(snapshot)?;
+        if let Event::PricesUpdated { changes } = &entry.event {
+            self.revert_price_history(changes)?;
+        }
+        self.record(Event::Undone { entry: entry.seq });
+        Ok(Some(entry))
+    }
+    /// Takes the quotes an undone price update added out of the history, putting back the ones
+    /// they replaced.
+    fn revert_price_history(&self, changes: &[PriceChange]) -> Result<(), WriteError> {
+        let quotes = |price: fn(&PriceChange) -> &SharePrice| {
+            changes
+                .iter()
+                .map(|change| MarketPrice {
+                    symbol: change.symbol.clone(),
+                    share_price: price(change).clone(),
+                })
+                .collect::<Vec<_>>()
+        };
+        let storage = self.storage()?;
+        storage.remove_prices(&quotes(|change| &change.after))?;
+        storage.append_prices(&quotes(|change| &change.before))?;
+        Ok(())
+    }
+    /// Replaces the stored data with the state rebuilt from the journal.
+    pub fn rebuild_from_journal(&mut self) -> Result<(), UndoError> {
+        let entries = self.journal()?;
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let snapshot = journal::replay(&entries)?;
+        self.restore(snapshot)?;
         Ok(())
     }
 }
 
+/// Keys present in either map, for saving every row that may differ between them.
+fn changed_keys<T: PartialEq>(before: &HashMap<Eid, T>, after: &HashMap<Eid, T>) -> Vec<Eid> {
+    before
+        .keys()
+        .chain(after.keys())
+        .filter(|eid| before.get(eid) != after.get(eid))
+        .cloned()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect()
+}
+
 #[derive(Error, Debug)]
 pub enum ConnectError {
     #[error("Read lots error: {0}")]
     ReadError(#[from] ReadError),
+
+    #[error("Migrate accounts error: {0}")]
+    WriteError(#[from] WriteError),
 }
 
 impl Ecs {
     pub fn connect(data_path: impl AsRef<Path>) -> Result<Self, ConnectError> {
         let data_path = data_path.as_ref();
-        let lots = read_lots(data_path)?;
-        let products = read_products(data_path)?;
+        let storage = StorageKind::detect(data_path);
+        let store = storage.open(data_path).map_err(ReadError::from)?;
+        let Snapshot {
+            lots,
+            products,
+            realized,
+            accounts,
+        } = store.load()?;
+        let identical = read_identical(data_path)?;
+        let tax = store.load_tax_settings()?;
+        let fx = store.load_fx_rates()?;
+        let currency = store.load_currency_settings()?;
         Ok(Self {
             data_path: data_path.to_owned(),
+            storage,
+            accounts,
             lots,
             products,
+            realized,
+            identical,
+            tax,
+            fx,
+            currency,
         })
     }
+    /// Lots and realized gains used to name their account with free-form text. Returns those
+    /// that do not reference a known account.
+    fn legacy_accounts(&self) -> HashSet<Eid> {
+        self.lots
+            .values()
+            .map(|lot| &lot.account)
+            .chain(self.realized.values().map(|gain| &gain.account))
+            .filter(|eid| !self.accounts.contains_key(eid))
+            .cloned()
+            .collect()
+    }
+    pub fn has_legacy_accounts(&self) -> bool {
+        !self.legacy_accounts().is_empty() || !self.tax.legacy_treatments.is_empty()
+    }
+    /// Gives each legacy account an account, matched by name or created with the tax treatment
+    /// formerly configured for that name. This writes to storage, so callers must hold the
+    /// user's lock.
+    pub fn migrate_accounts(&mut self) -> Result<(), WriteError> {
+        if !self.has_legacy_accounts() {
+            return Ok(());
+        }
+        let legacy = self.legacy_accounts();
+        let mut accounts = self.accounts.clone();
+        let mut remap = HashMap::new();
+        for old in legacy {
+            let name = old.as_str().trim();
+            let existing = accounts
+                .iter()
+                .find(|(_, account)| account.name.trim().eq_ignore_ascii_case(name))
+                .map(|(eid, _)| eid.clone());
+            let eid = existing.unwrap_or_else(|| {
+                let kind = self
+                    .tax
+                    .legacy_treatments
+                    .get(name)
+                    .copied()
+                    .map(AccountKind::from)
+                    .unwrap_or_default();
+                let eid = Eid::random();
+                accounts.insert(
+                    eid.clone(),
+                    Account {
+                        name: name.to_string(),
+                        kind,
+                    },
+                );
+                eid
+            });
+            remap.insert(old, eid);
+        }
+        let mut lots = self.lots.clone();
+        for lot in lots.values_mut() {
+            if let Some(eid) = remap.get(&lot.account) {
+                lot.account = eid.clone();
+            }
+        }
+        let mut realized = self.realized.clone();
+        for gain in realized.values_mut() {
+            if let Some(eid) = remap.get(&gain.account) {
+                gain.account = eid.clone();
+            }
+        }
+        let storage = self.storage()?;
+        storage.save_accounts(&accounts, &changed_keys(&self.accounts, &accounts))?;
+        storage.save_lots(&lots, &changed_keys(&self.lots, &lots))?;
+        storage.save_realized(&realized, &changed_keys(&self.realized, &realized))?;
+        let mut tax = self.tax.clone();
+        tax.legacy_treatments.clear();
+        storage.save_tax_settings(&tax)?;
+        self.accounts = accounts;
+        self.lots = lots;
+        self.realized = realized;
+        self.tax = tax;
+        // An existing journal starts over from the migrated state; a new one records it when the
+        // first change is made.
+        let path = journal_path(&self.data_path);
+        if path.exists() {
+            self.record(Event::Baseline {
+                snapshot: self.snapshot(),
+            });
+        }
+        Ok(())
+    }
 }
 
 #[derive(Error, Debug)]
@@ -106,6 +867,16 @@ pub enum ReadError {
 
     #[error("Portfolio parse error: {0}")]
     DataParseError(#[from] data::ParseError),
+
+    #[error("Serde error: {0}")]
+    SerdeJsonError(#[from] serde_json::Error),
+
+    #[error("Storage error: {0}")]
+    StorageError(#[from] StorageError),
+
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error: {0}")]
+    SqliteError(#[from] rusqlite::Error),
 }
 
 #[derive(Error, Debug)]
@@ -115,42 +886,425 @@ pub enum WriteError {
 
     #[error("IO error: {0}")]
     IOError(#[from] std::io::Error),
-}
 
-fn read_products(data_path: &Path) -> Result<Vec<Product>, ReadError> {
-    let path = products_csv_path(data_path);
-    let bytes = std::fs::read(path)?;
-    let products = market::parse_products(&bytes)?;
-    Ok(products)
-}
+    #[error("Journal error: {0}")]
+    JournalError(#[from] JournalError),
+
+    #[error("Read error: {0}")]
+    ReadError(#[from] ReadError),
+
+    #[error("Serde error: {0}")]
+    SerdeJsonError(#[from] serde_json::Error),
 
-fn write_products(products: Vec<Product>, data_path: &Path) -> Result<(), WriteError> {
-    let path = products_csv_path(data_path);
-    let string = market::format_products(products)?;
-    std::fs::write(path, string.as_bytes())?;
-    Ok(())
+    #[error("Storage error: {0}")]
+    StorageError(#[from] StorageError),
+
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error: {0}")]
+    SqliteError(#[from] rusqlite::Error),
 }
 
-fn read_lots(data_path: &Path) -> Result<HashMap<Eid, Lot>, ReadError> {
-    let path = lots_csv_path(data_path);
+/// Substantially identical products are optional configuration kept in `identical.csv`, whatever
+/// the storage.
+fn read_identical(data_path: &Path) -> Result<IdenticalProducts, ReadError> {
+    let path = identical_csv_path(data_path);
+    if !path.exists() {
+        return Ok(IdenticalProducts::default());
+    }
     let bytes = std::fs::read(path)?;
-    let lots = portfolio::parse_lots(&bytes)?;
-    Ok(lots)
+    let identical = term::parse_identical_products(&bytes)?;
+    Ok(identical)
 }
 
-fn write_lots(lots: HashMap<Eid, Lot>, data_path: &Path) -> Result<(), WriteError> {
-    let path = lots_csv_path(data_path);
-    let string = portfolio::format_lots(lots)?;
-    std::fs::write(path, string.as_bytes())?;
-    Ok(())
+fn journal_path(data_path: &Path) -> PathBuf {
+    data_path.join("journal.jsonl")
 }
 
-fn products_csv_path(data_path: &Path) -> PathBuf {
-    let path = data_path.join("products.csv");
-    path
+fn identical_csv_path(data_path: &Path) -> PathBuf {
+    data_path.join("identical.csv")
 }
 
-fn lots_csv_path(data_path: &Path) -> PathBuf {
-    let path = data_path.join("lots.csv");
-    path
+#[cfg(test)]
+mod tests {
+    use super::scratch::ScratchDir;
+    use super::storage::csv::{
+        accounts_csv_path, lots_csv_path, net_worth_csv_path, products_csv_path, realized_csv_path,
+    };
+    use super::*;
+    use crate::data::market;
+    use chrono::{TimeZone, Utc};
+    use rust_decimal_macros::dec;
+
+    const PRODUCTS_CSV: &str =
+        "type,symbol,name,outstanding_shares,share_price,share_price_as_of\n\
+        stock,AAPL,Apple Inc.,100,123.45,2021-01-01T00:00:00Z\n";
+    const LOTS_CSV: &str = "account,time,product,quantity,eid\n\
+        foo,2021-01-01T00:00:00Z,AAPL,100,l0\n";
+    const ACCOUNTS_CSV: &str = "name,kind,eid\nFoo,taxable,foo\n";
+
+    /// An `Ecs` over sample data in a scratch directory, which lasts as long as the returned guard.
+    fn scratch_ecs() -> (ScratchDir, Ecs) {
+        let dir = ScratchDir::create();
+        std::fs::write(products_csv_path(dir.path()), PRODUCTS_CSV).unwrap();
+        std::fs::write(lots_csv_path(dir.path()), LOTS_CSV).unwrap();
+        std::fs::write(accounts_csv_path(dir.path()), ACCOUNTS_CSV).unwrap();
+        let ecs = Ecs::connect(dir.path()).unwrap();
+        (dir, ecs)
+    }
+
+    fn new_lot() -> Lot {
+        Lot {
+            account: "foo".into(),
+            time: Utc.with_ymd_and_hms(2022, 1, 1, 0, 0, 0).unwrap(),
+            product: "AAPL".into(),
+            quantity: dec!(5),
+            unit_cost: None,
+            fees: None,
+        }
+    }
+
+    #[test]
+    fn test_undo_and_rebuild_from_journal() {
+        let (_dir, mut ecs) = scratch_ecs();
+        let eid = ecs.insert_lot(new_lot()).unwrap();
+        ecs.drop_lot(Eid::from("l0")).unwrap();
+        assert_eq!(ecs.lots.keys().collect::<Vec<_>>(), vec![&eid]);
+
+        let undone = ecs.undo_last().unwrap().unwrap();
+        assert!(matches!(undone.event, Event::LotDropped { .. }));
+        assert_eq!(Ecs::connect(&ecs.data_path).unwrap().lots.len(), 2);
+
+        ecs.undo_last().unwrap().unwrap();
+        assert_eq!(ecs.undo_last().unwrap(), None);
+        let mut reconnected = Ecs::connect(&ecs.data_path).unwrap();
+        assert_eq!(
+            reconnected.lots.keys().collect::<Vec<_>>(),
+            vec![&Eid::from("l0")]
+        );
+
+        std::fs::write(
+            lots_csv_path(&ecs.data_path),
+            "account,time,product,quantity,eid\n",
+        )
+        .unwrap();
+        reconnected.rebuild_from_journal().unwrap();
+        assert_eq!(Ecs::connect(&ecs.data_path).unwrap().lots, ecs.lots);
+    }
+
+    #[test]
+    fn test_fx_rates_convert_net_worth() {
+        let (_dir, mut ecs) = scratch_ecs();
+        ecs.update_currency_settings(CurrencySettings {
+            reporting_currency: "EUR".into(),
+        })
+        .unwrap();
+        let rate = FxRate {
+            base: "EUR".into(),
+            quote: "USD".into(),
+            rate: dec!(1.25),
+            time: Utc.with_ymd_and_hms(2021, 1, 2, 0, 0, 0).unwrap(),
+        };
+        assert_eq!(ecs.update_fx_rates(vec![rate.clone()]).unwrap().len(), 1);
+        assert!(ecs.update_fx_rates(vec![rate]).unwrap().is_empty());
+        let history = ecs.net_worth_history().unwrap();
+        assert_eq!(history.last().unwrap().pre_tax, dec!(9876));
+        assert_eq!(history.last().unwrap().currency, "EUR");
+        let reconnected = Ecs::connect(&ecs.data_path).unwrap();
+        assert_eq!(reconnected.fx, ecs.fx);
+        assert_eq!(reconnected.currency.reporting_currency, "EUR");
+    }
+
+    #[test]
+    fn test_update_prices_appends_history() {
+        let (_dir, mut ecs) = scratch_ecs();
+        let quote = |day, height| MarketPrice {
+            symbol: "AAPL".into(),
+            share_price: market::SharePrice {
+                height,
+                time: Utc.with_ymd_and_hms(2021, 1, day, 0, 0, 0).unwrap(),
+            },
+        };
+        ecs.update_prices([quote(2, dec!(130))]).unwrap();
+        ecs.update_prices([quote(3, dec!(125))]).unwrap();
+        ecs.update_prices([quote(3, dec!(125))]).unwrap();
+        let history = ecs.price_history().unwrap();
+        let heights = history
+            .series("AAPL")
+            .iter()
+            .map(|price| price.height)
+            .collect::<Vec<_>>();
+        assert_eq!(heights, vec![dec!(123.45), dec!(130), dec!(125)]);
+        let as_of = Utc.with_ymd_and_hms(2021, 1, 2, 12, 0, 0).unwrap();
+        assert_eq!(
+            history.price_as_of("AAPL", as_of).unwrap().height,
+            dec!(130)
+        );
+    }
+
+    #[test]
+    fn test_undo_prices_removes_history() {
+        let (_dir, mut ecs) = scratch_ecs();
+        let quote = |day, height| MarketPrice {
+            symbol: "AAPL".into(),
+            share_price: SharePrice {
+                height,
+                time: Utc.with_ymd_and_hms(2021, 1, day, 0, 0, 0).unwrap(),
+            },
+        };
+        ecs.update_prices([quote(2, dec!(130))]).unwrap();
+        ecs.update_prices([quote(3, dec!(125))]).unwrap();
+        ecs.undo_last().unwrap().unwrap();
+        let heights = |ecs: &Ecs| {
+            ecs.price_history()
+                .unwrap()
+                .series("AAPL")
+                .iter()
+                .map(|price| price.height)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(heights(&ecs), vec![dec!(123.45), dec!(130)]);
+        assert_eq!(ecs.products[0].share_price().height, dec!(130));
+        ecs.undo_last().unwrap().unwrap();
+        assert_eq!(heights(&ecs), vec![dec!(123.45)]);
+    }
+
+    #[test]
+    fn test_update_prices_through_aliases() {
+        let (_dir, mut ecs) = scratch_ecs();
+        let mut brk = Product::new(
+            market::ProductKind::Stock,
+            "BRK.B".into(),
+            "Berkshire Hathaway".into(),
+            1,
+            ecs.products[0].share_price().clone(),
+        );
+        brk.set_aliases(vec!["BRK-B".into()]);
+        ecs.insert_product(brk.clone()).unwrap();
+        let mut clash = brk.clone();
+        clash.set_aliases(vec!["AAPL".into()]);
+        assert!(matches!(
+            ecs.update_product("BRK.B", clash),
+            Err(ProductChangeError::AliasConflict(alias, other)) if alias == "AAPL" && other == "AAPL"
+        ));
+        let quote = |symbol: &str, height| MarketPrice {
+            symbol: symbol.into(),
+            share_price: market::SharePrice {
+                height,
+                time: Utc.with_ymd_and_hms(2021, 1, 2, 0, 0, 0).unwrap(),
+            },
+        };
+        let update = ecs
+            .update_prices([
+                quote("brk-b", dec!(410)),
+                quote("AAPL-USD", dec!(1)),
+                quote("AAPL", dec!(130)),
+            ])
+            .unwrap();
+        assert_eq!(update.unmatched, vec!["AAPL-USD".to_string()]);
+        assert!(update.unpriced.is_empty());
+        assert_eq!(update.matched[1].symbol, "BRK.B");
+        assert_eq!(update.matched[1].before.height, dec!(123.45));
+        assert_eq!(update.matched[1].after.height, dec!(410));
+        let update = ecs.update_prices([quote("AAPL", dec!(130))]).unwrap();
+        assert_eq!(update.matched[0].before, update.matched[0].after);
+        assert_eq!(update.unpriced, vec!["BRK.B".to_string()]);
+        assert_eq!(ecs.products[0].share_price().height, dec!(130));
+        assert_eq!(ecs.products[1].share_price().height, dec!(410));
+    }
+
+    #[test]
+    fn test_changes_record_net_worth() {
+        let (_dir, mut ecs) = scratch_ecs();
+        std::fs::write(
+            net_worth_csv_path(&ecs.data_path),
+            "time,pre_tax\n2021-01-01T00:00:00Z,12345\n",
+        )
+        .unwrap();
+        let eid = ecs.insert_lot(new_lot()).unwrap();
+        ecs.update_lot(eid.clone(), new_lot()).unwrap();
+        ecs.drop_lot(eid).unwrap();
+        let values = ecs
+            .net_worth_history()
+            .unwrap()
+            .iter()
+            .map(|snapshot| snapshot.pre_tax)
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![dec!(12345), dec!(12962.25), dec!(12345)]);
+    }
+
+    #[test]
+    fn test_failed_sale_keeps_lots() {
+        let (_dir, mut ecs) = scratch_ecs();
+        // A directory in the way of realized.csv makes saving the gains fail.
+        std::fs::create_dir(realized_csv_path(&ecs.data_path)).unwrap();
+        let sale = Sale {
+            account: "foo".into(),
+            product: "AAPL".into(),
+            time: Utc.with_ymd_and_hms(2022, 1, 1, 0, 0, 0).unwrap(),
+            quantity: dec!(40),
+            unit_price: dec!(150),
+            fees: None,
+        };
+        assert!(ecs.sell(sale.clone(), ReliefMethod::Fifo).is_err());
+        assert_eq!(ecs.lots[&Eid::from("l0")].quantity, dec!(100));
+        std::fs::remove_dir(realized_csv_path(&ecs.data_path)).unwrap();
+        let mut reconnected = Ecs::connect(&ecs.data_path).unwrap();
+        assert_eq!(reconnected.lots, ecs.lots);
+        assert!(reconnected.realized.is_empty());
+
+        reconnected.sell(sale, ReliefMethod::Fifo).unwrap();
+        let reconnected = Ecs::connect(&ecs.data_path).unwrap();
+        assert_eq!(reconnected.lots[&Eid::from("l0")].quantity, dec!(60));
+        assert_eq!(reconnected.realized.len(), 1);
+    }
+
+    #[test]
+    fn test_net_worth_failure_keeps_change() {
+        let (_dir, mut ecs) = scratch_ecs();
+        // A directory in the way of net_worth.csv makes recording the snapshot fail.
+        std::fs::create_dir(net_worth_csv_path(&ecs.data_path)).unwrap();
+        let eid = ecs.insert_lot(new_lot()).unwrap();
+        assert!(Ecs::connect(&ecs.data_path)
+            .unwrap()
+            .lots
+            .contains_key(&eid));
+    }
+
+    #[test]
+    fn test_insert_lot_keeps_previous_lots() {
+        let (_dir, mut ecs) = scratch_ecs();
+        ecs.insert_lot(new_lot()).unwrap();
+        let backup = std::fs::read_to_string(ecs.data_path.join("lots.csv.bak")).unwrap();
+        assert_eq!(backup, LOTS_CSV);
+        let reconnected = Ecs::connect(&ecs.data_path).unwrap();
+        assert_eq!(reconnected.lots.len(), 2);
+    }
+
+    #[test]
+    fn test_account_changes() {
+        let (_dir, mut ecs) = scratch_ecs();
+        let ira = Account {
+            name: "IRA".into(),
+            kind: AccountKind::Ira,
+        };
+        let eid = ecs.insert_account(ira.clone()).unwrap();
+        assert!(matches!(
+            ecs.insert_account(ira),
+            Err(AccountChangeError::DuplicateName(_))
+        ));
+        let mut lot = new_lot();
+        lot.account = Eid::from("missing");
+        assert!(matches!(
+            ecs.insert_lot(lot),
+            Err(InsertError::UnknownAccount(_))
+        ));
+        assert!(matches!(
+            ecs.drop_account(Eid::from("foo")),
+            Err(AccountChangeError::InUse(_))
+        ));
+        ecs.drop_account(eid).unwrap();
+        assert_eq!(Ecs::connect(&ecs.data_path).unwrap().accounts.len(), 1);
+        ecs.undo_last().unwrap().unwrap();
+        assert_eq!(ecs.accounts.len(), 2);
+    }
+
+    #[test]
+    fn test_migrate_legacy_accounts() {
+        let scratch = ScratchDir::create();
+        let dir = scratch.path();
+        std::fs::write(products_csv_path(dir), PRODUCTS_CSV).unwrap();
+        std::fs::write(
+            lots_csv_path(dir),
+            "account,time,product,quantity,eid\n\
+            IRA,2021-01-01T00:00:00Z,AAPL,10,l0\n\
+            Brokerage,2021-01-01T00:00:00Z,AAPL,20,l1\n\
+            IRA,2021-02-01T00:00:00Z,AAPL,30,l2\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("tax_settings.json"),
+            r#"{"federal":{"short_term":0,"long_term":0},"state":{"short_term":0,"long_term":0},"accounts":{"IRA":"tax_deferred"}}"#,
+        )
+        .unwrap();
+        let lots = std::fs::read_to_string(lots_csv_path(dir)).unwrap();
+        let mut ecs = Ecs::connect(dir).unwrap();
+        assert!(ecs.has_legacy_accounts());
+        assert!(ecs.accounts.is_empty());
+        assert_eq!(std::fs::read_to_string(lots_csv_path(dir)).unwrap(), lots);
+        ecs.migrate_accounts().unwrap();
+        assert!(!ecs.has_legacy_accounts());
+        let kinds = ecs
+            .accounts
+            .values()
+            .map(|account| (account.name.as_str(), account.kind))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(
+            kinds,
+            HashMap::from([
+                ("IRA", AccountKind::Ira),
+                ("Brokerage", AccountKind::Taxable)
+            ])
+        );
+        assert_eq!(
+            ecs.lots[&Eid::from("l0")].account,
+            ecs.lots[&Eid::from("l2")].account
+        );
+        assert!(ecs.tax.legacy_treatments.is_empty());
+        let reconnected = Ecs::connect(dir).unwrap();
+        assert_eq!(reconnected.accounts, ecs.accounts);
+        assert_eq!(reconnected.lots, ecs.lots);
+    }
+
+    #[test]
+    fn test_product_changes() {
+        let (_dir, mut ecs) = scratch_ecs();
+        let share_price = market::SharePrice {
+            height: dec!(10),
+            time: Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap(),
+        };
+        let etf = Product::Etf {
+            symbol: "VTI".into(),
+            name: "Vanguard Total Stock Market ETF".into(),
+            aliases: Vec::new(),
+            currency: "USD".into(),
+            share_price,
+        };
+        ecs.insert_product(etf.clone()).unwrap();
+        assert!(matches!(
+            ecs.insert_product(etf.clone()),
+            Err(ProductChangeError::DuplicateSymbol(_))
+        ));
+        let mut repriced = etf.clone();
+        repriced.set_share_price(market::SharePrice {
+            height: dec!(11),
+            time: Utc.with_ymd_and_hms(2021, 1, 2, 0, 0, 0).unwrap(),
+        });
+        ecs.update_product("VTI", repriced).unwrap();
+        assert_eq!(ecs.price_history().unwrap().series("VTI").len(), 2);
+        assert!(matches!(
+            ecs.drop_product("AAPL"),
+            Err(ProductChangeError::InUse(_, 1))
+        ));
+        ecs.drop_product("VTI").unwrap();
+        assert_eq!(Ecs::connect(&ecs.data_path).unwrap().products.len(), 1);
+        ecs.undo_last().unwrap().unwrap();
+        assert_eq!(ecs.products[1].share_price().height, dec!(11));
+    }
+
+    #[test]
+    fn test_insert_lots_all_or_nothing() {
+        let (_dir, mut ecs) = scratch_ecs();
+        let mut unknown = new_lot();
+        unknown.product = "MSFT".into();
+        assert!(matches!(
+            ecs.insert_lots(vec![new_lot(), unknown]),
+            Err(InsertError::UnknownProduct(_))
+        ));
+        assert_eq!(ecs.lots.len(), 1);
+        let eids = ecs.insert_lots(vec![new_lot(), new_lot()]).unwrap();
+        assert_eq!(Ecs::connect(&ecs.data_path).unwrap().lots.len(), 3);
+        let undone = ecs.undo_last().unwrap().unwrap();
+        assert!(matches!(undone.event, Event::LotsImported { lots } if lots.len() == eids.len()));
+        assert_eq!(ecs.lots.len(), 1);
+    }
 }
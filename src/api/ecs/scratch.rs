@@ -0,0 +1,23 @@
+use std::path::{Path, PathBuf};
+
+/// A uniquely named directory under the system temp dir for a test's files. It is deleted with
+/// everything in it when dropped, so it goes away even when an assertion fails.
+pub struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    pub fn create() -> Self {
+        let path = std::env::temp_dir().join(format!("terrapin-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&path).unwrap();
+        ScratchDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
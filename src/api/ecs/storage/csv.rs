@@ -0,0 +1,285 @@
+use crate::api::ecs::atomic::write_atomically;
+use crate::api::ecs::journal::Snapshot;
+use crate::api::ecs::storage::Storage;
+use crate::api::ecs::{Eid, ReadError, WriteError};
+use crate::data::account::{self, Account};
+use crate::data::fx::{self, CurrencySettings, FxTable};
+use crate::data::history::{self, PriceHistory};
+use crate::data::market::Product;
+use crate::data::net_worth::{self, NetWorthSnapshot};
+use crate::data::portfolio::Lot;
+use crate::data::realized::RealizedGain;
+use crate::data::tax::TaxSettings;
+use crate::data::yf::MarketPrice;
+use crate::data::{market, portfolio, realized};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Keeps each collection in its own CSV file, rewriting the whole file on every save.
+#[derive(Debug, Clone)]
+pub struct CsvStorage {
+    data_path: PathBuf,
+}
+
+impl CsvStorage {
+    pub fn new(data_path: &Path) -> Self {
+        Self {
+            data_path: data_path.to_owned(),
+        }
+    }
+}
+
+impl Storage for CsvStorage {
+    fn load(&self) -> Result<Snapshot, ReadError> {
+        Ok(Snapshot {
+            lots: read_lots(&self.data_path)?,
+            products: read_products(&self.data_path)?,
+            realized: read_realized(&self.data_path)?,
+            accounts: read_accounts(&self.data_path)?,
+        })
+    }
+
+    fn save_accounts(
+        &self,
+        accounts: &HashMap<Eid, Account>,
+        _changed: &[Eid],
+    ) -> Result<(), WriteError> {
+        let path = accounts_csv_path(&self.data_path);
+        let string = account::format_accounts(accounts)?;
+        write_atomically(&path, string.as_bytes())?;
+        Ok(())
+    }
+
+    fn save_lots(&self, lots: &HashMap<Eid, Lot>, _changed: &[Eid]) -> Result<(), WriteError> {
+        let path = lots_csv_path(&self.data_path);
+        let string = portfolio::format_lots(lots.clone())?;
+        write_atomically(&path, string.as_bytes())?;
+        Ok(())
+    }
+
+    fn save_products(&self, products: &[Product], _changed: &[&str]) -> Result<(), WriteError> {
+        let path = products_csv_path(&self.data_path);
+        let string = market::format_products(products.to_vec())?;
+        write_atomically(&path, string.as_bytes())?;
+        Ok(())
+    }
+
+    fn save_realized(
+        &self,
+        realized: &HashMap<Eid, RealizedGain>,
+        _changed: &[Eid],
+    ) -> Result<(), WriteError> {
+        let path = realized_csv_path(&self.data_path);
+        let string = realized::format_realized(realized.clone())?;
+        write_atomically(&path, string.as_bytes())?;
+        Ok(())
+    }
+
+    fn load_price_history(&self) -> Result<PriceHistory, ReadError> {
+        let path = prices_csv_path(&self.data_path);
+        if !path.exists() {
+            return Ok(PriceHistory::default());
+        }
+        let bytes = std::fs::read(path)?;
+        let history = history::parse_price_history(&bytes)?;
+        Ok(history)
+    }
+
+    /// Appends to `prices.csv` instead of rewriting it, since the history only grows.
+    fn append_prices(&self, prices: &[MarketPrice]) -> Result<(), WriteError> {
+        let path = prices_csv_path(&self.data_path);
+        let mut history = self.load_price_history()?;
+        let new_prices = prices
+            .iter()
+            .filter(|price| history.insert(price))
+            .cloned()
+            .collect::<Vec<_>>();
+        if new_prices.is_empty() {
+            return Ok(());
+        }
+        let string = history::format_price_rows(&new_prices, !path.exists())?;
+        append(&path, &string)?;
+        Ok(())
+    }
+
+    fn remove_prices(&self, prices: &[MarketPrice]) -> Result<(), WriteError> {
+        let path = prices_csv_path(&self.data_path);
+        let mut history = self.load_price_history()?;
+        let mut removed = false;
+        for price in prices {
+            removed |= history.remove(&price.symbol, price.share_price.time);
+        }
+        if !removed {
+            return Ok(());
+        }
+        let string = history::format_price_rows(&history.prices(), true)?;
+        write_atomically(&path, string.as_bytes())?;
+        Ok(())
+    }
+
+    fn load_net_worth_history(&self) -> Result<Vec<NetWorthSnapshot>, ReadError> {
+        let path = net_worth_csv_path(&self.data_path);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let bytes = std::fs::read(path)?;
+        let snapshots = net_worth::parse_net_worth_history(&bytes)?;
+        Ok(snapshots)
+    }
+
+    fn append_net_worth(&self, snapshot: &NetWorthSnapshot) -> Result<(), WriteError> {
+        let path = net_worth_csv_path(&self.data_path);
+        // Files from before snapshots had a currency are rewritten with the new column.
+        if path.exists()
+            && !std::fs::read_to_string(&path)?
+                .lines()
+                .next()
+                .is_some_and(|header| header.split(',').any(|name| name.trim() == "currency"))
+        {
+            let mut snapshots = self.load_net_worth_history()?;
+            snapshots.push(snapshot.clone());
+            let string = net_worth::format_net_worth_rows(&snapshots, true)?;
+            write_atomically(&path, string.as_bytes())?;
+            return Ok(());
+        }
+        let string =
+            net_worth::format_net_worth_rows(std::slice::from_ref(snapshot), !path.exists())?;
+        append(&path, &string)?;
+        Ok(())
+    }
+
+    fn load_tax_settings(&self) -> Result<TaxSettings, ReadError> {
+        let path = tax_settings_path(&self.data_path);
+        if !path.exists() {
+            return Ok(TaxSettings::default());
+        }
+        let bytes = std::fs::read(path)?;
+        let settings = serde_json::from_slice(&bytes)?;
+        Ok(settings)
+    }
+
+    fn save_tax_settings(&self, settings: &TaxSettings) -> Result<(), WriteError> {
+        let path = tax_settings_path(&self.data_path);
+        let string = serde_json::to_string_pretty(settings)?;
+        write_atomically(&path, string.as_bytes())?;
+        Ok(())
+    }
+
+    fn load_fx_rates(&self) -> Result<FxTable, ReadError> {
+        let path = fx_rates_csv_path(&self.data_path);
+        if !path.exists() {
+            return Ok(FxTable::default());
+        }
+        let bytes = std::fs::read(path)?;
+        let table = fx::parse_fx_rates(&bytes)?;
+        Ok(table)
+    }
+
+    fn save_fx_rates(&self, table: &FxTable) -> Result<(), WriteError> {
+        let path = fx_rates_csv_path(&self.data_path);
+        let string = fx::format_fx_rates(table)?;
+        write_atomically(&path, string.as_bytes())?;
+        Ok(())
+    }
+
+    fn load_currency_settings(&self) -> Result<CurrencySettings, ReadError> {
+        let path = currency_settings_path(&self.data_path);
+        if !path.exists() {
+            return Ok(CurrencySettings::default());
+        }
+        let bytes = std::fs::read(path)?;
+        let settings = serde_json::from_slice(&bytes)?;
+        Ok(settings)
+    }
+
+    fn save_currency_settings(&self, settings: &CurrencySettings) -> Result<(), WriteError> {
+        let path = currency_settings_path(&self.data_path);
+        let string = serde_json::to_string_pretty(settings)?;
+        write_atomically(&path, string.as_bytes())?;
+        Ok(())
+    }
+}
+
+fn append(path: &Path, string: &str) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    file.write_all(string.as_bytes())?;
+    file.sync_all()
+}
+
+fn read_products(data_path: &Path) -> Result<Vec<Product>, ReadError> {
+    let path = products_csv_path(data_path);
+    let bytes = std::fs::read(path)?;
+    let products = market::parse_products(&bytes)?;
+    Ok(products)
+}
+
+fn read_lots(data_path: &Path) -> Result<HashMap<Eid, Lot>, ReadError> {
+    let path = lots_csv_path(data_path);
+    let bytes = std::fs::read(path)?;
+    let lots = portfolio::parse_lots(&bytes)?;
+    Ok(lots)
+}
+
+/// Realized gains are only written once something is sold, so a missing file is empty.
+fn read_realized(data_path: &Path) -> Result<HashMap<Eid, RealizedGain>, ReadError> {
+    let path = realized_csv_path(data_path);
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let bytes = std::fs::read(path)?;
+    let realized = realized::parse_realized(&bytes)?;
+    Ok(realized)
+}
+
+/// Accounts are kept from the first time they are migrated or added, so a missing file is empty.
+fn read_accounts(data_path: &Path) -> Result<HashMap<Eid, Account>, ReadError> {
+    let path = accounts_csv_path(data_path);
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let bytes = std::fs::read(path)?;
+    let accounts = account::parse_accounts(&bytes)?;
+    Ok(accounts)
+}
+
+pub(crate) fn products_csv_path(data_path: &Path) -> PathBuf {
+    let path = data_path.join("products.csv");
+    path
+}
+
+pub(crate) fn lots_csv_path(data_path: &Path) -> PathBuf {
+    let path = data_path.join("lots.csv");
+    path
+}
+
+pub(crate) fn realized_csv_path(data_path: &Path) -> PathBuf {
+    data_path.join("realized.csv")
+}
+
+pub(crate) fn accounts_csv_path(data_path: &Path) -> PathBuf {
+    data_path.join("accounts.csv")
+}
+
+fn prices_csv_path(data_path: &Path) -> PathBuf {
+    data_path.join("prices.csv")
+}
+
+pub(crate) fn net_worth_csv_path(data_path: &Path) -> PathBuf {
+    data_path.join("net_worth.csv")
+}
+
+fn tax_settings_path(data_path: &Path) -> PathBuf {
+    data_path.join("tax_settings.json")
+}
+
+fn fx_rates_csv_path(data_path: &Path) -> PathBuf {
+    data_path.join("fx_rates.csv")
+}
+
+fn currency_settings_path(data_path: &Path) -> PathBuf {
+    data_path.join("currency_settings.json")
+}
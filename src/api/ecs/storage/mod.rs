@@ -0,0 +1,98 @@
+use crate::api::ecs::journal::Snapshot;
+use crate::api::ecs::{Eid, ReadError, WriteError};
+use crate::data::account::Account;
+use crate::data::fx::{CurrencySettings, FxTable};
+use crate::data::history::PriceHistory;
+use crate::data::market::Product;
+use crate::data::net_worth::NetWorthSnapshot;
+use crate::data::portfolio::Lot;
+use crate::data::realized::RealizedGain;
+use crate::data::tax::TaxSettings;
+use crate::data::yf::MarketPrice;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use thiserror::Error;
+
+pub mod csv;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// Keeps a user's accounts, lots, products and realized gains.
+///
+/// Each save receives the full collection after a change along with the keys that changed, so
+/// an implementation can either rewrite everything or touch only the changed rows. A changed key
+/// missing from the collection was removed.
+pub trait Storage {
+    fn load(&self) -> Result<Snapshot, ReadError>;
+    fn save_accounts(
+        &self,
+        accounts: &HashMap<Eid, Account>,
+        changed: &[Eid],
+    ) -> Result<(), WriteError>;
+    fn save_lots(&self, lots: &HashMap<Eid, Lot>, changed: &[Eid]) -> Result<(), WriteError>;
+    fn save_products(&self, products: &[Product], changed: &[&str]) -> Result<(), WriteError>;
+    fn save_realized(
+        &self,
+        realized: &HashMap<Eid, RealizedGain>,
+        changed: &[Eid],
+    ) -> Result<(), WriteError>;
+    fn load_price_history(&self) -> Result<PriceHistory, ReadError>;
+    /// Adds quotes to the price history, skipping any already recorded.
+    fn append_prices(&self, prices: &[MarketPrice]) -> Result<(), WriteError>;
+    /// Removes the quotes for the same symbols and times as `prices`, for undoing an update.
+    fn remove_prices(&self, prices: &[MarketPrice]) -> Result<(), WriteError>;
+    fn load_net_worth_history(&self) -> Result<Vec<NetWorthSnapshot>, ReadError>;
+    fn append_net_worth(&self, snapshot: &NetWorthSnapshot) -> Result<(), WriteError>;
+    /// The user's tax settings, or the defaults if none were saved.
+    fn load_tax_settings(&self) -> Result<TaxSettings, ReadError>;
+    fn save_tax_settings(&self, settings: &TaxSettings) -> Result<(), WriteError>;
+    fn load_fx_rates(&self) -> Result<FxTable, ReadError>;
+    fn save_fx_rates(&self, table: &FxTable) -> Result<(), WriteError>;
+    /// The user's reporting currency, or US dollars if none was saved.
+    fn load_currency_settings(&self) -> Result<CurrencySettings, ReadError>;
+    fn save_currency_settings(&self, settings: &CurrencySettings) -> Result<(), WriteError>;
+}
+
+/// Which storage holds a user's data. A user is on SQLite once their CSV files are migrated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StorageKind {
+    Csv,
+    Sqlite,
+}
+
+#[derive(Error, Debug)]
+pub enum StorageError {
+    #[error("Data is stored in SQLite but this build lacks the `sqlite` feature")]
+    SqliteUnavailable,
+
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error: {0}")]
+    SqliteError(#[from] rusqlite::Error),
+}
+
+impl StorageKind {
+    pub fn detect(data_path: &Path) -> Self {
+        if sqlite_db_path(data_path).exists() {
+            StorageKind::Sqlite
+        } else {
+            StorageKind::Csv
+        }
+    }
+
+    pub fn open(self, data_path: &Path) -> Result<Box<dyn Storage>, StorageError> {
+        match self {
+            StorageKind::Csv => Ok(Box::new(csv::CsvStorage::new(data_path))),
+            #[cfg(feature = "sqlite")]
+            StorageKind::Sqlite => Ok(Box::new(sqlite::SqliteStorage::open(&sqlite_db_path(
+                data_path,
+            ))?)),
+            #[cfg(not(feature = "sqlite"))]
+            StorageKind::Sqlite => Err(StorageError::SqliteUnavailable),
+        }
+    }
+}
+
+pub fn sqlite_db_path(data_path: &Path) -> std::path::PathBuf {
+    data_path.join("terrapin.db")
+}
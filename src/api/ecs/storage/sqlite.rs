@@ -0,0 +1,959 @@
+use crate::api::ecs::journal::Snapshot;
+use crate::api::ecs::storage::csv::CsvStorage;
+use crate::api::ecs::storage::{sqlite_db_path, Storage};
+use crate::api::ecs::{Eid, ReadError, WriteError};
+use crate::data::account::{Account, AccountKind};
+use crate::data::fx::{CurrencySettings, FxRate, FxTable};
+use crate::data::history::PriceHistory;
+use crate::data::market::{parse_aliases, Product, SharePrice};
+use crate::data::net_worth::NetWorthSnapshot;
+use crate::data::portfolio::Lot;
+use crate::data::realized::RealizedGain;
+use crate::data::tax::TaxSettings;
+use crate::data::yf::MarketPrice;
+use chrono::{DateTime, Utc};
+use rusqlite::types::{Type, ValueRef};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use thiserror::Error;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS accounts (
+    eid TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS lots (
+    eid TEXT PRIMARY KEY,
+    account TEXT NOT NULL,
+    time TEXT NOT NULL,
+    product TEXT NOT NULL,
+    quantity TEXT NOT NULL,
+    unit_cost TEXT,
+    fees TEXT
+);
+CREATE TABLE IF NOT EXISTS products (
+    symbol TEXT PRIMARY KEY,
+    position INTEGER NOT NULL,
+    type TEXT NOT NULL,
+    name TEXT NOT NULL,
+    supply INTEGER,
+    share_price TEXT NOT NULL,
+    share_price_as_of TEXT NOT NULL,
+    aliases TEXT NOT NULL DEFAULT '',
+    currency TEXT NOT NULL DEFAULT 'USD'
+);
+CREATE TABLE IF NOT EXISTS realized (
+    eid TEXT PRIMARY KEY,
+    account TEXT NOT NULL,
+    product TEXT NOT NULL,
+    acquired TEXT NOT NULL,
+    sold TEXT NOT NULL,
+    quantity TEXT NOT NULL,
+    proceeds TEXT NOT NULL,
+    cost_basis TEXT,
+    lot TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS prices (
+    symbol TEXT NOT NULL,
+    time TEXT NOT NULL,
+    share_price TEXT NOT NULL,
+    PRIMARY KEY (symbol, time)
+);
+CREATE TABLE IF NOT EXISTS net_worth (
+    time TEXT NOT NULL,
+    pre_tax TEXT NOT NULL,
+    currency TEXT NOT NULL DEFAULT 'USD'
+);
+CREATE TABLE IF NOT EXISTS fx_rates (
+    base TEXT NOT NULL,
+    quote TEXT NOT NULL,
+    rate TEXT NOT NULL,
+    time TEXT NOT NULL,
+    PRIMARY KEY (base, quote)
+);
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+";
+
+#[derive(Error, Debug)]
+pub enum MigrateError {
+    #[error("{0} already exists")]
+    AlreadyMigrated(PathBuf),
+
+    #[error("Read error: {0}")]
+    ReadError(#[from] ReadError),
+
+    #[error("Write error: {0}")]
+    WriteError(#[from] WriteError),
+}
+
+/// Copies a user's CSV data into a new SQLite database, which `Ecs::connect` uses from then on.
+/// The CSV files are left in place as a backup.
+pub fn migrate(data_path: &Path) -> Result<Snapshot, MigrateError> {
+    let db_path = sqlite_db_path(data_path);
+    if db_path.exists() {
+        return Err(MigrateError::AlreadyMigrated(db_path));
+    }
+    let csv = CsvStorage::new(data_path);
+    let snapshot = csv.load()?;
+    let history = csv.load_price_history()?;
+    // Build the database beside its final name so a failed import never looks migrated.
+    let temp_path = db_path.with_extension("db.tmp");
+    if temp_path.exists() {
+        std::fs::remove_file(&temp_path).map_err(WriteError::from)?;
+    }
+    let mut storage = SqliteStorage::open(&temp_path).map_err(WriteError::from)?;
+    storage.import(&snapshot)?;
+    let prices = history
+        .symbols()
+        .flat_map(|symbol| {
+            history
+                .series(symbol)
+                .into_iter()
+                .map(|share_price| MarketPrice {
+                    symbol: symbol.to_string(),
+                    share_price,
+                })
+        })
+        .collect::<Vec<_>>();
+    storage.append_prices(&prices)?;
+    for snapshot in csv.load_net_worth_history()? {
+        storage.append_net_worth(&snapshot)?;
+    }
+    storage.save_tax_settings(&csv.load_tax_settings()?)?;
+    storage.save_fx_rates(&csv.load_fx_rates()?)?;
+    storage.save_currency_settings(&csv.load_currency_settings()?)?;
+    drop(storage);
+    std::fs::rename(&temp_path, &db_path).map_err(WriteError::from)?;
+    Ok(snapshot)
+}
+
+/// Adds the aliases column to databases created before products had aliases. Coins were matched
+/// as `<symbol>-USD` back then, so they keep that as an alias.
+fn add_product_aliases(connection: &Connection) -> rusqlite::Result<()> {
+    if connection
+        .prepare("SELECT aliases FROM products LIMIT 0")
+        .is_ok()
+    {
+        return Ok(());
+    }
+    connection.execute_batch(
+        "ALTER TABLE products ADD COLUMN aliases TEXT NOT NULL DEFAULT '';
+         UPDATE products SET aliases = symbol || '-USD' WHERE type = 'coin';",
+    )
+}
+
+/// Adds the currency column to tables created before products and net worth had a currency, when
+/// everything was in US dollars.
+fn add_currency(connection: &Connection, table: &str) -> rusqlite::Result<()> {
+    if connection
+        .prepare(&format!("SELECT currency FROM {} LIMIT 0", table))
+        .is_ok()
+    {
+        return Ok(());
+    }
+    connection.execute_batch(&format!(
+        "ALTER TABLE {} ADD COLUMN currency TEXT NOT NULL DEFAULT 'USD';",
+        table
+    ))
+}
+
+/// Kept in the database's `user_version` once its tables match `SCHEMA`, so that the migrations
+/// run once per database rather than on every open. Bump it when adding a migration.
+const SCHEMA_VERSION: u32 = 1;
+
+/// The columns holding amounts, which databases created before amounts were exact declared REAL.
+const AMOUNT_COLUMNS: [(&str, &[&str]); 6] = [
+    ("lots", &["quantity", "unit_cost", "fees"]),
+    ("products", &["share_price"]),
+    ("realized", &["quantity", "proceeds", "cost_basis"]),
+    ("prices", &["share_price"]),
+    ("net_worth", &["pre_tax"]),
+    ("fx_rates", &["rate"]),
+];
+
+/// Rebuilds tables whose amount columns are still REAL so that amounts are kept as exact TEXT.
+/// SQLite prints each old value with 15 significant digits, which drops the noise of the binary
+/// fraction, so 0.30000000000000004 becomes 0.3.
+fn store_amounts_as_text(connection: &mut Connection) -> rusqlite::Result<()> {
+    for (table, amounts) in AMOUNT_COLUMNS {
+        let columns = connection
+            .prepare(&format!("PRAGMA table_info({})", table))?
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let is_amount = |name: &str| amounts.contains(&name);
+        if !columns
+            .iter()
+            .any(|(name, type_)| is_amount(name) && type_.eq_ignore_ascii_case("REAL"))
+        {
+            continue;
+        }
+        let names = columns
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let values = columns
+            .iter()
+            .map(|(name, _)| match is_amount(name) {
+                true => format!("CAST({} AS TEXT)", name),
+                false => name.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let tx = connection.transaction()?;
+        tx.execute_batch(&format!("ALTER TABLE {0} RENAME TO {0}_real;", table))?;
+        tx.execute_batch(SCHEMA)?;
+        tx.execute_batch(&format!(
+            "INSERT INTO {0} ({1}) SELECT {2} FROM {0}_real; DROP TABLE {0}_real;",
+            table, names, values
+        ))?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// Keeps each collection in a table and writes only the rows that changed.
+#[derive(Debug)]
+pub struct SqliteStorage {
+    connection: Connection,
+}
+
+impl SqliteStorage {
+    /// Opens the database, creating or migrating its tables the first time it is opened at this
+    /// schema version.
+    pub fn open(path: &Path) -> rusqlite::Result<Self> {
+        let mut connection = Connection::open(path)?;
+        let version: u32 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version < SCHEMA_VERSION {
+            connection.execute_batch(SCHEMA)?;
+            add_product_aliases(&connection)?;
+            add_currency(&connection, "products")?;
+            add_currency(&connection, "net_worth")?;
+            store_amounts_as_text(&mut connection)?;
+            connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        }
+        Ok(Self { connection })
+    }
+
+    /// Writes every row of `snapshot` in one transaction.
+    pub fn import(&mut self, snapshot: &Snapshot) -> Result<(), WriteError> {
+        let tx = self.connection.transaction()?;
+        for (eid, account) in &snapshot.accounts {
+            upsert_account(&tx, eid, account)?;
+        }
+        for (eid, lot) in &snapshot.lots {
+            upsert_lot(&tx, eid, lot)?;
+        }
+        for (position, product) in snapshot.products.iter().enumerate() {
+            upsert_product(&tx, position, product)?;
+        }
+        for (eid, gain) in &snapshot.realized {
+            upsert_realized(&tx, eid, gain)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn load(&self) -> Result<Snapshot, ReadError> {
+        let lots = self
+            .connection
+            .prepare("SELECT eid, account, time, product, quantity, unit_cost, fees FROM lots")?
+            .query_map([], |row| {
+                let lot = Lot {
+                    account: eid_at(row, 1)?,
+                    time: row.get(2)?,
+                    product: row.get(3)?,
+                    quantity: decimal_at(row, 4)?,
+                    unit_cost: optional_decimal_at(row, 5)?,
+                    fees: optional_decimal_at(row, 6)?,
+                };
+                Ok((eid_at(row, 0)?, lot))
+            })?
+            .collect::<Result<HashMap<_, _>, _>>()?;
+        let products = self
+            .connection
+            .prepare(
+                "SELECT type, symbol, name, supply, share_price, share_price_as_of, aliases, \
+                 currency \
+                 FROM products ORDER BY position",
+            )?
+            .query_map([], product_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        let realized = self
+            .connection
+            .prepare(
+                "SELECT eid, account, product, acquired, sold, quantity, proceeds, cost_basis, lot \
+                 FROM realized",
+            )?
+            .query_map([], |row| {
+                let gain = RealizedGain {
+                    account: eid_at(row, 1)?,
+                    product: row.get(2)?,
+                    acquired: row.get(3)?,
+                    sold: row.get(4)?,
+                    quantity: decimal_at(row, 5)?,
+                    proceeds: decimal_at(row, 6)?,
+                    cost_basis: optional_decimal_at(row, 7)?,
+                    lot: eid_at(row, 8)?,
+                };
+                Ok((eid_at(row, 0)?, gain))
+            })?
+            .collect::<Result<HashMap<_, _>, _>>()?;
+        let accounts = self
+            .connection
+            .prepare("SELECT eid, name, kind FROM accounts")?
+            .query_map([], |row| {
+                let account = Account {
+                    name: row.get(1)?,
+                    kind: kind_from_sql(&row.get::<_, String>(2)?)?,
+                };
+                Ok((eid_at(row, 0)?, account))
+            })?
+            .collect::<Result<HashMap<_, _>, _>>()?;
+        Ok(Snapshot {
+            lots,
+            products,
+            realized,
+            accounts,
+        })
+    }
+
+    fn save_accounts(
+        &self,
+        accounts: &HashMap<Eid, Account>,
+        changed: &[Eid],
+    ) -> Result<(), WriteError> {
+        let tx = self.connection.unchecked_transaction()?;
+        for eid in changed {
+            match accounts.get(eid) {
+                Some(account) => upsert_account(&tx, eid, account)?,
+                None => {
+                    tx.execute("DELETE FROM accounts WHERE eid = ?1", [eid.as_str()])?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn save_lots(&self, lots: &HashMap<Eid, Lot>, changed: &[Eid]) -> Result<(), WriteError> {
+        let tx = self.connection.unchecked_transaction()?;
+        for eid in changed {
+            match lots.get(eid) {
+                Some(lot) => upsert_lot(&tx, eid, lot)?,
+                None => {
+                    tx.execute("DELETE FROM lots WHERE eid = ?1", [eid.as_str()])?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn save_products(&self, products: &[Product], changed: &[&str]) -> Result<(), WriteError> {
+        let tx = self.connection.unchecked_transaction()?;
+        for symbol in changed {
+            match products.iter().position(|p| p.symbol() == *symbol) {
+                Some(position) => upsert_product(&tx, position, &products[position])?,
+                None => {
+                    tx.execute("DELETE FROM products WHERE symbol = ?1", [symbol])?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn save_realized(
+        &self,
+        realized: &HashMap<Eid, RealizedGain>,
+        changed: &[Eid],
+    ) -> Result<(), WriteError> {
+        let tx = self.connection.unchecked_transaction()?;
+        for eid in changed {
+            match realized.get(eid) {
+                Some(gain) => upsert_realized(&tx, eid, gain)?,
+                None => {
+                    tx.execute("DELETE FROM realized WHERE eid = ?1", [eid.as_str()])?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn load_price_history(&self) -> Result<PriceHistory, ReadError> {
+        let mut history = PriceHistory::default();
+        let mut statement = self
+            .connection
+            .prepare("SELECT symbol, time, share_price FROM prices")?;
+        let prices = statement.query_map([], |row| {
+            Ok(MarketPrice {
+                symbol: row.get(0)?,
+                share_price: SharePrice {
+                    time: row.get(1)?,
+                    height: decimal_at(row, 2)?,
+                },
+            })
+        })?;
+        for price in prices {
+            history.insert(&price?);
+        }
+        Ok(history)
+    }
+
+    fn append_prices(&self, prices: &[MarketPrice]) -> Result<(), WriteError> {
+        let tx = self.connection.unchecked_transaction()?;
+        for price in prices {
+            tx.execute(
+                "INSERT OR REPLACE INTO prices (symbol, time, share_price) VALUES (?1, ?2, ?3)",
+                params![
+                    price.symbol,
+                    price.share_price.time,
+                    text(price.share_price.height)
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn remove_prices(&self, prices: &[MarketPrice]) -> Result<(), WriteError> {
+        let tx = self.connection.unchecked_transaction()?;
+        for price in prices {
+            tx.execute(
+                "DELETE FROM prices WHERE symbol = ?1 AND time = ?2",
+                params![price.symbol, price.share_price.time],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn load_net_worth_history(&self) -> Result<Vec<NetWorthSnapshot>, ReadError> {
+        let snapshots = self
+            .connection
+            .prepare("SELECT time, pre_tax, currency FROM net_worth ORDER BY time")?
+            .query_map([], |row| {
+                Ok(NetWorthSnapshot {
+                    time: row.get(0)?,
+                    pre_tax: decimal_at(row, 1)?,
+                    currency: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(snapshots)
+    }
+
+    fn append_net_worth(&self, snapshot: &NetWorthSnapshot) -> Result<(), WriteError> {
+        self.connection.execute(
+            "INSERT INTO net_worth (time, pre_tax, currency) VALUES (?1, ?2, ?3)",
+            params![snapshot.time, text(snapshot.pre_tax), snapshot.currency],
+        )?;
+        Ok(())
+    }
+
+    fn load_tax_settings(&self) -> Result<TaxSettings, ReadError> {
+        let value = self
+            .connection
+            .query_row("SELECT value FROM settings WHERE key = 'tax'", [], |row| {
+                row.get::<_, String>(0)
+            })
+            .optional()?;
+        match value {
+            Some(value) => Ok(serde_json::from_str(&value)?),
+            None => Ok(TaxSettings::default()),
+        }
+    }
+
+    fn save_tax_settings(&self, settings: &TaxSettings) -> Result<(), WriteError> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES ('tax', ?1)",
+            [serde_json::to_string(settings)?],
+        )?;
+        Ok(())
+    }
+
+    fn load_fx_rates(&self) -> Result<FxTable, ReadError> {
+        let mut table = FxTable::default();
+        let mut statement = self
+            .connection
+            .prepare("SELECT base, quote, rate, time FROM fx_rates")?;
+        let rates = statement.query_map([], |row| {
+            Ok(FxRate {
+                base: row.get(0)?,
+                quote: row.get(1)?,
+                rate: decimal_at(row, 2)?,
+                time: row.get(3)?,
+            })
+        })?;
+        for rate in rates {
+            table.insert(rate?);
+        }
+        Ok(table)
+    }
+
+    fn save_fx_rates(&self, table: &FxTable) -> Result<(), WriteError> {
+        let tx = self.connection.unchecked_transaction()?;
+        tx.execute("DELETE FROM fx_rates", [])?;
+        for rate in table.rates() {
+            tx.execute(
+                "INSERT INTO fx_rates (base, quote, rate, time) VALUES (?1, ?2, ?3, ?4)",
+                params![rate.base, rate.quote, text(rate.rate), rate.time],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn load_currency_settings(&self) -> Result<CurrencySettings, ReadError> {
+        let value = self
+            .connection
+            .query_row(
+                "SELECT value FROM settings WHERE key = 'currency'",
+                [],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        match value {
+            Some(value) => Ok(serde_json::from_str(&value)?),
+            None => Ok(CurrencySettings::default()),
+        }
+    }
+
+    fn save_currency_settings(&self, settings: &CurrencySettings) -> Result<(), WriteError> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES ('currency', ?1)",
+            [serde_json::to_string(settings)?],
+        )?;
+        Ok(())
+    }
+}
+
+fn upsert_account(connection: &Connection, eid: &Eid, account: &Account) -> rusqlite::Result<()> {
+    connection.execute(
+        "INSERT OR REPLACE INTO accounts (eid, name, kind) VALUES (?1, ?2, ?3)",
+        params![eid.as_str(), account.name, kind_to_sql(account.kind)],
+    )?;
+    Ok(())
+}
+
+fn kind_to_sql(kind: AccountKind) -> &'static str {
+    match kind {
+        AccountKind::Taxable => "taxable",
+        AccountKind::Ira => "ira",
+        AccountKind::Roth => "roth",
+        AccountKind::Hsa => "hsa",
+    }
+}
+
+fn kind_from_sql(kind: &str) -> rusqlite::Result<AccountKind> {
+    AccountKind::ALL
+        .into_iter()
+        .find(|k| kind_to_sql(*k) == kind)
+        .ok_or_else(|| {
+            rusqlite::Error::InvalidColumnType(2, kind.to_string(), rusqlite::types::Type::Text)
+        })
+}
+
+fn upsert_lot(connection: &Connection, eid: &Eid, lot: &Lot) -> rusqlite::Result<()> {
+    connection.execute(
+        "INSERT OR REPLACE INTO lots (eid, account, time, product, quantity, unit_cost, fees) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            eid.as_str(),
+            lot.account.as_str(),
+            lot.time,
+            lot.product,
+            text(lot.quantity),
+            lot.unit_cost.map(text),
+            lot.fees.map(text)
+        ],
+    )?;
+    Ok(())
+}
+
+fn upsert_product(
+    connection: &Connection,
+    position: usize,
+    product: &Product,
+) -> rusqlite::Result<()> {
+    let type_ = match product {
+        Product::Stock { .. } => "stock",
+        Product::Etf { .. } => "etf",
+        Product::Coin { .. } => "coin",
+        Product::Note { .. } => "note",
+    };
+    // Keep a product's original position when only its price changes.
+    let existing = connection
+        .query_row(
+            "SELECT position FROM products WHERE symbol = ?1",
+            [product.symbol()],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?;
+    connection.execute(
+        "INSERT OR REPLACE INTO products \
+         (symbol, position, type, name, supply, share_price, share_price_as_of, aliases, currency) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            product.symbol(),
+            existing.unwrap_or(position as i64),
+            type_,
+            product.name(),
+            product.supply().map(|supply| supply as i64),
+            text(product.share_price().height),
+            product.share_price().time,
+            product.aliases().join(" "),
+            product.currency(),
+        ],
+    )?;
+    Ok(())
+}
+
+fn upsert_realized(
+    connection: &Connection,
+    eid: &Eid,
+    gain: &RealizedGain,
+) -> rusqlite::Result<()> {
+    connection.execute(
+        "INSERT OR REPLACE INTO realized \
+         (eid, account, product, acquired, sold, quantity, proceeds, cost_basis, lot) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            eid.as_str(),
+            gain.account.as_str(),
+            gain.product,
+            gain.acquired,
+            gain.sold,
+            text(gain.quantity),
+            text(gain.proceeds),
+            gain.cost_basis.map(text),
+            gain.lot.as_str()
+        ],
+    )?;
+    Ok(())
+}
+
+fn eid_at(row: &Row, index: usize) -> rusqlite::Result<Eid> {
+    Ok(Eid::from(row.get::<_, String>(index)?.as_str()))
+}
+
+fn decimal_at(row: &Row, index: usize) -> rusqlite::Result<Decimal> {
+    optional_decimal_at(row, index)?.ok_or(rusqlite::Error::InvalidColumnType(
+        index,
+        "NULL".to_string(),
+        Type::Null,
+    ))
+}
+
+/// Amounts are stored as TEXT so that they keep every digit. Values converted from REAL columns
+/// may be in scientific notation, like `1.0e-08`.
+fn optional_decimal_at(row: &Row, index: usize) -> rusqlite::Result<Option<Decimal>> {
+    let text = match row.get_ref(index)? {
+        ValueRef::Null => return Ok(None),
+        ValueRef::Text(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+        value => {
+            return Err(rusqlite::Error::InvalidColumnType(
+                index,
+                format!("{:?}", value),
+                value.data_type(),
+            ))
+        }
+    };
+    Decimal::from_str(&text)
+        .or_else(|_| Decimal::from_scientific(&text))
+        .map(Some)
+        .map_err(|_| rusqlite::Error::InvalidColumnType(index, text, Type::Text))
+}
+
+fn text(amount: Decimal) -> String {
+    amount.to_string()
+}
+
+fn product_from_row(row: &Row) -> rusqlite::Result<Product> {
+    let type_ = row.get::<_, String>(0)?;
+    let symbol = row.get(1)?;
+    let name = row.get(2)?;
+    let supply = row.get::<_, Option<i64>>(3)?.unwrap_or_default() as usize;
+    let share_price = SharePrice {
+        height: decimal_at(row, 4)?,
+        time: row.get::<_, DateTime<Utc>>(5)?,
+    };
+    let aliases = parse_aliases(&row.get::<_, String>(6)?);
+    let currency = row.get(7)?;
+    let product = match type_.as_str() {
+        "stock" => Product::Stock {
+            symbol,
+            name,
+            aliases,
+            currency,
+            outstanding_shares: supply,
+            share_price,
+        },
+        "etf" => Product::Etf {
+            symbol,
+            name,
+            aliases,
+            currency,
+            share_price,
+        },
+        "coin" => Product::Coin {
+            symbol,
+            name,
+            aliases,
+            currency,
+            total_supply: supply,
+            share_price,
+        },
+        "note" => Product::Note {
+            symbol,
+            name,
+            aliases,
+            currency,
+            share_price,
+        },
+        _ => {
+            return Err(rusqlite::Error::InvalidColumnType(
+                0,
+                format!("unknown product type '{}'", type_),
+                rusqlite::types::Type::Text,
+            ))
+        }
+    };
+    Ok(product)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+
+    fn snapshot() -> Snapshot {
+        let time = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+        let lot = Lot {
+            account: "brokerage".into(),
+            time,
+            product: "VTI".into(),
+            quantity: dec!(10),
+            unit_cost: Some(dec!(200)),
+            fees: None,
+        };
+        let gain = RealizedGain {
+            account: "brokerage".into(),
+            product: "BTC".into(),
+            acquired: time,
+            sold: Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap(),
+            quantity: dec!(0.5),
+            proceeds: dec!(30000),
+            cost_basis: None,
+            lot: Eid::from("l1"),
+        };
+        let share_price = SharePrice {
+            height: dec!(250),
+            time,
+        };
+        Snapshot {
+            lots: HashMap::from([(Eid::from("l0"), lot)]),
+            products: vec![
+                Product::Etf {
+                    symbol: "VTI".into(),
+                    name: "Total Market".into(),
+                    aliases: Vec::new(),
+                    currency: "USD".into(),
+                    share_price: share_price.clone(),
+                },
+                Product::Coin {
+                    symbol: "BTC".into(),
+                    name: "Bitcoin".into(),
+                    aliases: vec!["XBT".into(), "BTC-USD".into()],
+                    currency: "EUR".into(),
+                    total_supply: 21_000_000,
+                    share_price,
+                },
+            ],
+            realized: HashMap::from([(Eid::from("r0"), gain)]),
+            accounts: HashMap::from([(
+                Eid::from("brokerage"),
+                Account {
+                    name: "Brokerage".into(),
+                    kind: AccountKind::Taxable,
+                },
+            )]),
+        }
+    }
+
+    #[test]
+    fn test_import_and_save_changes() {
+        let mut storage = SqliteStorage {
+            connection: Connection::open_in_memory().unwrap(),
+        };
+        storage.connection.execute_batch(SCHEMA).unwrap();
+        let mut expected = snapshot();
+        storage.import(&expected).unwrap();
+        assert_eq!(storage.load().unwrap(), expected);
+
+        let mut lot = expected.lots[&Eid::from("l0")].clone();
+        lot.quantity = dec!(4);
+        expected.lots.insert(Eid::from("l1"), lot);
+        expected.lots.remove(&Eid::from("l0"));
+        storage
+            .save_lots(&expected.lots, &[Eid::from("l0"), Eid::from("l1")])
+            .unwrap();
+        expected.products[1].set_share_price(SharePrice {
+            height: dec!(60000),
+            time: Utc.with_ymd_and_hms(2024, 7, 1, 0, 0, 0).unwrap(),
+        });
+        storage.save_products(&expected.products, &["BTC"]).unwrap();
+        assert_eq!(storage.load().unwrap(), expected);
+
+        let quote = MarketPrice {
+            symbol: "BTC".into(),
+            share_price: expected.products[1].share_price().clone(),
+        };
+        storage
+            .append_prices(&[quote.clone(), quote.clone()])
+            .unwrap();
+        let history = storage.load_price_history().unwrap();
+        assert_eq!(history.series("BTC").len(), 1);
+        storage.remove_prices(&[quote]).unwrap();
+        let history = storage.load_price_history().unwrap();
+        assert!(history.series("BTC").is_empty());
+
+        let mut rates = FxTable::default();
+        rates.insert(FxRate {
+            base: "EUR".into(),
+            quote: "USD".into(),
+            rate: dec!(1.08),
+            time: Utc.with_ymd_and_hms(2024, 7, 1, 0, 0, 0).unwrap(),
+        });
+        storage.save_fx_rates(&rates).unwrap();
+        assert_eq!(storage.load_fx_rates().unwrap(), rates);
+
+        let net_worth = NetWorthSnapshot {
+            time: Utc.with_ymd_and_hms(2024, 7, 1, 0, 0, 0).unwrap(),
+            pre_tax: dec!(55000.25),
+            currency: "EUR".into(),
+        };
+        storage.append_net_worth(&net_worth).unwrap();
+        assert_eq!(storage.load_net_worth_history().unwrap(), vec![net_worth]);
+    }
+
+    #[test]
+    fn test_amounts_keep_every_digit() {
+        let mut storage = SqliteStorage {
+            connection: Connection::open_in_memory().unwrap(),
+        };
+        storage.connection.execute_batch(SCHEMA).unwrap();
+        let mut expected = snapshot();
+        let lot = expected.lots.get_mut(&Eid::from("l0")).unwrap();
+        lot.quantity = dec!(0.123456789012);
+        lot.unit_cost = Some(dec!(0.1) + dec!(0.2));
+        lot.fees = Some(dec!(0.30));
+        storage.import(&expected).unwrap();
+        assert_eq!(storage.load().unwrap(), expected);
+        let fees: String = storage
+            .connection
+            .query_row("SELECT fees FROM lots", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(fees, "0.30");
+    }
+
+    #[test]
+    fn test_store_amounts_as_text() {
+        let mut storage = SqliteStorage {
+            connection: Connection::open_in_memory().unwrap(),
+        };
+        let real_schema = AMOUNT_COLUMNS
+            .iter()
+            .flat_map(|(_, columns)| columns.iter())
+            .fold(SCHEMA.to_string(), |schema, column| {
+                schema.replace(&format!("{} TEXT", column), &format!("{} REAL", column))
+            });
+        storage.connection.execute_batch(&real_schema).unwrap();
+        storage
+            .connection
+            .execute(
+                "INSERT INTO lots (eid, account, time, product, quantity, unit_cost, fees)
+                 VALUES ('l0', 'brokerage', '2024-01-02T00:00:00Z', 'VTI', 0.1 + 0.2, 1e-8, NULL)",
+                [],
+            )
+            .unwrap();
+        store_amounts_as_text(&mut storage.connection).unwrap();
+        let lot = &storage.load().unwrap().lots[&Eid::from("l0")];
+        assert_eq!(lot.quantity, dec!(0.3));
+        assert_eq!(lot.unit_cost, Some(dec!(0.00000001)));
+        assert_eq!(lot.fees, None);
+        let quantity: String = storage
+            .connection
+            .query_row("SELECT typeof(quantity) FROM lots", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(quantity, "text");
+        store_amounts_as_text(&mut storage.connection).unwrap();
+    }
+
+    #[test]
+    fn test_open_migrates_once() {
+        use crate::api::ecs::scratch::ScratchDir;
+        let scratch = ScratchDir::create();
+        let path = scratch.path().join("terrapin.db");
+        let storage = SqliteStorage::open(&path).unwrap();
+        let version: u32 = storage
+            .connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+        storage
+            .connection
+            .execute_batch("DROP TABLE fx_rates;")
+            .unwrap();
+        drop(storage);
+        let storage = SqliteStorage::open(&path).unwrap();
+        assert!(storage.load_fx_rates().is_err());
+    }
+
+    #[test]
+    fn test_migrate_from_csv() {
+        use crate::api::ecs::scratch::ScratchDir;
+        use crate::api::ecs::storage::StorageKind;
+        let scratch = ScratchDir::create();
+        let dir = scratch.path();
+        let expected = snapshot();
+        let csv = CsvStorage::new(dir);
+        csv.save_accounts(&expected.accounts, &[]).unwrap();
+        csv.save_lots(&expected.lots, &[]).unwrap();
+        csv.save_products(&expected.products, &[]).unwrap();
+        csv.save_realized(&expected.realized, &[]).unwrap();
+        let quote = MarketPrice {
+            symbol: "VTI".into(),
+            share_price: expected.products[0].share_price().clone(),
+        };
+        csv.append_prices(std::slice::from_ref(&quote)).unwrap();
+        assert_eq!(StorageKind::detect(dir), StorageKind::Csv);
+
+        assert_eq!(migrate(dir).unwrap(), expected);
+        assert_eq!(StorageKind::detect(dir), StorageKind::Sqlite);
+        let sqlite = StorageKind::Sqlite.open(dir).unwrap();
+        assert_eq!(sqlite.load().unwrap(), expected);
+        assert_eq!(
+            sqlite.load_price_history().unwrap().series("VTI"),
+            vec![quote.share_price]
+        );
+        assert!(matches!(
+            migrate(dir),
+            Err(MigrateError::AlreadyMigrated(_))
+        ));
+    }
+}
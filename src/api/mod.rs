@@ -1,8 +1,21 @@
+use crate::api::ecs::journal::ChangeItem;
 use crate::api::ecs::{Eid, LotItem};
-use crate::data::market::Product;
+use crate::data::account::Account;
+use crate::data::broker::{ColumnMapping, ImportPreview};
+use crate::data::fx::{CurrencySettings, FxImport, FxTable};
+use crate::data::market::{Product, SharePrice};
+use crate::data::net_worth::NetWorthSnapshot;
+use crate::data::portfolio::Lot;
+use crate::data::price_source::PriceImport;
+use crate::data::quote::RefreshStatus;
+use crate::data::realized::RealizedGain;
+use crate::data::sale::{ReliefMethod, Sale};
+use crate::data::tax::TaxSettings;
+use crate::data::term::WashSale;
 use dioxus::fullstack::ServerFnError;
 use dioxus::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use uuid::Uuid;
 
 pub mod authentication;
@@ -28,6 +41,44 @@ pub async fn active_user() -> Result<Option<User>, ServerFnError> {
     Ok(user)
 }
 
+#[server]
+pub async fn query_accounts() -> Result<HashMap<Eid, Account>, ServerFnError> {
+    use crate::backend::require_ecs;
+    let ecs = require_ecs().await?;
+    Ok(ecs.query_accounts())
+}
+
+#[server]
+pub async fn add_account(account: Account) -> Result<Eid, ServerFnError> {
+    use crate::backend::require_ecs_mut;
+    let mut ecs = require_ecs_mut().await?;
+    let eid = ecs
+        .insert_account(account)
+        .map_err(|e| ServerFnError::new(format!("Failed to add account: {}", e)))?;
+    info!("Added account: {:?}", eid);
+    Ok(eid)
+}
+
+#[server]
+pub async fn update_account(eid: Eid, account: Account) -> Result<(), ServerFnError> {
+    use crate::backend::require_ecs_mut;
+    let mut ecs = require_ecs_mut().await?;
+    ecs.update_account(eid.clone(), account)
+        .map_err(|e| ServerFnError::new(format!("Failed to update account: {}", e)))?;
+    info!("Updated account: {:?}", eid);
+    Ok(())
+}
+
+#[server]
+pub async fn drop_account(eid: Eid) -> Result<(), ServerFnError> {
+    use crate::backend::require_ecs_mut;
+    let mut ecs = require_ecs_mut().await?;
+    ecs.drop_account(eid.clone())
+        .map_err(|e| ServerFnError::new(format!("Failed to drop account: {}", e)))?;
+    info!("Dropped account: {:?}", eid);
+    Ok(())
+}
+
 #[server]
 pub async fn query_lots() -> Result<Vec<LotItem>, ServerFnError> {
     use crate::backend::require_ecs;
@@ -35,20 +86,88 @@ pub async fn query_lots() -> Result<Vec<LotItem>, ServerFnError> {
     Ok(ecs.query_lots())
 }
 
+#[server]
+pub async fn add_lot(lot: Lot) -> Result<Eid, ServerFnError> {
+    use crate::backend::require_ecs_mut;
+    let mut ecs = require_ecs_mut().await?;
+    let eid = ecs
+        .insert_lot(lot)
+        .map_err(|e| ServerFnError::new(format!("Failed to add lot: {}", e)))?;
+    info!("Added lot: {:?}", eid);
+    Ok(eid)
+}
+
+#[server]
+pub async fn preview_lot_import(
+    csv: String,
+    mapping: ColumnMapping,
+    account: Eid,
+) -> Result<ImportPreview, ServerFnError> {
+    use crate::backend::require_ecs;
+    use crate::data::broker::parse_broker_lots;
+    let ecs = require_ecs().await?;
+    let mut preview = parse_broker_lots(csv.as_bytes(), &mapping, &account)
+        .map_err(|e| ServerFnError::new(format!("Failed to read export: {}", e)))?;
+    preview.mark_duplicates(&ecs.query_lots());
+    Ok(preview)
+}
+
+#[server]
+pub async fn import_lots(lots: Vec<Lot>) -> Result<Vec<Eid>, ServerFnError> {
+    use crate::backend::require_ecs_mut;
+    let mut ecs = require_ecs_mut().await?;
+    let eids = ecs
+        .insert_lots(lots)
+        .map_err(|e| ServerFnError::new(format!("Failed to import lots: {}", e)))?;
+    info!("Imported {} lots", eids.len());
+    Ok(eids)
+}
+
+#[server]
+pub async fn update_lot(eid: Eid, lot: Lot) -> Result<(), ServerFnError> {
+    use crate::backend::require_ecs_mut;
+    let mut ecs = require_ecs_mut().await?;
+    ecs.update_lot(eid.clone(), lot)
+        .map_err(|e| ServerFnError::new(format!("Failed to update lot: {}", e)))?;
+    info!("Updated lot: {:?}", eid);
+    Ok(())
+}
+
 #[server]
 pub async fn drop_lot(eid: Eid) -> Result<(), ServerFnError> {
-    use crate::api::session::fetch_session;
-    let mut session = fetch_session()
-        .await?
-        .ok_or(ServerFnError::new("No session"))?;
-    session
-        .ecs
-        .drop_lot(eid.clone())
+    use crate::backend::require_ecs_mut;
+    let mut ecs = require_ecs_mut().await?;
+    ecs.drop_lot(eid.clone())
         .map_err(|_| ServerFnError::new("Failed to drop lot"))?;
     info!("Dropped lot: {:?}", eid);
     Ok(())
 }
 
+#[server]
+pub async fn sell_lots(sale: Sale, method: ReliefMethod) -> Result<(), ServerFnError> {
+    use crate::backend::require_ecs_mut;
+    let mut ecs = require_ecs_mut().await?;
+    let eids = ecs
+        .sell(sale, method)
+        .map_err(|e| ServerFnError::new(format!("Failed to sell: {}", e)))?;
+    info!("Recorded realized gains: {:?}", eids);
+    Ok(())
+}
+
+#[server]
+pub async fn query_realized() -> Result<HashMap<Eid, RealizedGain>, ServerFnError> {
+    use crate::backend::require_ecs;
+    let ecs = require_ecs().await?;
+    Ok(ecs.query_realized())
+}
+
+#[server]
+pub async fn query_wash_sales() -> Result<Vec<WashSale>, ServerFnError> {
+    use crate::backend::require_ecs;
+    let ecs = require_ecs().await?;
+    Ok(ecs.wash_sales())
+}
+
 #[server]
 pub async fn query_products() -> Result<Vec<Product>, ServerFnError> {
     use crate::backend::require_ecs;
@@ -57,15 +176,201 @@ pub async fn query_products() -> Result<Vec<Product>, ServerFnError> {
 }
 
 #[server]
-pub async fn update_product_prices(csv: String) -> Result<(), ServerFnError> {
+pub async fn add_product(product: Product) -> Result<(), ServerFnError> {
+    use crate::backend::require_ecs_mut;
+    let mut ecs = require_ecs_mut().await?;
+    let symbol = product.symbol().to_string();
+    ecs.insert_product(product)
+        .map_err(|e| ServerFnError::new(format!("Failed to add product: {}", e)))?;
+    info!("Added product: {}", symbol);
+    Ok(())
+}
+
+#[server]
+pub async fn update_product(symbol: String, product: Product) -> Result<(), ServerFnError> {
+    use crate::backend::require_ecs_mut;
+    let mut ecs = require_ecs_mut().await?;
+    ecs.update_product(&symbol, product)
+        .map_err(|e| ServerFnError::new(format!("Failed to update product: {}", e)))?;
+    info!("Updated product: {}", symbol);
+    Ok(())
+}
+
+#[server]
+pub async fn drop_product(symbol: String) -> Result<(), ServerFnError> {
+    use crate::backend::require_ecs_mut;
+    let mut ecs = require_ecs_mut().await?;
+    ecs.drop_product(&symbol)
+        .map_err(|e| ServerFnError::new(format!("Failed to drop product: {}", e)))?;
+    info!("Dropped product: {}", symbol);
+    Ok(())
+}
+
+#[server]
+pub async fn query_price_history(symbol: String) -> Result<Vec<SharePrice>, ServerFnError> {
+    use crate::backend::require_ecs;
+    let ecs = require_ecs().await?;
+    let history = ecs
+        .price_history()
+        .map_err(|e| ServerFnError::new(format!("Failed to read price history: {}", e)))?;
+    Ok(history.series(&symbol))
+}
+
+/// Products priced with their latest quote at or before `time`; those not yet quoted are left out.
+#[server]
+pub async fn query_products_as_of(
+    time: chrono::DateTime<chrono::Utc>,
+) -> Result<Vec<Product>, ServerFnError> {
+    use crate::backend::require_ecs;
+    let ecs = require_ecs().await?;
+    let history = ecs
+        .price_history()
+        .map_err(|e| ServerFnError::new(format!("Failed to read price history: {}", e)))?;
+    Ok(history.products_as_of(&ecs.query_products(), time))
+}
+
+#[server]
+pub async fn query_net_worth_history() -> Result<Vec<NetWorthSnapshot>, ServerFnError> {
+    use crate::backend::require_ecs;
+    let ecs = require_ecs().await?;
+    ecs.net_worth_history()
+        .map_err(|e| ServerFnError::new(format!("Failed to read net worth history: {}", e)))
+}
+
+#[server]
+pub async fn query_tax_settings() -> Result<TaxSettings, ServerFnError> {
+    use crate::backend::require_ecs;
+    let ecs = require_ecs().await?;
+    Ok(ecs.tax.clone())
+}
+
+#[server]
+pub async fn update_tax_settings(settings: TaxSettings) -> Result<(), ServerFnError> {
+    use crate::backend::require_ecs_mut;
+    let mut ecs = require_ecs_mut().await?;
+    ecs.update_tax_settings(settings)
+        .map_err(|e| ServerFnError::new(format!("Failed to save tax settings: {}", e)))?;
+    info!("Updated tax settings");
+    Ok(())
+}
+
+#[server]
+pub async fn query_currency_settings() -> Result<CurrencySettings, ServerFnError> {
+    use crate::backend::require_ecs;
+    let ecs = require_ecs().await?;
+    Ok(ecs.currency.clone())
+}
+
+#[server]
+pub async fn update_currency_settings(settings: CurrencySettings) -> Result<(), ServerFnError> {
+    use crate::backend::require_ecs_mut;
+    use crate::data::fx::is_currency_code;
+    if !is_currency_code(&settings.reporting_currency) {
+        return Err(ServerFnError::new(format!(
+            "Invalid currency '{}'",
+            settings.reporting_currency
+        )));
+    }
+    let mut ecs = require_ecs_mut().await?;
+    ecs.update_currency_settings(settings)
+        .map_err(|e| ServerFnError::new(format!("Failed to save currency settings: {}", e)))?;
+    info!("Updated currency settings");
+    Ok(())
+}
+
+#[server]
+pub async fn query_fx_rates() -> Result<FxTable, ServerFnError> {
     use crate::backend::require_ecs;
-    use crate::data::yf;
-    info!("Importing prices");
-    let market_prices = yf::parse_market_prices(csv.as_bytes())
+    let ecs = require_ecs().await?;
+    Ok(ecs.fx.clone())
+}
+
+#[server]
+pub async fn update_fx_rates(
+    file_name: String,
+    content: String,
+) -> Result<FxImport, ServerFnError> {
+    use crate::backend::require_ecs_mut;
+    use crate::data::{fx, price_source};
+    info!("Importing exchange rates from {}", file_name);
+    let (source, parsed) = price_source::parse_prices(&file_name, content.as_bytes())
+        .map_err(|e| ServerFnError::new(format!("Failed to parse exchange rates: {}", e)))?;
+    let (rates, skipped) = fx::rates_from_prices(parsed.rows);
+    let mut ecs = require_ecs_mut().await?;
+    let updated = ecs
+        .update_fx_rates(rates)
+        .map_err(|e| ServerFnError::new(format!("Failed to update exchange rates: {}", e)))?;
+    info!(
+        "Updated exchange rates from {}: {} updated, {} skipped, {} bad rows",
+        source,
+        updated.len(),
+        skipped.len(),
+        parsed.errors.len()
+    );
+    Ok(FxImport {
+        source: source.to_string(),
+        updated,
+        skipped,
+        errors: parsed.errors,
+    })
+}
+
+#[server]
+pub async fn update_product_prices(
+    file_name: String,
+    content: String,
+) -> Result<PriceImport, ServerFnError> {
+    use crate::backend::require_ecs_mut;
+    use crate::data::price_source;
+    info!("Importing prices from {}", file_name);
+    let (source, parsed) = price_source::parse_prices(&file_name, content.as_bytes())
         .map_err(|e| ServerFnError::new(format!("Failed to parse market prices: {}", e)))?;
-    let mut ecs = require_ecs().await?;
-    ecs.update_prices(market_prices)
+    let mut ecs = require_ecs_mut().await?;
+    let update = ecs
+        .update_prices(parsed.rows)
         .map_err(|e| ServerFnError::new(format!("Failed to update prices: {}", e)))?;
-    info!("Updated prices");
-    Ok(())
+    info!(
+        "Updated prices from {}: {} matched, {} unmatched, {} unpriced, {} bad rows",
+        source,
+        update.matched.len(),
+        update.unmatched.len(),
+        update.unpriced.len(),
+        parsed.errors.len()
+    );
+    Ok(PriceImport {
+        source: source.to_string(),
+        update,
+        errors: parsed.errors,
+    })
+}
+
+#[server]
+pub async fn query_refresh_status() -> Result<Option<RefreshStatus>, ServerFnError> {
+    use crate::backend::{refresh, require_user, user_data_path};
+    let user = require_user().await?;
+    refresh::read_status(&user_data_path(&user.username))
+        .map_err(|e| ServerFnError::new(format!("Failed to read refresh status: {}", e)))
+}
+
+#[server]
+pub async fn query_history() -> Result<Vec<ChangeItem>, ServerFnError> {
+    use crate::api::ecs::journal::change_items;
+    use crate::backend::require_ecs;
+    let ecs = require_ecs().await?;
+    let entries = ecs
+        .journal()
+        .map_err(|e| ServerFnError::new(format!("Failed to read history: {}", e)))?;
+    Ok(change_items(&entries, &ecs.accounts))
+}
+
+#[server]
+pub async fn undo_last_change() -> Result<Option<String>, ServerFnError> {
+    use crate::backend::require_ecs_mut;
+    let mut ecs = require_ecs_mut().await?;
+    let entry = ecs
+        .undo_last()
+        .map_err(|e| ServerFnError::new(format!("Failed to undo: {}", e)))?;
+    let description = entry.map(|entry| entry.event.describe(&ecs.accounts));
+    info!("Undid change: {:?}", description);
+    Ok(description)
 }
@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::OwnedMutexGuard;
+
+/// How long a mutation waits for another one by the same user before giving up.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+static USER_LOCKS: LazyLock<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Error, Debug)]
+pub enum LockError {
+    #[error("Another change to {0}'s data is still in progress, try again")]
+    Conflict(String),
+}
+
+/// Serializes changes to one user's data. Hold the guard for the whole read-modify-write.
+pub async fn lock_user(username: &str) -> Result<OwnedMutexGuard<()>, LockError> {
+    lock_user_within(username, LOCK_TIMEOUT).await
+}
+
+async fn lock_user_within(
+    username: &str,
+    timeout: Duration,
+) -> Result<OwnedMutexGuard<()>, LockError> {
+    let lock = {
+        let mut locks = USER_LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+        locks.entry(username.to_string()).or_default().clone()
+    };
+    tokio::time::timeout(timeout, lock.lock_owned())
+        .await
+        .map_err(|_| LockError::Conflict(username.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_second_writer_waits_then_conflicts() {
+        let held = lock_user("alice").await.unwrap();
+        let other_user = lock_user_within("bob", Duration::from_millis(10)).await;
+        assert!(other_user.is_ok());
+        let same_user = lock_user_within("alice", Duration::from_millis(10)).await;
+        assert!(matches!(same_user, Err(LockError::Conflict(_))));
+        drop(held);
+        let after_release = lock_user_within("alice", Duration::from_millis(10)).await;
+        assert!(after_release.is_ok());
+    }
+}
@@ -0,0 +1,40 @@
+use crate::api::ecs::storage::sqlite;
+use crate::backend::{all_users, user_data_path};
+use std::path::Path;
+
+/// Handles `terrapin-dx migrate-sqlite [user…]`, moving each named user's CSV data into SQLite,
+/// or every user under `data/` when none are named. Returns the process exit code.
+pub fn run(users: Vec<String>) -> i32 {
+    let users = if users.is_empty() {
+        match all_users(Path::new("data")) {
+            Ok(users) => users,
+            Err(e) => {
+                eprintln!("Failed to list users in data/: {}", e);
+                return 1;
+            }
+        }
+    } else {
+        users
+    };
+    let mut failures = 0;
+    for user in users {
+        match sqlite::migrate(&user_data_path(&user)) {
+            Ok(snapshot) => println!(
+                "{}: migrated {} lots, {} products and {} realized gains",
+                user,
+                snapshot.lots.len(),
+                snapshot.products.len(),
+                snapshot.realized.len()
+            ),
+            Err(e) => {
+                eprintln!("{}: {}", user, e);
+                failures += 1;
+            }
+        }
+    }
+    if failures == 0 {
+        0
+    } else {
+        1
+    }
+}
@@ -1,22 +1,90 @@
 use crate::api::ecs::Ecs;
 use crate::api::{active_user, User};
 use dioxus::fullstack::ServerFnError;
-use std::path::PathBuf;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use tokio::sync::OwnedMutexGuard;
 
+pub mod lock;
+#[cfg(feature = "sqlite")]
+pub mod migrate;
 pub mod passkey;
+pub mod refresh;
 pub mod session;
 
 pub fn user_data_path(user: impl AsRef<str>) -> PathBuf {
     PathBuf::from("data").join(user.as_ref())
 }
 
+/// Names of the users with a directory under `data_path`, sorted.
+pub fn all_users(data_path: &Path) -> std::io::Result<Vec<String>> {
+    let mut users = Vec::new();
+    for entry in std::fs::read_dir(data_path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            users.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    users.sort();
+    Ok(users)
+}
+
 pub async fn require_ecs() -> dioxus::Result<Ecs, ServerFnError> {
     let user = require_user().await?;
-    let data_path = user_data_path(&user.username);
-    Ecs::connect(&data_path)
+    connect_user(&user.username).await
+}
+
+/// Connects to a user's `Ecs` for reading. Data still naming legacy accounts is migrated first,
+/// under the user's lock so that concurrent requests cannot each create the accounts.
+pub async fn connect_user(username: &str) -> dioxus::Result<Ecs, ServerFnError> {
+    let data_path = user_data_path(username);
+    let ecs = connect(&data_path)?;
+    if !ecs.has_legacy_accounts() {
+        return Ok(ecs);
+    }
+    Ok(connect_locked(username).await?.ecs)
+}
+
+fn connect(data_path: &Path) -> dioxus::Result<Ecs, ServerFnError> {
+    Ecs::connect(data_path)
         .map_err(|e| ServerFnError::new(format!("Failed to connect to ECS: {}", e)))
 }
 
+/// The user's `Ecs` loaded while holding their write lock, so changes made through it
+/// cannot interleave with another request's.
+pub struct EcsGuard {
+    ecs: Ecs,
+    _lock: OwnedMutexGuard<()>,
+}
+
+impl Deref for EcsGuard {
+    type Target = Ecs;
+    fn deref(&self) -> &Self::Target {
+        &self.ecs
+    }
+}
+
+impl DerefMut for EcsGuard {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.ecs
+    }
+}
+
+pub async fn require_ecs_mut() -> dioxus::Result<EcsGuard, ServerFnError> {
+    let user = require_user().await?;
+    connect_locked(&user.username).await
+}
+
+async fn connect_locked(username: &str) -> dioxus::Result<EcsGuard, ServerFnError> {
+    let lock = lock::lock_user(username)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    let mut ecs = connect(&user_data_path(username))?;
+    ecs.migrate_accounts()
+        .map_err(|e| ServerFnError::new(format!("Failed to migrate accounts: {}", e)))?;
+    Ok(EcsGuard { ecs, _lock: lock })
+}
+
 pub async fn require_user() -> dioxus::Result<User, ServerFnError> {
     active_user().await?.ok_or(ServerFnError::new("No user"))
 }
@@ -0,0 +1,276 @@
+use crate::api::ecs::atomic::write_atomically;
+use crate::api::ecs::{ConnectError, DropError, Ecs, WriteError};
+use crate::backend::lock::{self, LockError};
+use crate::backend::{all_users, user_data_path};
+use crate::data::quote::{self, Quote, RefreshStatus, SymbolRefresh};
+use chrono::Utc;
+use dioxus::logger::tracing::{info, warn};
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+use std::time::Duration;
+use thiserror::Error;
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(15 * 60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where and how often to fetch quotes, from `TERRAPIN_QUOTE_URL` and
+/// `TERRAPIN_QUOTE_INTERVAL_SECS`. The URL's `{symbols}` is replaced with the comma separated,
+/// percent-encoded symbols and aliases of a user's products, and the endpoint answers with a JSON
+/// array of quotes like `{"symbol": "AAPL", "price": 172.6, "time": "2024-03-15T20:00:00Z"}`
+/// or `{"symbol": "XYZ", "error": "Unknown symbol"}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefreshConfig {
+    pub url: String,
+    pub interval: Duration,
+}
+
+impl RefreshConfig {
+    pub fn from_env() -> Option<Self> {
+        let url = std::env::var("TERRAPIN_QUOTE_URL").ok()?;
+        let interval = std::env::var("TERRAPIN_QUOTE_INTERVAL_SECS")
+            .ok()
+            .and_then(|secs| secs.parse::<u64>().ok())
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_INTERVAL);
+        Some(RefreshConfig { url, interval })
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RefreshError {
+    #[error("HTTP error: {0}")]
+    HttpError(#[from] reqwest::Error),
+
+    #[error("Quote provider returned {0}")]
+    HttpStatus(reqwest::StatusCode),
+
+    #[error("Serde error: {0}")]
+    SerdeJsonError(#[from] serde_json::Error),
+
+    #[error("IO error: {0}")]
+    IOError(#[from] std::io::Error),
+
+    #[error("Connect error: {0}")]
+    ConnectError(#[from] ConnectError),
+
+    #[error("Lock error: {0}")]
+    LockError(#[from] LockError),
+
+    #[error("Update error: {0}")]
+    UpdateError(#[from] DropError),
+
+    #[error("Failed to migrate accounts: {0}")]
+    MigrateError(#[from] WriteError),
+}
+
+/// Starts refreshing every user's prices in the background if a quote provider is configured.
+/// Later calls do nothing.
+pub fn spawn() {
+    static STARTED: Once = Once::new();
+    let Some(config) = RefreshConfig::from_env() else {
+        return;
+    };
+    STARTED.call_once(|| {
+        info!(
+            "Refreshing prices from {} every {:?}",
+            config.url, config.interval
+        );
+        tokio::spawn(run(config));
+    });
+}
+
+async fn run(config: RefreshConfig) {
+    let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("Price refresh disabled: {}", e);
+            return;
+        }
+    };
+    let mut interval = tokio::time::interval(config.interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let users = match all_users(Path::new("data")) {
+            Ok(users) => users,
+            Err(e) => {
+                warn!("Price refresh could not list users: {}", e);
+                continue;
+            }
+        };
+        for user in users {
+            match refresh_user(&client, &config.url, &user, &user_data_path(&user)).await {
+                Ok(RefreshStatus {
+                    error: Some(error), ..
+                }) => warn!("Price refresh failed for {}: {}", user, error),
+                Ok(status) => {
+                    let failed = status
+                        .symbols
+                        .values()
+                        .filter(|refresh| matches!(refresh, SymbolRefresh::Failed(_)))
+                        .count();
+                    info!(
+                        "Refreshed prices for {}: {} symbols, {} failed",
+                        user,
+                        status.symbols.len(),
+                        failed
+                    );
+                }
+                Err(e) => warn!("Price refresh failed for {}: {}", user, e),
+            }
+        }
+    }
+}
+
+/// Fetches quotes for a user's products and applies them, recording the outcome for each symbol.
+/// The user's lock is only held while the prices are written, not during the request.
+pub async fn refresh_user(
+    client: &reqwest::Client,
+    url: &str,
+    username: &str,
+    data_path: &Path,
+) -> Result<RefreshStatus, RefreshError> {
+    let products = Ecs::connect(data_path)?.products;
+    if products.is_empty() {
+        return Ok(RefreshStatus::failed(Utc::now(), "No products".to_string()));
+    }
+    let symbols = quote::quote_symbols(&products);
+    let status = match fetch_quotes(client, url, &symbols).await {
+        Ok(quotes) => {
+            let (prices, status) = quote::resolve_quotes(&products, quotes, Utc::now());
+            let _lock = lock::lock_user(username).await?;
+            let mut ecs = Ecs::connect(data_path)?;
+            ecs.migrate_accounts()?;
+            ecs.update_prices(prices)?;
+            status
+        }
+        Err(e) => RefreshStatus::failed(Utc::now(), e.to_string()),
+    };
+    write_status(data_path, &status)?;
+    Ok(status)
+}
+
+async fn fetch_quotes(
+    client: &reqwest::Client,
+    url: &str,
+    symbols: &[String],
+) -> Result<Vec<Quote>, RefreshError> {
+    let response = client.get(quote_url(url, symbols)).send().await?;
+    if !response.status().is_success() {
+        return Err(RefreshError::HttpStatus(response.status()));
+    }
+    let body = response.text().await?;
+    Ok(serde_json::from_str(&body)?)
+}
+
+/// Fills the URL's `{symbols}` with the symbols, percent-encoded so that ones like `EURUSD=X` or
+/// `^GSPC` reach the provider intact.
+fn quote_url(url: &str, symbols: &[String]) -> String {
+    let encoded = symbols
+        .iter()
+        .map(|symbol| {
+            symbol
+                .bytes()
+                .map(|byte| match byte {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                        (byte as char).to_string()
+                    }
+                    _ => format!("%{:02X}", byte),
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>();
+    url.replace("{symbols}", &encoded.join(","))
+}
+
+pub fn read_status(data_path: &Path) -> Result<Option<RefreshStatus>, RefreshError> {
+    let path = status_path(data_path);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let bytes = std::fs::read(path)?;
+    Ok(Some(serde_json::from_slice(&bytes)?))
+}
+
+fn write_status(data_path: &Path, status: &RefreshStatus) -> Result<(), RefreshError> {
+    write_atomically(&status_path(data_path), &serde_json::to_vec_pretty(status)?)?;
+    Ok(())
+}
+
+fn status_path(data_path: &Path) -> PathBuf {
+    data_path.join("price_refresh.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::ecs::scratch::ScratchDir;
+    use axum::extract::Query;
+    use axum::routing::get;
+    use rust_decimal_macros::dec;
+    use std::collections::HashMap;
+
+    const PRODUCTS_CSV: &str =
+        "type,symbol,name,outstanding_shares,share_price,share_price_as_of\n\
+        stock,AAPL,Apple Inc.,100,123.45,2021-01-01T00:00:00Z\n\
+        etf,VTI,Vanguard Total Stock Market ETF,,200,2021-01-01T00:00:00Z\n";
+
+    /// Serves quotes for AAPL only, echoing the requested symbols back as unknown otherwise.
+    async fn mock_provider() -> String {
+        let quotes = |Query(query): Query<HashMap<String, String>>| async move {
+            let quotes = query["symbols"]
+                .split(',')
+                .map(|symbol| match symbol {
+                    "AAPL" => serde_json::json!({"symbol": "AAPL", "price": 172.6}),
+                    _ => serde_json::json!({"symbol": symbol, "error": "Unknown symbol"}),
+                })
+                .collect::<Vec<_>>();
+            axum::Json(quotes)
+        };
+        let router = axum::Router::new().route("/quotes", get(quotes));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        format!("http://{}/quotes?symbols={{symbols}}", address)
+    }
+
+    #[test]
+    fn test_quote_url_encodes_symbols() {
+        let symbols = ["BRK.B", "EURUSD=X", "^GSPC", "A&B"].map(String::from);
+        assert_eq!(
+            quote_url("https://quotes.test/q?symbols={symbols}", &symbols),
+            "https://quotes.test/q?symbols=BRK.B,EURUSD%3DX,%5EGSPC,A%26B"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refresh_user_from_mock_provider() {
+        let scratch = ScratchDir::create();
+        let dir = scratch.path();
+        std::fs::write(dir.join("products.csv"), PRODUCTS_CSV).unwrap();
+        std::fs::write(dir.join("lots.csv"), "account,time,product,quantity,eid\n").unwrap();
+        let url = mock_provider().await;
+        let client = reqwest::Client::new();
+
+        let status = refresh_user(&client, &url, "refresh-test", dir)
+            .await
+            .unwrap();
+        assert_eq!(status.error, None);
+        assert!(matches!(status.symbols["AAPL"], SymbolRefresh::Updated(_)));
+        assert_eq!(
+            status.symbols["VTI"],
+            SymbolRefresh::Failed("Unknown symbol".into())
+        );
+        let ecs = Ecs::connect(dir).unwrap();
+        assert_eq!(ecs.products[0].share_price().height, dec!(172.6));
+        assert_eq!(ecs.products[1].share_price().height, dec!(200));
+        assert_eq!(read_status(dir).unwrap(), Some(status));
+
+        let unreachable = "http://127.0.0.1:9/quotes?symbols={symbols}";
+        let status = refresh_user(&client, unreachable, "refresh-test", dir)
+            .await
+            .unwrap();
+        assert!(status.error.is_some());
+    }
+}
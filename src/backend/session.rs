@@ -1,4 +1,3 @@
-use crate::api::ecs::Ecs;
 use crate::api::session::SessionState;
 use crate::backend;
 use dioxus::prelude::*;
@@ -11,8 +10,7 @@ pub async fn load_session(login_name: String) -> Result<SessionState, ServerFnEr
             login_name
         )));
     }
-    let ecs = Ecs::connect(&data_path)
-        .map_err(|e| ServerFnError::new(format!("Failed to connect to ECS: {}", e)))?;
+    let ecs = backend::connect_user(&login_name).await?;
     let state = SessionState { login_name, ecs };
     Ok(state)
 }
@@ -14,12 +14,17 @@ pub fn ProductLabel(symbol: String, name: String) -> Element {
     }
 }
 
+/// A price and its time, with the time highlighted when the price is `stale`.
 #[component]
-pub fn SharePriceLabel(share_price: SharePrice) -> Element {
+pub fn SharePriceLabel(share_price: SharePrice, #[props(default)] stale: bool) -> Element {
     rsx! {
         div { class: "tags has-addons",
             span { class: "tag is-dark", "{format_share_price(&share_price)}" }
-            span { class: "tag", "{format_time(&share_price.time)}"}
+            span { class: "tag",
+                class: if stale { "is-warning" },
+                title: if stale { "Stale price" },
+                "{format_time(&share_price.time)}"
+            }
         }
     }
 }
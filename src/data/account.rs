@@ -0,0 +1,167 @@
+use crate::api::ecs::Eid;
+use crate::data::tax::TaxTreatment;
+use crate::data::{FormatError, ParseError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Account {
+    pub name: String,
+    pub kind: AccountKind,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountKind {
+    #[default]
+    Taxable,
+    /// A traditional IRA, 401(k) or other pre-tax retirement account.
+    Ira,
+    Roth,
+    Hsa,
+}
+
+impl AccountKind {
+    pub const ALL: [AccountKind; 4] = [
+        AccountKind::Taxable,
+        AccountKind::Ira,
+        AccountKind::Roth,
+        AccountKind::Hsa,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AccountKind::Taxable => "Taxable",
+            AccountKind::Ira => "IRA",
+            AccountKind::Roth => "Roth",
+            AccountKind::Hsa => "HSA",
+        }
+    }
+
+    /// HSA withdrawals are assumed to pay for qualified medical expenses.
+    pub fn treatment(&self) -> TaxTreatment {
+        match self {
+            AccountKind::Taxable => TaxTreatment::Taxable,
+            AccountKind::Ira => TaxTreatment::TaxDeferred,
+            AccountKind::Roth | AccountKind::Hsa => TaxTreatment::TaxFree,
+        }
+    }
+}
+
+impl From<TaxTreatment> for AccountKind {
+    fn from(treatment: TaxTreatment) -> Self {
+        match treatment {
+            TaxTreatment::Taxable => AccountKind::Taxable,
+            TaxTreatment::TaxDeferred => AccountKind::Ira,
+            TaxTreatment::TaxFree => AccountKind::Roth,
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum AccountError {
+    #[error("Account name is required")]
+    MissingName,
+}
+
+impl Account {
+    pub fn validate(&self) -> Result<(), AccountError> {
+        if self.name.trim().is_empty() {
+            return Err(AccountError::MissingName);
+        }
+        Ok(())
+    }
+}
+
+/// The account's name, or its id if the account is unknown.
+pub fn account_name(accounts: &HashMap<Eid, Account>, eid: &Eid) -> String {
+    accounts
+        .get(eid)
+        .map(|account| account.name.clone())
+        .unwrap_or_else(|| eid.as_str().to_string())
+}
+
+/// Whether sales in the account are reported for tax, which only taxable accounts' are. An
+/// unknown account counts as taxable, as it does for the liquidation estimate.
+pub fn is_taxable(accounts: &HashMap<Eid, Account>, eid: &Eid) -> bool {
+    accounts
+        .get(eid)
+        .is_none_or(|account| account.kind.treatment() == TaxTreatment::Taxable)
+}
+
+pub fn parse_accounts(csv_data: &[u8]) -> Result<HashMap<Eid, Account>, ParseError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(csv_data);
+    let accounts = reader
+        .deserialize::<AccountCsvRow>()
+        .map(|row| {
+            row.map(|row| {
+                let account = Account {
+                    name: row.name,
+                    kind: row.kind,
+                };
+                (row.eid, account)
+            })
+        })
+        .collect::<Result<HashMap<_, _>, _>>()?;
+    Ok(accounts)
+}
+
+pub fn format_accounts(accounts: &HashMap<Eid, Account>) -> Result<String, FormatError> {
+    let mut rows = accounts
+        .iter()
+        .map(|(eid, account)| AccountCsvRow {
+            name: account.name.clone(),
+            kind: account.kind,
+            eid: eid.clone(),
+        })
+        .collect::<Vec<_>>();
+    rows.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    let data = writer.into_inner()?;
+    let string = String::from_utf8(data)?;
+    Ok(string)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AccountCsvRow {
+    name: String,
+    kind: AccountKind,
+    eid: Eid,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accounts_round_trip() {
+        let accounts = HashMap::from([
+            (
+                Eid::from("a0"),
+                Account {
+                    name: "Brokerage".into(),
+                    kind: AccountKind::Taxable,
+                },
+            ),
+            (
+                Eid::from("a1"),
+                Account {
+                    name: "Health".into(),
+                    kind: AccountKind::Hsa,
+                },
+            ),
+        ]);
+        let string = format_accounts(&accounts).unwrap();
+        assert_eq!(
+            string,
+            "name,kind,eid\nBrokerage,taxable,a0\nHealth,hsa,a1\n"
+        );
+        assert_eq!(parse_accounts(string.as_bytes()).unwrap(), accounts);
+    }
+}
@@ -0,0 +1,369 @@
+use crate::api::ecs::{Eid, LotItem};
+use crate::data::portfolio::Lot;
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Column headers to read from a broker's transaction export. Headers match case-insensitively.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ColumnMapping {
+    pub date: String,
+    pub symbol: String,
+    pub quantity: String,
+    pub price: Option<String>,
+    /// Columns summed into the lot's fees, e.g. separate commission and fee columns.
+    pub fees: Vec<String>,
+    /// A column describing the transaction. When present only buys are imported.
+    pub action: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BrokerPreset {
+    Generic,
+    Fidelity,
+    Schwab,
+    Vanguard,
+}
+
+impl BrokerPreset {
+    pub const ALL: [BrokerPreset; 4] = [
+        BrokerPreset::Generic,
+        BrokerPreset::Fidelity,
+        BrokerPreset::Schwab,
+        BrokerPreset::Vanguard,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BrokerPreset::Generic => "Generic",
+            BrokerPreset::Fidelity => "Fidelity",
+            BrokerPreset::Schwab => "Schwab",
+            BrokerPreset::Vanguard => "Vanguard",
+        }
+    }
+
+    pub fn mapping(&self) -> ColumnMapping {
+        let column = |name: &str| name.to_string();
+        match self {
+            BrokerPreset::Generic => ColumnMapping {
+                date: column("date"),
+                symbol: column("symbol"),
+                quantity: column("quantity"),
+                price: Some(column("price")),
+                fees: Vec::new(),
+                action: None,
+            },
+            BrokerPreset::Fidelity => ColumnMapping {
+                date: column("Run Date"),
+                symbol: column("Symbol"),
+                quantity: column("Quantity"),
+                price: Some(column("Price ($)")),
+                fees: vec![column("Commission ($)"), column("Fees ($)")],
+                action: Some(column("Action")),
+            },
+            BrokerPreset::Schwab => ColumnMapping {
+                date: column("Date"),
+                symbol: column("Symbol"),
+                quantity: column("Quantity"),
+                price: Some(column("Price")),
+                fees: vec![column("Fees & Comm")],
+                action: Some(column("Action")),
+            },
+            BrokerPreset::Vanguard => ColumnMapping {
+                date: column("Trade Date"),
+                symbol: column("Symbol"),
+                quantity: column("Shares"),
+                price: Some(column("Share Price")),
+                fees: vec![column("Commissions and Fees")],
+                action: Some(column("Transaction Type")),
+            },
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("Csv read error: {0}")]
+    CsvReadError(#[from] csv::Error),
+
+    #[error("No header row with a '{0}' column")]
+    MissingHeader(String),
+
+    #[error("Missing column '{0}'")]
+    MissingColumn(String),
+}
+
+/// A lot read from one row of an export.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportedLot {
+    /// The 1-based line number in the export.
+    pub line: usize,
+    pub lot: Lot,
+    /// An existing lot that looks like the same purchase.
+    pub duplicate_of: Option<Eid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkippedRow {
+    pub line: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImportPreview {
+    pub lots: Vec<ImportedLot>,
+    pub skipped: Vec<SkippedRow>,
+}
+
+/// Reads purchases from a broker export into lots for `account`. Exports often carry notes above
+/// the header and totals below the rows, so lines before the header are ignored and rows that do
+/// not parse are reported as skipped rather than failing the import.
+pub fn parse_broker_lots(
+    csv_data: &[u8],
+    mapping: &ColumnMapping,
+    account: &Eid,
+) -> Result<ImportPreview, ImportError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(csv_data);
+    let mut records = reader.records();
+    let header = loop {
+        match records.next() {
+            Some(record) => {
+                let record = record?;
+                if find_column(&record, &mapping.date).is_some() {
+                    break record;
+                }
+            }
+            None => return Err(ImportError::MissingHeader(mapping.date.clone())),
+        }
+    };
+    let required = |name: &str| {
+        find_column(&header, name).ok_or_else(|| ImportError::MissingColumn(name.to_string()))
+    };
+    let optional = |name: &Option<String>| match name {
+        Some(name) => required(name).map(Some),
+        None => Ok(None),
+    };
+    let date = required(&mapping.date)?;
+    let symbol = required(&mapping.symbol)?;
+    let quantity = required(&mapping.quantity)?;
+    let price = optional(&mapping.price)?;
+    let action = optional(&mapping.action)?;
+    let fees = mapping
+        .fees
+        .iter()
+        .map(|name| required(name))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut preview = ImportPreview::default();
+    for record in records {
+        let record = record?;
+        let line = record
+            .position()
+            .map(|p| p.line() as usize)
+            .unwrap_or_default();
+        let field = |index: usize| record.get(index).unwrap_or_default();
+        if record.iter().all(|value| value.is_empty()) {
+            continue;
+        }
+        let mut skip = |reason: String| preview.skipped.push(SkippedRow { line, reason });
+        if let Some(index) = action {
+            if !is_purchase(field(index)) {
+                skip(format!("Not a purchase: '{}'", field(index)));
+                continue;
+            }
+        }
+        let product = field(symbol).to_uppercase();
+        if product.is_empty() {
+            skip("No symbol".to_string());
+            continue;
+        }
+        let Some(time) = parse_trade_date(field(date)) else {
+            skip(format!("Invalid date '{}'", field(date)));
+            continue;
+        };
+        // Some brokers show bought shares as negative, but without an action column confirming a
+        // buy a negative quantity is a sale.
+        let quantity = match parse_number(field(quantity)) {
+            Some(number) if number < Decimal::ZERO && action.is_none() => {
+                skip(format!("Not a purchase: quantity {}", field(quantity)));
+                continue;
+            }
+            Some(quantity) if !quantity.is_zero() => quantity.abs(),
+            _ => {
+                skip(format!("Invalid quantity '{}'", field(quantity)));
+                continue;
+            }
+        };
+        let unit_cost = price.and_then(|index| parse_number(field(index)).map(|n| n.abs()));
+        let fees = fees
+            .iter()
+            .filter_map(|index| parse_number(field(*index)).map(|n| n.abs()))
+            .reduce(|a, b| a + b)
+            .filter(|fees| !fees.is_zero());
+        preview.lots.push(ImportedLot {
+            line,
+            lot: Lot {
+                account: account.clone(),
+                time,
+                product,
+                quantity,
+                unit_cost,
+                fees,
+            },
+            duplicate_of: None,
+        });
+    }
+    Ok(preview)
+}
+
+impl ImportPreview {
+    /// Flags lots that match an existing lot in the same account on the same day.
+    pub fn mark_duplicates(&mut self, existing: &[LotItem]) {
+        for imported in self.lots.iter_mut() {
+            imported.duplicate_of = existing
+                .iter()
+                .find(|item| is_same_purchase(&imported.lot, &item.0))
+                .map(|item| item.to_eid());
+        }
+    }
+}
+
+fn is_same_purchase(a: &Lot, b: &Lot) -> bool {
+    a.account == b.account
+        && a.product == b.product
+        && a.time.date_naive() == b.time.date_naive()
+        && a.quantity == b.quantity
+        && a.unit_cost == b.unit_cost
+}
+
+fn find_column(record: &csv::StringRecord, name: &str) -> Option<usize> {
+    record
+        .iter()
+        .position(|header| header.eq_ignore_ascii_case(name.trim()))
+}
+
+fn is_purchase(action: &str) -> bool {
+    let action = action.to_lowercase();
+    ["buy", "bought", "reinvest"]
+        .iter()
+        .any(|word| action.contains(word))
+}
+
+/// Accepts `MM/DD/YYYY` and `YYYY-MM-DD`, ignoring anything after the date such as Schwab's
+/// "as of" settlement dates.
+fn parse_trade_date(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.split_whitespace().next()?;
+    let date = NaiveDate::parse_from_str(text, "%m/%d/%Y")
+        .or_else(|_| NaiveDate::parse_from_str(text, "%Y-%m-%d"))
+        .ok()?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+/// Parses amounts like `$1,234.50` or `(12.00)`.
+fn parse_number(text: &str) -> Option<Decimal> {
+    let negative = text.starts_with('(') && text.ends_with(')');
+    let digits = text
+        .chars()
+        .filter(|c| !matches!(c, '$' | ',' | '(' | ')' | ' '))
+        .collect::<String>();
+    if digits.is_empty() {
+        return None;
+    }
+    let number = digits.parse::<Decimal>().ok()?;
+    Some(if negative { -number } else { number })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_parse_fidelity_export() {
+        let csv = "\n\
+            Brokerage\n\
+            Run Date,Action,Symbol,Description,Quantity,Price ($),Commission ($),Fees ($),Amount ($)\n\
+            03/15/2024,YOU BOUGHT VANGUARD TOTAL STOCK MKT,VTI,VANGUARD,10,\"$1,250.00\",,0.02,-12500.02\n\
+            03/16/2024,DIVIDEND RECEIVED,VTI,VANGUARD,,,,,12.00\n\
+            \n\
+            \"The data and information in this spreadsheet is provided to you solely for your use\"\n";
+        let account = Eid::from("a0");
+        let preview =
+            parse_broker_lots(csv.as_bytes(), &BrokerPreset::Fidelity.mapping(), &account).unwrap();
+        assert_eq!(
+            preview.lots,
+            vec![ImportedLot {
+                line: 4,
+                lot: Lot {
+                    account: account.clone(),
+                    time: Utc.with_ymd_and_hms(2024, 3, 15, 0, 0, 0).unwrap(),
+                    product: "VTI".into(),
+                    quantity: dec!(10),
+                    unit_cost: Some(dec!(1250.00)),
+                    fees: Some(dec!(0.02)),
+                },
+                duplicate_of: None,
+            }]
+        );
+        assert_eq!(preview.skipped.len(), 2);
+        assert_eq!(preview.skipped[0].line, 5);
+    }
+
+    #[test]
+    fn test_mark_duplicates() {
+        let csv = "Date,Action,Symbol,Quantity,Price,Fees & Comm\n\
+            01/02/2024 as of 12/29/2023,Buy,aapl,5,$190.00,\n\
+            01/03/2024,Buy,AAPL,5,$190.00,\n";
+        let account = Eid::from("a0");
+        let mut preview =
+            parse_broker_lots(csv.as_bytes(), &BrokerPreset::Schwab.mapping(), &account).unwrap();
+        let mut existing = preview.lots[0].lot.clone();
+        existing.time = Utc.with_ymd_and_hms(2024, 1, 2, 15, 30, 0).unwrap();
+        preview.mark_duplicates(&[LotItem(existing, Eid::from("l0"))]);
+        let duplicates = preview
+            .lots
+            .iter()
+            .map(|imported| imported.duplicate_of.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(duplicates, vec![Some(Eid::from("l0")), None]);
+    }
+
+    #[test]
+    fn test_generic_skips_negative_quantity() {
+        let csv = "date,symbol,quantity,price\n\
+            2024-01-02,VTI,4,250\n\
+            2024-02-01,VTI,-3,260\n";
+        let preview = parse_broker_lots(
+            csv.as_bytes(),
+            &BrokerPreset::Generic.mapping(),
+            &Eid::from("a0"),
+        )
+        .unwrap();
+        assert_eq!(preview.lots.len(), 1);
+        assert_eq!(preview.lots[0].lot.quantity, dec!(4));
+        assert_eq!(
+            preview.skipped,
+            vec![SkippedRow {
+                line: 3,
+                reason: "Not a purchase: quantity -3".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_missing_column() {
+        let csv = "date,symbol,shares\n2024-01-02,VTI,1\n";
+        let result = parse_broker_lots(
+            csv.as_bytes(),
+            &BrokerPreset::Generic.mapping(),
+            &Eid::from("a0"),
+        );
+        assert!(matches!(result, Err(ImportError::MissingColumn(column)) if column == "quantity"));
+    }
+}
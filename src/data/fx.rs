@@ -0,0 +1,242 @@
+use crate::data::yf::MarketPrice;
+use crate::data::{FormatError, ParseError, RowError};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// The currency products are priced in when none is given.
+pub const USD: &str = "USD";
+
+/// Whether `code` looks like an ISO 4217 currency code, e.g. `EUR`.
+pub fn is_currency_code(code: &str) -> bool {
+    code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase())
+}
+
+/// The price of one unit of `base` in `quote`, so EUR/USD at 1.08 means one euro buys 1.08
+/// dollars.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FxRate {
+    pub base: String,
+    pub quote: String,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub rate: Decimal,
+    pub time: DateTime<Utc>,
+}
+
+/// The latest rate for each currency pair.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FxTable(Vec<FxRate>);
+
+impl FxTable {
+    pub fn rates(&self) -> &[FxRate] {
+        &self.0
+    }
+
+    /// Records a rate unless the table holds a newer one for the pair, in either direction.
+    /// Returns whether the table changed.
+    pub fn insert(&mut self, rate: FxRate) -> bool {
+        let existing = self.0.iter().position(|known| {
+            (known.base == rate.base && known.quote == rate.quote)
+                || (known.base == rate.quote && known.quote == rate.base)
+        });
+        match existing {
+            Some(index) if self.0[index].time > rate.time => false,
+            Some(index) if self.0[index] == rate => false,
+            Some(index) => {
+                self.0[index] = rate;
+                true
+            }
+            None => {
+                self.0.push(rate);
+                self.0
+                    .sort_by(|a, b| (&a.base, &a.quote).cmp(&(&b.base, &b.quote)));
+                true
+            }
+        }
+    }
+
+    /// How many units of `to` one unit of `from` buys, using a pair quoted either way round or,
+    /// failing that, crossing through a third currency.
+    pub fn rate(&self, from: &str, to: &str) -> Option<Decimal> {
+        if from == to {
+            return Some(Decimal::ONE);
+        }
+        if let Some(rate) = self.direct(from, to) {
+            return Some(rate);
+        }
+        self.0
+            .iter()
+            .flat_map(|rate| [&rate.base, &rate.quote])
+            .filter(|via| *via != from && *via != to)
+            .find_map(|via| Some(self.direct(from, via)? * self.direct(via, to)?))
+    }
+
+    pub fn convert(&self, amount: Decimal, from: &str, to: &str) -> Option<Decimal> {
+        Some(amount * self.rate(from, to)?)
+    }
+
+    fn direct(&self, from: &str, to: &str) -> Option<Decimal> {
+        self.0.iter().find_map(|rate| {
+            if rate.base == from && rate.quote == to {
+                Some(rate.rate)
+            } else if rate.base == to && rate.quote == from {
+                Decimal::ONE.checked_div(rate.rate)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// The currency net worth and holdings are reported in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CurrencySettings {
+    pub reporting_currency: String,
+}
+
+impl Default for CurrencySettings {
+    fn default() -> Self {
+        CurrencySettings {
+            reporting_currency: USD.to_string(),
+        }
+    }
+}
+
+/// The outcome of importing a file of exchange rates.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FxImport {
+    /// The name of the format the file was read as.
+    pub source: String,
+    /// Rates that were new or replaced an older rate.
+    pub updated: Vec<FxRate>,
+    /// Symbols in the file that name no currency pair or have no positive rate.
+    pub skipped: Vec<String>,
+    /// Rows of the file that could not be read.
+    pub errors: Vec<RowError>,
+}
+
+/// Reads a currency pair from a quote symbol like `EURUSD=X`, `EUR/USD` or Stooq's `EURUSD`.
+pub fn parse_pair(symbol: &str) -> Option<(String, String)> {
+    let symbol = symbol.trim().to_uppercase();
+    let symbol = symbol.strip_suffix("=X").unwrap_or(&symbol);
+    let letters = symbol
+        .chars()
+        .filter(|c| !matches!(c, '/' | '-' | '_'))
+        .collect::<String>();
+    if letters.len() != 6 {
+        return None;
+    }
+    let (base, quote) = letters.split_at(3);
+    (is_currency_code(base) && is_currency_code(quote) && base != quote)
+        .then(|| (base.to_string(), quote.to_string()))
+}
+
+/// Reads rates from quotes of currency pairs, returning the symbols that could not be used.
+pub fn rates_from_prices(prices: Vec<MarketPrice>) -> (Vec<FxRate>, Vec<String>) {
+    let mut rates = Vec::new();
+    let mut skipped = Vec::new();
+    for price in prices {
+        let pair = parse_pair(&price.symbol);
+        match pair {
+            Some((base, quote)) if price.share_price.height > Decimal::ZERO => {
+                rates.push(FxRate {
+                    base,
+                    quote,
+                    rate: price.share_price.height,
+                    time: price.share_price.time,
+                });
+            }
+            _ if !skipped.contains(&price.symbol) => skipped.push(price.symbol),
+            _ => {}
+        }
+    }
+    (rates, skipped)
+}
+
+pub fn parse_fx_rates(csv_data: &[u8]) -> Result<FxTable, ParseError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(csv_data);
+    let mut table = FxTable::default();
+    for rate in reader.deserialize::<FxRate>() {
+        table.insert(rate?);
+    }
+    Ok(table)
+}
+
+pub fn format_fx_rates(table: &FxTable) -> Result<String, FormatError> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for rate in table.rates() {
+        writer.serialize(rate)?;
+    }
+    let data = writer.into_inner()?;
+    let string = String::from_utf8(data)?;
+    Ok(string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::market::SharePrice;
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+
+    fn rate(base: &str, quote: &str, rate: Decimal, day: u32) -> FxRate {
+        FxRate {
+            base: base.into(),
+            quote: quote.into(),
+            rate,
+            time: Utc.with_ymd_and_hms(2024, 3, day, 0, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_rates() {
+        let mut table = FxTable::default();
+        assert!(table.insert(rate("EUR", "USD", dec!(1.25), 15)));
+        assert!(table.insert(rate("USD", "JPY", dec!(150), 15)));
+        assert!(!table.insert(rate("USD", "EUR", dec!(0.5), 14)));
+        assert!(table.insert(rate("GBP", "USD", dec!(1.5), 15)));
+        assert_eq!(table.rates().len(), 3);
+        assert_eq!(table.rate("CHF", "CHF"), Some(Decimal::ONE));
+        assert_eq!(table.rate("EUR", "USD"), Some(dec!(1.25)));
+        assert_eq!(table.rate("USD", "EUR"), Some(dec!(0.8)));
+        assert_eq!(table.rate("EUR", "JPY"), Some(dec!(187.5)));
+        assert_eq!(table.convert(dec!(10), "GBP", "EUR"), Some(dec!(12)));
+        assert_eq!(table.rate("EUR", "CHF"), None);
+    }
+
+    #[test]
+    fn test_rates_from_prices() {
+        let time = Utc.with_ymd_and_hms(2024, 3, 15, 0, 0, 0).unwrap();
+        let price = |symbol: &str, height| MarketPrice {
+            symbol: symbol.into(),
+            share_price: SharePrice { height, time },
+        };
+        let (rates, skipped) = rates_from_prices(vec![
+            price("EURUSD=X", dec!(1.08)),
+            price("gbp/usd", dec!(1.25)),
+            price("AAPL", dec!(172)),
+            price("USDJPY", dec!(0)),
+        ]);
+        assert_eq!(
+            rates,
+            vec![
+                rate("EUR", "USD", dec!(1.08), 15),
+                rate("GBP", "USD", dec!(1.25), 15)
+            ]
+        );
+        assert_eq!(skipped, ["AAPL", "USDJPY"]);
+        assert_eq!(parse_pair("USDUSD"), None);
+    }
+
+    #[test]
+    fn test_fx_rates_round_trip() {
+        let mut table = FxTable::default();
+        table.insert(rate("EUR", "USD", dec!(1.08), 15));
+        table.insert(rate("CAD", "USD", dec!(0.74), 15));
+        let csv = format_fx_rates(&table).unwrap();
+        assert!(csv.starts_with("base,quote,rate,time\nCAD,USD,0.74,"));
+        assert_eq!(parse_fx_rates(csv.as_bytes()).unwrap(), table);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::data::market::SharePrice;
+use crate::data::portfolio::Lot;
+use rust_decimal::Decimal;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnrealizedGain {
+    pub market_value: Decimal,
+    pub cost_basis: Decimal,
+    pub gain: Decimal,
+    /// Quantity held in lots without a recorded cost basis and left out of the gain.
+    pub unknown_basis: Decimal,
+}
+
+impl UnrealizedGain {
+    pub fn new(lots: &[Lot], share_price: &SharePrice) -> Self {
+        let mut report = UnrealizedGain {
+            market_value: Decimal::ZERO,
+            cost_basis: Decimal::ZERO,
+            gain: Decimal::ZERO,
+            unknown_basis: Decimal::ZERO,
+        };
+        for lot in lots {
+            match lot.cost_basis() {
+                Some(cost_basis) => {
+                    let market_value = lot.quantity * share_price.height;
+                    report.market_value += market_value;
+                    report.cost_basis += cost_basis;
+                    report.gain += market_value - cost_basis;
+                }
+                None => report.unknown_basis += lot.quantity,
+            }
+        }
+        report
+    }
+
+    /// Raises the cost basis, as when a wash sale's disallowed loss moves onto replacement shares.
+    pub fn adjust_basis(&mut self, amount: Decimal) {
+        self.cost_basis += amount;
+        self.gain -= amount;
+    }
+
+    /// The gain in another currency, where one unit of the product's currency buys `rate` units.
+    pub fn convert(&self, rate: Decimal) -> Self {
+        UnrealizedGain {
+            market_value: self.market_value * rate,
+            cost_basis: self.cost_basis * rate,
+            gain: self.gain * rate,
+            unknown_basis: self.unknown_basis,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use rust_decimal_macros::dec;
+
+    fn lot(quantity: Decimal, unit_cost: Option<Decimal>, fees: Option<Decimal>) -> Lot {
+        Lot {
+            account: "brokerage".into(),
+            time: Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap(),
+            product: "AAPL".into(),
+            quantity,
+            unit_cost,
+            fees,
+        }
+    }
+
+    #[test]
+    fn test_unrealized_gain() {
+        let lots = vec![
+            lot(dec!(10), Some(dec!(100)), Some(dec!(5))),
+            lot(dec!(5), Some(dec!(150)), None),
+            lot(dec!(3), None, None),
+        ];
+        let share_price = SharePrice {
+            height: dec!(120),
+            time: Utc.with_ymd_and_hms(2025, 1, 2, 0, 0, 0).unwrap(),
+        };
+        let report = UnrealizedGain::new(&lots, &share_price);
+        assert_eq!(report.market_value, dec!(1800));
+        assert_eq!(report.cost_basis, dec!(1755));
+        assert_eq!(report.gain, dec!(45));
+        assert_eq!(report.unknown_basis, dec!(3));
+        let converted = report.convert(dec!(0.5));
+        assert_eq!(converted.gain, dec!(22.5));
+        assert_eq!(converted.unknown_basis, dec!(3));
+    }
+}
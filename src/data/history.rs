@@ -0,0 +1,199 @@
+use crate::data::market::{Product, SharePrice};
+use crate::data::yf::MarketPrice;
+use crate::data::{FormatError, ParseError};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Every quote seen for each product, keyed by symbol and then by the time of the quote.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PriceHistory(BTreeMap<String, BTreeMap<DateTime<Utc>, Decimal>>);
+
+impl PriceHistory {
+    /// Records a quote, replacing any other quote for the symbol at the same time. Returns
+    /// whether the history changed.
+    pub fn insert(&mut self, price: &MarketPrice) -> bool {
+        let SharePrice { height, time } = price.share_price;
+        let quotes = self.0.entry(price.symbol.clone()).or_default();
+        quotes.insert(time, height) != Some(height)
+    }
+
+    /// Removes the symbol's quote at `time`. Returns whether the history changed.
+    pub fn remove(&mut self, symbol: &str, time: DateTime<Utc>) -> bool {
+        let Some(quotes) = self.0.get_mut(symbol) else {
+            return false;
+        };
+        let removed = quotes.remove(&time).is_some();
+        if quotes.is_empty() {
+            self.0.remove(symbol);
+        }
+        removed
+    }
+
+    /// Every quote, by symbol and then oldest first.
+    pub fn prices(&self) -> Vec<MarketPrice> {
+        self.symbols()
+            .flat_map(|symbol| {
+                self.series(symbol)
+                    .into_iter()
+                    .map(|share_price| MarketPrice {
+                        symbol: symbol.to_string(),
+                        share_price,
+                    })
+            })
+            .collect()
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+
+    /// The symbol's quotes, oldest first.
+    pub fn series(&self, symbol: &str) -> Vec<SharePrice> {
+        self.0
+            .get(symbol)
+            .map(|quotes| {
+                quotes
+                    .iter()
+                    .map(|(time, height)| SharePrice {
+                        height: *height,
+                        time: *time,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The latest quote at or before `time`.
+    pub fn price_as_of(&self, symbol: &str, time: DateTime<Utc>) -> Option<SharePrice> {
+        let (time, height) = self.0.get(symbol)?.range(..=time).next_back()?;
+        Some(SharePrice {
+            height: *height,
+            time: *time,
+        })
+    }
+
+    /// The products priced as of `time`, leaving out those without a quote by then.
+    pub fn products_as_of(&self, products: &[Product], time: DateTime<Utc>) -> Vec<Product> {
+        products
+            .iter()
+            .filter_map(|product| {
+                let share_price = self.price_as_of(product.symbol(), time)?;
+                let mut product = product.clone();
+                product.set_share_price(share_price);
+                Some(product)
+            })
+            .collect()
+    }
+}
+
+/// Reads `symbol,share_price,share_price_as_of` rows. When a time repeats, the later row wins.
+pub fn parse_price_history(csv_data: &[u8]) -> Result<PriceHistory, ParseError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(csv_data);
+    let mut history = PriceHistory::default();
+    for row in reader.deserialize::<PriceCsvRow>() {
+        history.insert(&MarketPrice::from(row?));
+    }
+    Ok(history)
+}
+
+/// Formats quotes as rows for appending to a price history file, with a header if `header`.
+pub fn format_price_rows(prices: &[MarketPrice], header: bool) -> Result<String, FormatError> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(header)
+        .from_writer(vec![]);
+    for price in prices {
+        writer.serialize(PriceCsvRow::from(price.clone()))?;
+    }
+    let data = writer.into_inner()?;
+    let string = String::from_utf8(data)?;
+    Ok(string)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PriceCsvRow {
+    symbol: String,
+    #[serde(with = "rust_decimal::serde::str")]
+    share_price: Decimal,
+    share_price_as_of: DateTime<Utc>,
+}
+
+impl From<PriceCsvRow> for MarketPrice {
+    fn from(row: PriceCsvRow) -> Self {
+        MarketPrice {
+            symbol: row.symbol,
+            share_price: SharePrice {
+                height: row.share_price,
+                time: row.share_price_as_of,
+            },
+        }
+    }
+}
+
+impl From<MarketPrice> for PriceCsvRow {
+    fn from(price: MarketPrice) -> Self {
+        PriceCsvRow {
+            symbol: price.symbol,
+            share_price: price.share_price.height,
+            share_price_as_of: price.share_price.time,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+
+    fn quote(symbol: &str, day: u32, height: Decimal) -> MarketPrice {
+        MarketPrice {
+            symbol: symbol.into(),
+            share_price: SharePrice {
+                height,
+                time: Utc.with_ymd_and_hms(2025, 3, day, 16, 0, 0).unwrap(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_price_as_of() {
+        let mut history = PriceHistory::default();
+        assert!(history.insert(&quote("VTI", 3, dec!(100))));
+        assert!(history.insert(&quote("VTI", 5, dec!(110))));
+        assert!(!history.insert(&quote("VTI", 5, dec!(110))));
+        assert!(history.insert(&quote("VTI", 5, dec!(111))));
+        assert_eq!(history.series("VTI").len(), 2);
+
+        let as_of = |day| Utc.with_ymd_and_hms(2025, 3, day, 23, 0, 0).unwrap();
+        assert_eq!(history.price_as_of("VTI", as_of(2)), None);
+        assert_eq!(
+            history.price_as_of("VTI", as_of(4)).unwrap().height,
+            dec!(100)
+        );
+        assert_eq!(
+            history.price_as_of("VTI", as_of(9)).unwrap().height,
+            dec!(111)
+        );
+        assert_eq!(history.price_as_of("BND", as_of(9)), None);
+    }
+
+    #[test]
+    fn test_price_history_round_trip() {
+        let first = format_price_rows(
+            &[quote("VTI", 3, dec!(100)), quote("BND", 3, dec!(70))],
+            true,
+        );
+        let appended = format_price_rows(&[quote("VTI", 3, dec!(101))], false);
+        let csv = first.unwrap() + &appended.unwrap();
+        let history = parse_price_history(csv.as_bytes()).unwrap();
+        assert_eq!(history.symbols().collect::<Vec<_>>(), vec!["BND", "VTI"]);
+        assert_eq!(
+            history.series("VTI"),
+            vec![quote("VTI", 3, dec!(101)).share_price]
+        );
+    }
+}
@@ -1,10 +1,14 @@
-use crate::data::{FormatError, ParseError};
+use crate::data::{deserialize_lenient, fx, FormatError, ParseError, Parsed};
+use chrono::{DateTime, Datelike, Duration, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::America::New_York;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct SharePrice {
     #[serde(rename = "share_price")]
-    pub height: f64,
+    pub height: Decimal,
     #[serde(rename = "share_price_as_of")]
     pub time: chrono::DateTime<chrono::Utc>,
 }
@@ -15,6 +19,15 @@ pub enum Product {
     Stock {
         symbol: String,
         name: String,
+        /// Other symbols price files may use for this product, e.g. `BRK-B` for `BRK.B`.
+        #[serde(default, with = "alias_list")]
+        aliases: Vec<String>,
+        /// The ISO 4217 code of the currency the product is priced in.
+        #[serde(
+            default = "currency_code::usd",
+            deserialize_with = "currency_code::deserialize"
+        )]
+        currency: String,
         outstanding_shares: usize,
         #[serde(flatten)]
         share_price: SharePrice,
@@ -22,12 +35,26 @@ pub enum Product {
     Etf {
         symbol: String,
         name: String,
+        #[serde(default, with = "alias_list")]
+        aliases: Vec<String>,
+        #[serde(
+            default = "currency_code::usd",
+            deserialize_with = "currency_code::deserialize"
+        )]
+        currency: String,
         #[serde(flatten)]
         share_price: SharePrice,
     },
     Coin {
         symbol: String,
         name: String,
+        #[serde(default, with = "alias_list")]
+        aliases: Vec<String>,
+        #[serde(
+            default = "currency_code::usd",
+            deserialize_with = "currency_code::deserialize"
+        )]
+        currency: String,
         #[serde(rename = "outstanding_shares")]
         total_supply: usize,
         #[serde(flatten)]
@@ -36,12 +63,200 @@ pub enum Product {
     Note {
         symbol: String,
         name: String,
+        #[serde(default, with = "alias_list")]
+        aliases: Vec<String>,
+        #[serde(
+            default = "currency_code::usd",
+            deserialize_with = "currency_code::deserialize"
+        )]
+        currency: String,
         #[serde(flatten)]
         share_price: SharePrice,
     },
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProductKind {
+    Stock,
+    Etf,
+    Coin,
+    Note,
+}
+
+impl ProductKind {
+    pub const ALL: [ProductKind; 4] = [
+        ProductKind::Stock,
+        ProductKind::Etf,
+        ProductKind::Coin,
+        ProductKind::Note,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProductKind::Stock => "Stock",
+            ProductKind::Etf => "ETF",
+            ProductKind::Coin => "Coin",
+            ProductKind::Note => "Note",
+        }
+    }
+
+    /// Stocks have outstanding shares and coins a total supply; other kinds have no supply.
+    pub fn has_supply(&self) -> bool {
+        matches!(self, ProductKind::Stock | ProductKind::Coin)
+    }
+
+    /// Coins trade around the clock, stocks and ETFs on weekdays, and notes hold a fixed value.
+    pub fn staleness(&self) -> Staleness {
+        match self {
+            ProductKind::Stock | ProductKind::Etf => Staleness::TradingDays(1),
+            ProductKind::Coin => Staleness::Hours(6),
+            ProductKind::Note => Staleness::Never,
+        }
+    }
+}
+
+/// How long a price stays current.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Staleness {
+    Hours(i64),
+    /// Stale once more than this many weekday closes, at 4pm in New York, have passed since the
+    /// price. Market holidays count as trading days.
+    TradingDays(usize),
+    Never,
+}
+
+impl Staleness {
+    pub fn is_stale(&self, time: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        match *self {
+            Staleness::Hours(hours) => now - time > Duration::hours(hours),
+            Staleness::TradingDays(days) => closes_between(time, now).take(days + 1).count() > days,
+            Staleness::Never => false,
+        }
+    }
+}
+
+/// Weekday market closes after `start` and no later than `end`.
+fn closes_between(start: DateTime<Utc>, end: DateTime<Utc>) -> impl Iterator<Item = DateTime<Utc>> {
+    let close = NaiveTime::from_hms_opt(16, 0, 0).expect("4pm is a valid time");
+    let first = start.with_timezone(&New_York).date_naive();
+    first
+        .iter_days()
+        .filter(|date| !matches!(date.weekday(), Weekday::Sat | Weekday::Sun))
+        .filter_map(move |date| {
+            New_York
+                .from_local_datetime(&date.and_time(close))
+                .earliest()
+        })
+        .map(|close| close.with_timezone(&Utc))
+        .skip_while(move |close| *close <= start)
+        .take_while(move |close| *close <= end)
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ProductError {
+    #[error("Symbol is required")]
+    MissingSymbol,
+
+    #[error("Symbol '{0}' must not contain spaces or commas")]
+    InvalidSymbol(String),
+
+    #[error("Name is required")]
+    MissingName,
+
+    #[error("Invalid supply '{0}'")]
+    InvalidSupply(String),
+
+    #[error("Supply must be greater than zero")]
+    NonPositiveSupply,
+
+    #[error("Invalid share price '{0}'")]
+    InvalidSharePrice(String),
+
+    #[error("Alias '{0}' must not contain spaces or commas")]
+    InvalidAlias(String),
+
+    #[error("Currency '{0}' must be a three-letter code like USD")]
+    InvalidCurrency(String),
+}
+
 impl Product {
+    /// Builds a product of the given kind. `supply` is ignored for kinds without one.
+    pub fn new(
+        kind: ProductKind,
+        symbol: String,
+        name: String,
+        supply: usize,
+        share_price: SharePrice,
+    ) -> Self {
+        match kind {
+            ProductKind::Stock => Product::Stock {
+                symbol,
+                name,
+                aliases: Vec::new(),
+                currency: fx::USD.to_string(),
+                outstanding_shares: supply,
+                share_price,
+            },
+            ProductKind::Etf => Product::Etf {
+                symbol,
+                name,
+                aliases: Vec::new(),
+                currency: fx::USD.to_string(),
+                share_price,
+            },
+            ProductKind::Coin => Product::Coin {
+                symbol,
+                name,
+                aliases: Vec::new(),
+                currency: fx::USD.to_string(),
+                total_supply: supply,
+                share_price,
+            },
+            ProductKind::Note => Product::Note {
+                symbol,
+                name,
+                aliases: Vec::new(),
+                currency: fx::USD.to_string(),
+                share_price,
+            },
+        }
+    }
+    pub fn kind(&self) -> ProductKind {
+        match self {
+            Product::Stock { .. } => ProductKind::Stock,
+            Product::Etf { .. } => ProductKind::Etf,
+            Product::Coin { .. } => ProductKind::Coin,
+            Product::Note { .. } => ProductKind::Note,
+        }
+    }
+    pub fn validate(&self) -> Result<(), ProductError> {
+        let symbol = self.symbol();
+        if symbol.is_empty() {
+            return Err(ProductError::MissingSymbol);
+        }
+        if symbol.contains(|c: char| c.is_whitespace() || c == ',') {
+            return Err(ProductError::InvalidSymbol(symbol.to_string()));
+        }
+        if self.name().trim().is_empty() {
+            return Err(ProductError::MissingName);
+        }
+        if let Some(alias) = self.aliases().iter().find(|alias| {
+            alias.is_empty() || alias.contains(|c: char| c.is_whitespace() || c == ',')
+        }) {
+            return Err(ProductError::InvalidAlias(alias.clone()));
+        }
+        if !fx::is_currency_code(self.currency()) {
+            return Err(ProductError::InvalidCurrency(self.currency().to_string()));
+        }
+        if self.supply() == Some(0) {
+            return Err(ProductError::NonPositiveSupply);
+        }
+        let height = self.share_price().height;
+        if height < Decimal::ZERO {
+            return Err(ProductError::InvalidSharePrice(height.to_string()));
+        }
+        Ok(())
+    }
     pub fn symbol(&self) -> &str {
         match self {
             Product::Stock { symbol, .. } => symbol,
@@ -58,6 +273,54 @@ impl Product {
             Product::Note { name, .. } => name,
         }
     }
+    pub fn aliases(&self) -> &[String] {
+        match self {
+            Product::Stock { aliases, .. } => aliases,
+            Product::Etf { aliases, .. } => aliases,
+            Product::Coin { aliases, .. } => aliases,
+            Product::Note { aliases, .. } => aliases,
+        }
+    }
+    pub fn set_aliases(&mut self, new_aliases: Vec<String>) {
+        match self {
+            Product::Stock { aliases, .. } => *aliases = new_aliases,
+            Product::Etf { aliases, .. } => *aliases = new_aliases,
+            Product::Coin { aliases, .. } => *aliases = new_aliases,
+            Product::Note { aliases, .. } => *aliases = new_aliases,
+        }
+    }
+    pub fn currency(&self) -> &str {
+        match self {
+            Product::Stock { currency, .. } => currency,
+            Product::Etf { currency, .. } => currency,
+            Product::Coin { currency, .. } => currency,
+            Product::Note { currency, .. } => currency,
+        }
+    }
+    pub fn set_currency(&mut self, new_currency: String) {
+        match self {
+            Product::Stock { currency, .. } => *currency = new_currency,
+            Product::Etf { currency, .. } => *currency = new_currency,
+            Product::Coin { currency, .. } => *currency = new_currency,
+            Product::Note { currency, .. } => *currency = new_currency,
+        }
+    }
+    /// The product's symbol followed by its aliases.
+    pub fn symbols(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.symbol()).chain(self.aliases().iter().map(String::as_str))
+    }
+    /// Whether a price file's `symbol` names this product, by its own symbol or an alias.
+    /// Symbols match case-insensitively.
+    pub fn matches_symbol(&self, symbol: &str) -> bool {
+        self.symbols()
+            .any(|known| known.eq_ignore_ascii_case(symbol))
+    }
+    /// Whether the price is older than the product's kind allows.
+    pub fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        self.kind()
+            .staleness()
+            .is_stale(self.share_price().time, now)
+    }
     pub fn supply(&self) -> Option<usize> {
         match self {
             Product::Stock {
@@ -99,6 +362,97 @@ impl Product {
     }
 }
 
+pub fn parse_product_supply(text: &str) -> Result<usize, ProductError> {
+    let text = text.trim().replace(['_', ','], "");
+    let supply = text
+        .parse::<usize>()
+        .map_err(|_| ProductError::InvalidSupply(text.to_string()))?;
+    if supply == 0 {
+        return Err(ProductError::NonPositiveSupply);
+    }
+    Ok(supply)
+}
+
+pub fn parse_share_price(text: &str) -> Result<Decimal, ProductError> {
+    let text = text.trim();
+    match text.parse::<Decimal>() {
+        Ok(height) if height >= Decimal::ZERO => Ok(height),
+        _ => Err(ProductError::InvalidSharePrice(text.to_string())),
+    }
+}
+
+/// Splits a list of aliases separated by spaces, commas or semicolons.
+pub fn parse_aliases(text: &str) -> Vec<String> {
+    let mut aliases = Vec::<String>::new();
+    for alias in text.split(|c: char| c.is_whitespace() || c == ',' || c == ';') {
+        let alias = alias.to_uppercase();
+        if !alias.is_empty() && !aliases.contains(&alias) {
+            aliases.push(alias);
+        }
+    }
+    aliases
+}
+
+/// Aliases are kept in a single space-separated column.
+mod alias_list {
+    use serde::de::{self, Visitor};
+    use serde::{Deserializer, Serializer};
+    use std::fmt;
+
+    pub fn serialize<S: Serializer>(aliases: &[String], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&aliases.join(" "))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<String>, D::Error> {
+        struct AliasVisitor;
+
+        impl Visitor<'_> for AliasVisitor {
+            type Value = Vec<String>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a space-separated list of symbols")
+            }
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                Ok(super::parse_aliases(value))
+            }
+            // CSV infers the type of flattened fields, so a numeric symbol arrives as a number.
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+                Ok(vec![value.to_string()])
+            }
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+                Ok(vec![value.to_string()])
+            }
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+                Ok(vec![value.to_string()])
+            }
+            fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+                Ok(Vec::new())
+            }
+            fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+                Ok(Vec::new())
+            }
+        }
+
+        deserializer.deserialize_any(AliasVisitor)
+    }
+}
+
+/// Products saved before they had a currency, or with a blank one, are quoted in US dollars.
+pub(crate) mod currency_code {
+    use serde::{Deserialize, Deserializer};
+
+    pub fn usd() -> String {
+        crate::data::fx::USD.to_string()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+        let code = String::deserialize(deserializer)?.trim().to_uppercase();
+        Ok(if code.is_empty() { usd() } else { code })
+    }
+}
+
 // Flattening into a proxy works around an issue with deserializing enums with interior flattened fields.
 #[derive(Debug, Serialize, Deserialize)]
 struct ProductProxy {
@@ -107,15 +461,37 @@ struct ProductProxy {
 }
 
 pub fn parse_products(csv_data: &[u8]) -> Result<Vec<Product>, ParseError> {
+    parse_products_lenient(csv_data)?
+        .strict()
+        .map_err(ParseError::InvalidRow)
+}
+
+/// Reads every product that parses, reporting the other rows as errors.
+pub fn parse_products_lenient(csv_data: &[u8]) -> Result<Parsed<Product>, ParseError> {
     let mut reader = csv::ReaderBuilder::new()
         .has_headers(true)
         .trim(csv::Trim::All)
         .from_reader(csv_data);
-    let records: Vec<Product> = reader
-        .deserialize::<ProductProxy>()
-        .map(|result| result.map(|proxy| proxy.product))
-        .collect::<Result<Vec<Product>, _>>()?;
-    Ok(records)
+    let has_aliases = reader.headers()?.iter().any(|header| header == "aliases");
+    let parsed = deserialize_lenient::<ProductProxy, _>(&mut reader)?;
+    let mut records: Vec<Product> = parsed
+        .rows
+        .into_iter()
+        .map(|(_, proxy)| proxy.product)
+        .collect();
+    if !has_aliases {
+        // Files from before aliases relied on coins being quoted as `<symbol>-USD`.
+        for product in records.iter_mut() {
+            if product.kind() == ProductKind::Coin {
+                let alias = format!("{}-USD", product.symbol());
+                product.set_aliases(vec![alias]);
+            }
+        }
+    }
+    Ok(Parsed {
+        rows: records,
+        errors: parsed.errors,
+    })
 }
 
 pub fn format_products(products: Vec<Product>) -> Result<String, FormatError> {
@@ -139,8 +515,10 @@ struct ProductRow {
     symbol: String,
     name: String,
     outstanding_shares: usize,
-    share_price: f64,
+    share_price: Decimal,
     share_price_as_of: chrono::DateTime<chrono::Utc>,
+    aliases: String,
+    currency: String,
 }
 
 impl From<Product> for ProductRow {
@@ -149,6 +527,8 @@ impl From<Product> for ProductRow {
             Product::Stock {
                 symbol,
                 name,
+                aliases,
+                currency,
                 outstanding_shares,
                 share_price,
             } => ProductRow {
@@ -158,10 +538,14 @@ impl From<Product> for ProductRow {
                 outstanding_shares,
                 share_price: share_price.height,
                 share_price_as_of: share_price.time,
+                aliases: aliases.join(" "),
+                currency,
             },
             Product::Etf {
                 symbol,
                 name,
+                aliases,
+                currency,
                 share_price,
             } => ProductRow {
                 type_: "etf",
@@ -170,10 +554,14 @@ impl From<Product> for ProductRow {
                 outstanding_shares: 0,
                 share_price: share_price.height,
                 share_price_as_of: share_price.time,
+                aliases: aliases.join(" "),
+                currency,
             },
             Product::Coin {
                 symbol,
                 name,
+                aliases,
+                currency,
                 total_supply,
                 share_price,
             } => ProductRow {
@@ -183,10 +571,14 @@ impl From<Product> for ProductRow {
                 outstanding_shares: total_supply,
                 share_price: share_price.height,
                 share_price_as_of: share_price.time,
+                aliases: aliases.join(" "),
+                currency,
             },
             Product::Note {
                 symbol,
                 name,
+                aliases,
+                currency,
                 share_price,
             } => ProductRow {
                 type_: "note",
@@ -195,6 +587,8 @@ impl From<Product> for ProductRow {
                 outstanding_shares: 0,
                 share_price: share_price.height,
                 share_price_as_of: share_price.time,
+                aliases: aliases.join(" "),
+                currency,
             },
         }
     }
@@ -204,22 +598,64 @@ impl From<Product> for ProductRow {
 mod tests {
     use crate::data::market::{Product, SharePrice};
     use chrono::{TimeZone, Utc};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_validate_product() {
+        use super::{parse_product_supply, ProductError, ProductKind};
+        let share_price = SharePrice {
+            height: dec!(1.0),
+            time: Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap(),
+        };
+        let product = |kind, symbol: &str, supply| {
+            Product::new(
+                kind,
+                symbol.into(),
+                "Name".into(),
+                supply,
+                share_price.clone(),
+            )
+        };
+        assert_eq!(product(ProductKind::Coin, "BTC", 21).validate(), Ok(()));
+        assert_eq!(product(ProductKind::Etf, "VTI", 0).validate(), Ok(()));
+        assert_eq!(
+            product(ProductKind::Stock, "AAPL", 0).validate(),
+            Err(ProductError::NonPositiveSupply)
+        );
+        assert_eq!(
+            product(ProductKind::Note, "US D", 0).validate(),
+            Err(ProductError::InvalidSymbol("US D".into()))
+        );
+        let mut euro = product(ProductKind::Etf, "VWCE", 0);
+        euro.set_currency("EURO".into());
+        assert_eq!(
+            euro.validate(),
+            Err(ProductError::InvalidCurrency("EURO".into()))
+        );
+        assert_eq!(parse_product_supply("1,000,000"), Ok(1_000_000));
+        assert_eq!(
+            parse_product_supply("-5"),
+            Err(ProductError::InvalidSupply("-5".into()))
+        );
+    }
 
     #[test]
     fn test_format_products() {
         let products = vec![Product::Stock {
             symbol: "RKLB".into(),
             name: "Rocket Lab, Inc.".into(),
+            aliases: vec!["RKLB.US".into()],
+            currency: "USD".into(),
             outstanding_shares: 1000,
             share_price: SharePrice {
-                height: 80.80,
+                height: dec!(80.8),
                 time: Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap(),
             },
         }];
         let string = super::format_products(products).unwrap();
         assert_eq!(
 			string,
-			"type,symbol,name,outstanding_shares,share_price,share_price_as_of\nstock,RKLB,\"Rocket Lab, Inc.\",1000,80.8,2021-01-01T00:00:00Z\n"
+			"type,symbol,name,outstanding_shares,share_price,share_price_as_of,aliases,currency\nstock,RKLB,\"Rocket Lab, Inc.\",1000,80.8,2021-01-01T00:00:00Z,RKLB.US,USD\n"
 		);
     }
 
@@ -242,17 +678,21 @@ mod tests {
                 Product::Stock {
                     symbol: "AAPL".to_string(),
                     name: "Apple Inc.".to_string(),
+                    aliases: Vec::new(),
+                    currency: "USD".to_string(),
                     outstanding_shares: 100,
                     share_price: SharePrice {
-                        height: 123.45,
+                        height: dec!(123.45),
                         time: chrono::Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap()
                     },
                 },
                 Product::Etf {
                     symbol: "CMF".to_string(),
                     name: "iShares California Muni Bond ETF".to_string(),
+                    aliases: Vec::new(),
+                    currency: "USD".to_string(),
                     share_price: SharePrice {
-                        height: 57.85,
+                        height: dec!(57.85),
                         time: chrono::Utc
                             .with_ymd_and_hms(2026, 1, 30, 16, 26, 31)
                             .unwrap()
@@ -261,21 +701,88 @@ mod tests {
                 Product::Coin {
                     symbol: "ETH".to_string(),
                     name: "Ethereum".to_string(),
+                    aliases: vec!["ETH-USD".to_string()],
+                    currency: "USD".to_string(),
                     total_supply: 120690000,
                     share_price: SharePrice {
-                        height: 2722.99,
+                        height: dec!(2722.99),
                         time: chrono::Utc.with_ymd_and_hms(2026, 1, 30, 4, 51, 0).unwrap()
                     },
                 },
                 Product::Note {
                     symbol: "USD".to_string(),
                     name: "US Dollar Credits".to_string(),
+                    aliases: Vec::new(),
+                    currency: "USD".to_string(),
                     share_price: SharePrice {
-                        height: 1.0,
+                        height: dec!(1.0),
                         time: chrono::Utc.with_ymd_and_hms(1971, 8, 16, 1, 0, 0).unwrap()
                     },
                 }
             ]
         );
     }
+
+    #[test]
+    fn test_parse_products_lenient() {
+        let csv_data = "type,symbol,name,outstanding_shares,share_price,share_price_as_of\n\
+            stock,AAPL,Apple Inc.,100,123.45,2021-01-01T00:00:00Z\n\
+            bond,T,Treasury,,100,2021-01-01T00:00:00Z\n\
+            etf,VTI,Total Market,,abc,2021-01-01T00:00:00Z\n";
+        let parsed = super::parse_products_lenient(csv_data.as_bytes()).unwrap();
+        assert_eq!(parsed.rows.len(), 1);
+        let lines = parsed.errors.iter().map(|e| e.line).collect::<Vec<_>>();
+        assert_eq!(lines, vec![3, 4]);
+        assert!(super::parse_products(csv_data.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_aliases() {
+        use super::parse_aliases;
+        let csv_data =
+            "type,symbol,name,outstanding_shares,share_price,share_price_as_of,aliases\n\
+            stock,BRK.B,Berkshire Hathaway,100,400,2024-01-01T00:00:00Z,brk-b 7203\n\
+            coin,BTC,Bitcoin,21000000,60000,2024-01-01T00:00:00Z,\n";
+        let products = super::parse_products(csv_data.as_bytes()).unwrap();
+        assert_eq!(products[0].aliases(), ["BRK-B", "7203"]);
+        assert!(products[0].matches_symbol("brk-b"));
+        assert!(!products[0].matches_symbol("BRK"));
+        assert!(products[1].aliases().is_empty());
+        assert_eq!(
+            parse_aliases(" eth-usd; ETH-USD,weth "),
+            ["ETH-USD", "WETH"]
+        );
+    }
+
+    #[test]
+    fn test_currencies() {
+        let csv_data =
+            "type,symbol,name,outstanding_shares,share_price,share_price_as_of,aliases,currency\n\
+            stock,SHOP,Shopify,100,90,2024-01-01T00:00:00Z,,cad\n\
+            etf,VTI,Total Market,,250,2024-01-01T00:00:00Z,,\n";
+        let products = super::parse_products(csv_data.as_bytes()).unwrap();
+        assert_eq!(products[0].currency(), "CAD");
+        assert_eq!(products[1].currency(), "USD");
+        let json = r#"{"type":"etf","symbol":"VTI","name":"Total Market","share_price":"250","share_price_as_of":"2024-01-01T00:00:00Z"}"#;
+        let product = serde_json::from_str::<Product>(json).unwrap();
+        assert_eq!(product.currency(), "USD");
+    }
+
+    #[test]
+    fn test_staleness() {
+        use super::Staleness;
+        // Friday 4pm and Monday 4pm in New York.
+        let friday_close = Utc.with_ymd_and_hms(2024, 3, 15, 20, 0, 0).unwrap();
+        let monday_close = Utc.with_ymd_and_hms(2024, 3, 18, 20, 0, 0).unwrap();
+        let stock = Staleness::TradingDays(1);
+        assert!(!stock.is_stale(friday_close, monday_close));
+        assert!(stock.is_stale(friday_close, monday_close + chrono::Duration::days(1)));
+        // A Friday morning quote has missed both Friday's and Monday's closes.
+        assert!(stock.is_stale(friday_close - chrono::Duration::hours(6), monday_close));
+        let coin = Staleness::Hours(6);
+        assert!(coin.is_stale(friday_close, friday_close + chrono::Duration::hours(7)));
+        assert!(
+            !Staleness::Never.is_stale(friday_close, monday_close + chrono::Duration::days(999))
+        );
+    }
 }
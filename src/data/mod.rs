@@ -1,10 +1,23 @@
-use csv::{IntoInnerError, Writer};
+use csv::{IntoInnerError, StringRecord, Writer};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use thiserror::Error;
 
+pub mod account;
+pub mod broker;
+pub mod fx;
+pub mod gain;
+pub mod history;
 pub mod market;
 pub mod net_worth;
 pub mod ownership;
 pub mod portfolio;
+pub mod price_source;
+pub mod quote;
+pub mod realized;
+pub mod sale;
+pub mod tax;
 pub mod term;
 pub mod yf;
 
@@ -12,6 +25,108 @@ pub mod yf;
 pub enum ParseError {
     #[error("Csv read error: {0}")]
     CsvReadError(#[from] csv::Error),
+
+    #[error("Invalid row at {0}")]
+    InvalidRow(RowError),
+}
+
+/// A row that could not be read. `line` is 1-based, and `column` names the header at fault when
+/// it is known.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RowError {
+    pub line: u64,
+    pub column: Option<String>,
+    pub message: String,
+}
+
+impl RowError {
+    pub fn new(line: u64, column: Option<&str>, message: impl ToString) -> Self {
+        RowError {
+            line,
+            column: column.map(String::from),
+            message: message.to_string(),
+        }
+    }
+
+    fn from_csv(error: &csv::Error, headers: &StringRecord) -> Self {
+        let line = error.position().map(|p| p.line()).unwrap_or_default();
+        match error.kind() {
+            csv::ErrorKind::Deserialize { err, .. } => {
+                // Errors raised inside a field's own deserializer, like a malformed decimal, carry
+                // no index, so the column is left unknown rather than guessed.
+                let column = err.field().and_then(|index| headers.get(index as usize));
+                RowError::new(line, column, err.kind())
+            }
+            _ => RowError::new(line, None, error),
+        }
+    }
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.column {
+            Some(column) => write!(
+                f,
+                "line {}, column '{}': {}",
+                self.line, column, self.message
+            ),
+            None => write!(f, "line {}: {}", self.line, self.message),
+        }
+    }
+}
+
+/// The rows of a file that could be read, and an error for each row that could not, so that one
+/// bad row does not hold back the rest of an import.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Parsed<T> {
+    pub rows: Vec<T>,
+    pub errors: Vec<RowError>,
+}
+
+impl<T> Default for Parsed<T> {
+    fn default() -> Self {
+        Parsed {
+            rows: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+impl<T> Parsed<T> {
+    /// The rows, or the first error, for files that must be read whole.
+    pub fn strict(self) -> Result<Vec<T>, RowError> {
+        match self.errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(self.rows),
+        }
+    }
+}
+
+/// Deserializes each record after the header, pairing rows with their line numbers and
+/// collecting an error for each record that fails. Only an unreadable header fails outright.
+pub(crate) fn deserialize_lenient<T: DeserializeOwned, R: std::io::Read>(
+    reader: &mut csv::Reader<R>,
+) -> Result<Parsed<(u64, T)>, csv::Error> {
+    let headers = reader.headers()?.clone();
+    let mut parsed = Parsed::default();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(error) => {
+                parsed.errors.push(RowError::from_csv(&error, &headers));
+                continue;
+            }
+        };
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
+        match record.deserialize::<T>(Some(&headers)) {
+            Ok(row) => parsed.rows.push((line, row)),
+            Err(error) => {
+                let error = RowError::from_csv(&error, &headers);
+                parsed.errors.push(error);
+            }
+        }
+    }
+    Ok(parsed)
 }
 
 #[derive(Error, Debug)]
@@ -20,8 +135,14 @@ pub enum FormatError {
     CsvWriteError(#[from] csv::Error),
 
     #[error("Csv into inner error: {0}")]
-    CsvIntoInnerError(#[from] IntoInnerError<Writer<Vec<u8>>>),
+    CsvIntoInnerError(Box<IntoInnerError<Writer<Vec<u8>>>>),
 
     #[error("String from utf8 error: {0}")]
     StringFromUtf8Error(#[from] std::string::FromUtf8Error),
 }
+
+impl From<IntoInnerError<Writer<Vec<u8>>>> for FormatError {
+    fn from(error: IntoInnerError<Writer<Vec<u8>>>) -> Self {
+        FormatError::CsvIntoInnerError(Box::new(error))
+    }
+}
@@ -1,43 +1,359 @@
-use crate::data::market::{Product, SharePrice};
+use crate::api::ecs::Eid;
+use crate::data::account::Account;
+use crate::data::fx::FxTable;
+use crate::data::market::{currency_code, Product, Staleness};
 use crate::data::portfolio::Lot;
-use chrono::{DateTime, Utc};
+use crate::data::tax::TaxSettings;
+use crate::data::{FormatError, ParseError};
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 
 pub struct NetWorthReport {
-    pub pre_tax: f64,
+    /// The reporting currency the totals are in.
+    pub currency: String,
+    pub pre_tax: Decimal,
+    /// Net worth less the estimated tax on selling every priced lot.
+    pub after_tax: Decimal,
+    /// The newest price of any held product.
     pub as_of: DateTime<Utc>,
+    /// The oldest price of any held product whose price can go stale, which bounds how current
+    /// the totals are. Products like notes, whose price never changes, are left out.
+    pub oldest_as_of: DateTime<Utc>,
     pub unpriced_products: HashSet<String>,
+    /// Held products priced in a currency with no exchange rate to the reporting currency, which
+    /// are left out of the totals.
+    pub unconverted_products: HashSet<String>,
+    /// Held products whose price is older than their kind allows.
+    pub stale_products: HashSet<String>,
 }
 
 impl NetWorthReport {
-    pub fn new(lots: &Vec<Lot>, products: &Vec<Product>) -> Self {
-        let price_map = to_price_map(products);
-        let mut pre_tax = 0.0;
+    pub fn new(
+        lots: &Vec<Lot>,
+        products: &Vec<Product>,
+        accounts: &HashMap<Eid, Account>,
+        tax: &TaxSettings,
+        fx: &FxTable,
+        currency: &str,
+    ) -> Self {
+        let product_map = to_product_map(products);
+        let now = Utc::now();
+        let stale = products
+            .iter()
+            .filter(|product| product.is_stale(now))
+            .map(|product| product.symbol())
+            .collect::<HashSet<_>>();
+        let mut pre_tax = Decimal::ZERO;
+        let mut estimated_tax = Decimal::ZERO;
         let mut as_of = DateTime::<Utc>::MIN_UTC;
+        let mut oldest_as_of = DateTime::<Utc>::MAX_UTC;
         let mut unpriced_products: HashSet<String> = HashSet::new();
+        let mut unconverted_products: HashSet<String> = HashSet::new();
+        let mut stale_products: HashSet<String> = HashSet::new();
         for lot in lots {
             let product_id = &lot.product;
-            let share_price = price_map.get(product_id);
-            if let Some(share_price) = share_price {
-                let dollars = lot.quantity * share_price.height;
-                pre_tax += dollars;
+            let product = product_map.get(product_id);
+            if let Some(product) = product {
+                let Some(rate) = fx.rate(product.currency(), currency) else {
+                    unconverted_products.insert(product_id.to_string());
+                    continue;
+                };
+                let share_price = product.share_price();
+                pre_tax += lot.quantity * share_price.height * rate;
+                let treatment = accounts
+                    .get(&lot.account)
+                    .map(|account| account.kind.treatment())
+                    .unwrap_or_default();
+                estimated_tax += tax.liquidation_tax(lot, treatment, share_price, now) * rate;
                 as_of = as_of.max(share_price.time.clone());
+                if product.kind().staleness() != Staleness::Never {
+                    oldest_as_of = oldest_as_of.min(share_price.time);
+                }
+                if stale.contains(product_id.as_str()) {
+                    stale_products.insert(product_id.to_string());
+                }
             } else {
                 unpriced_products.insert(product_id.to_string());
             }
         }
         Self {
+            currency: currency.to_string(),
             pre_tax,
+            after_tax: pre_tax - estimated_tax,
             as_of,
+            oldest_as_of: oldest_as_of.min(as_of),
             unpriced_products,
+            unconverted_products,
+            stale_products,
         }
     }
+
+    /// How old the newest and the oldest held prices are, or `None` when nothing is priced.
+    pub fn price_ages(&self, now: DateTime<Utc>) -> Option<(Duration, Duration)> {
+        (self.as_of > DateTime::<Utc>::MIN_UTC).then(|| (now - self.as_of, now - self.oldest_as_of))
+    }
 }
 
-fn to_price_map(products: &Vec<Product>) -> HashMap<String, SharePrice> {
-    let mut map: HashMap<String, SharePrice> = HashMap::new();
+fn to_product_map(products: &Vec<Product>) -> HashMap<String, &Product> {
+    let mut map: HashMap<String, &Product> = HashMap::new();
     for p in products {
-        map.insert(p.symbol().to_string(), p.share_price().clone());
+        map.insert(p.symbol().to_string(), p);
     }
     map
 }
+
+/// Net worth recorded when prices or lots change, for charting its trend.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetWorthSnapshot {
+    pub time: DateTime<Utc>,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub pre_tax: Decimal,
+    /// The reporting currency `pre_tax` is in. Snapshots from before it was recorded are in US
+    /// dollars.
+    #[serde(
+        default = "currency_code::usd",
+        deserialize_with = "currency_code::deserialize"
+    )]
+    pub currency: String,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChartRange {
+    OneMonth,
+    YearToDate,
+    OneYear,
+    All,
+}
+
+impl ChartRange {
+    pub const ALL: [ChartRange; 4] = [
+        ChartRange::OneMonth,
+        ChartRange::YearToDate,
+        ChartRange::OneYear,
+        ChartRange::All,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChartRange::OneMonth => "1M",
+            ChartRange::YearToDate => "YTD",
+            ChartRange::OneYear => "1Y",
+            ChartRange::All => "All",
+        }
+    }
+
+    /// The earliest time shown, or `None` for no limit.
+    pub fn start(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            ChartRange::OneMonth => Some(now - Duration::days(30)),
+            ChartRange::YearToDate => Utc.with_ymd_and_hms(now.year(), 1, 1, 0, 0, 0).single(),
+            ChartRange::OneYear => Some(now - Duration::days(365)),
+            ChartRange::All => None,
+        }
+    }
+
+    /// The snapshots within the range, oldest first.
+    pub fn select(
+        &self,
+        snapshots: &[NetWorthSnapshot],
+        now: DateTime<Utc>,
+    ) -> Vec<NetWorthSnapshot> {
+        let start = self.start(now);
+        let mut selected = snapshots
+            .iter()
+            .filter(|snapshot| start.is_none_or(|start| snapshot.time >= start))
+            .cloned()
+            .collect::<Vec<_>>();
+        selected.sort_by_key(|snapshot| snapshot.time);
+        selected
+    }
+}
+
+pub fn parse_net_worth_history(csv_data: &[u8]) -> Result<Vec<NetWorthSnapshot>, ParseError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(csv_data);
+    let snapshots = reader
+        .deserialize::<NetWorthSnapshot>()
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(snapshots)
+}
+
+/// Formats snapshots as rows for appending to a history file, with a header if `header`.
+pub fn format_net_worth_rows(
+    snapshots: &[NetWorthSnapshot],
+    header: bool,
+) -> Result<String, FormatError> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(header)
+        .from_writer(vec![]);
+    for snapshot in snapshots {
+        writer.serialize(snapshot)?;
+    }
+    let data = writer.into_inner()?;
+    let string = String::from_utf8(data)?;
+    Ok(string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::fx::USD;
+    use crate::data::market::SharePrice;
+    use rust_decimal_macros::dec;
+
+    fn snapshot(year: i32, month: u32, day: u32, pre_tax: Decimal) -> NetWorthSnapshot {
+        NetWorthSnapshot {
+            time: Utc.with_ymd_and_hms(year, month, day, 12, 0, 0).unwrap(),
+            pre_tax,
+            currency: USD.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_report_flags_stale_prices() {
+        use crate::data::market::ProductKind;
+        let now = Utc::now();
+        let product = |kind, symbol: &str, age: Duration| {
+            let share_price = SharePrice {
+                height: dec!(10),
+                time: now - age,
+            };
+            Product::new(kind, symbol.into(), symbol.into(), 1, share_price)
+        };
+        let products = vec![
+            product(ProductKind::Coin, "BTC", Duration::hours(12)),
+            product(ProductKind::Stock, "AAPL", Duration::days(10)),
+            product(ProductKind::Note, "USD", Duration::days(3650)),
+            product(ProductKind::Coin, "ETH", Duration::days(30)),
+        ];
+        let lot = |symbol: &str| Lot {
+            account: Eid::from("a0"),
+            time: now - Duration::days(400),
+            product: symbol.into(),
+            quantity: dec!(1),
+            unit_cost: None,
+            fees: None,
+        };
+        let lots = vec![lot("BTC"), lot("AAPL"), lot("USD")];
+        let report = NetWorthReport::new(
+            &lots,
+            &products,
+            &HashMap::new(),
+            &TaxSettings::default(),
+            &FxTable::default(),
+            USD,
+        );
+        assert_eq!(
+            report.stale_products,
+            HashSet::from(["BTC".to_string(), "AAPL".to_string()])
+        );
+        let (newest, oldest) = report.price_ages(now).unwrap();
+        assert_eq!(newest, Duration::hours(12));
+        assert_eq!(oldest, Duration::days(10));
+    }
+
+    #[test]
+    fn test_report_converts_currencies() {
+        use crate::data::fx::FxRate;
+        use crate::data::market::ProductKind;
+        let now = Utc::now();
+        let product = |symbol: &str, currency: &str, height| {
+            let share_price = SharePrice { height, time: now };
+            let mut product = Product::new(
+                ProductKind::Etf,
+                symbol.into(),
+                symbol.into(),
+                1,
+                share_price,
+            );
+            product.set_currency(currency.into());
+            product
+        };
+        let products = vec![
+            product("VTI", "USD", dec!(250)),
+            product("VWCE", "EUR", dec!(100)),
+            product("XIU", "CAD", dec!(30)),
+        ];
+        let lot = |symbol: &str| Lot {
+            account: Eid::from("a0"),
+            time: now - Duration::days(400),
+            product: symbol.into(),
+            quantity: dec!(2),
+            unit_cost: Some(dec!(50)),
+            fees: None,
+        };
+        let lots = vec![lot("VTI"), lot("VWCE"), lot("XIU")];
+        let mut fx = FxTable::default();
+        fx.insert(FxRate {
+            base: "EUR".into(),
+            quote: "USD".into(),
+            rate: dec!(1.25),
+            time: now,
+        });
+        let report = NetWorthReport::new(
+            &lots,
+            &products,
+            &HashMap::new(),
+            &TaxSettings::default(),
+            &fx,
+            "EUR",
+        );
+        assert_eq!(report.currency, "EUR");
+        assert_eq!(report.pre_tax, dec!(400) + dec!(200));
+        assert_eq!(
+            report.unconverted_products,
+            HashSet::from(["XIU".to_string()])
+        );
+        assert!(report.unpriced_products.is_empty());
+    }
+
+    #[test]
+    fn test_chart_ranges() {
+        let snapshots = vec![
+            snapshot(2025, 3, 1, dec!(300)),
+            snapshot(2024, 1, 15, dec!(100)),
+            snapshot(2024, 12, 31, dec!(200)),
+            snapshot(2025, 2, 20, dec!(250)),
+        ];
+        let now = Utc.with_ymd_and_hms(2025, 3, 10, 0, 0, 0).unwrap();
+        let values = |range: ChartRange| {
+            range
+                .select(&snapshots, now)
+                .iter()
+                .map(|s| s.pre_tax)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values(ChartRange::OneMonth), vec![dec!(250), dec!(300)]);
+        assert_eq!(values(ChartRange::YearToDate), vec![dec!(250), dec!(300)]);
+        assert_eq!(
+            values(ChartRange::OneYear),
+            vec![dec!(200), dec!(250), dec!(300)]
+        );
+        assert_eq!(
+            values(ChartRange::All),
+            vec![dec!(100), dec!(200), dec!(250), dec!(300)]
+        );
+    }
+
+    #[test]
+    fn test_net_worth_history_round_trip() {
+        let first = format_net_worth_rows(&[snapshot(2025, 1, 1, dec!(100))], true).unwrap();
+        let appended = format_net_worth_rows(&[snapshot(2025, 1, 2, dec!(150.5))], false).unwrap();
+        let snapshots = parse_net_worth_history((first + &appended).as_bytes()).unwrap();
+        assert_eq!(
+            snapshots,
+            vec![
+                snapshot(2025, 1, 1, dec!(100)),
+                snapshot(2025, 1, 2, dec!(150.5))
+            ]
+        );
+        let before_currencies = "time,pre_tax\n2025-01-01T12:00:00Z,100\n";
+        assert_eq!(
+            parse_net_worth_history(before_currencies.as_bytes()).unwrap(),
+            vec![snapshot(2025, 1, 1, dec!(100))]
+        );
+    }
+}
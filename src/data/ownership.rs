@@ -1,3 +1,7 @@
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Ownership {
     pub level: OwnerLevel,
@@ -16,17 +20,19 @@ impl std::fmt::Display for Ownership {
 }
 
 impl Ownership {
-    pub fn new(quantity: f64, outstanding: usize) -> Self {
-        let level = OwnerLevel::new(quantity / outstanding as f64);
-        let floor_shares = level.floor() * outstanding as f64;
-        let excess_shares = (quantity - floor_shares).floor() as usize;
-        let ceiling_shares = level.ceiling() * outstanding as f64;
-        let deficit_shares = (ceiling_shares - quantity).ceil() as usize;
-        Self {
+    /// Returns `None` when nothing is outstanding, since there is no share of it to own.
+    pub fn new(quantity: Decimal, outstanding: usize) -> Option<Self> {
+        let outstanding = Decimal::from(outstanding);
+        let level = OwnerLevel::new(quantity.checked_div(outstanding)?);
+        let floor_shares = level.floor() * outstanding;
+        let excess_shares = (quantity - floor_shares).floor().to_usize().unwrap_or(0);
+        let ceiling_shares = level.ceiling() * outstanding;
+        let deficit_shares = (ceiling_shares - quantity).ceil().to_usize().unwrap_or(0);
+        Some(Self {
             level,
             excess_shares,
             deficit_shares,
-        }
+        })
     }
     pub fn progress(&self) -> f64 {
         self.excess_shares as f64 / (self.excess_shares + self.deficit_shares) as f64
@@ -64,28 +70,28 @@ impl std::fmt::Display for OwnerLevel {
     }
 }
 impl OwnerLevel {
-    const F_FLOOR: f64 = 0.00000001;
-    const E_FLOOR: f64 = 0.0000001;
-    const D_FLOOR: f64 = 0.000001;
-    const C_FLOOR: f64 = 0.00001;
-    const B_FLOOR: f64 = 0.0001;
-    const A_FLOOR: f64 = 0.001;
-    const S_FLOOR: f64 = 0.01;
-    pub fn new(fraction: f64) -> Self {
+    const F_FLOOR: Decimal = dec!(0.00000001);
+    const E_FLOOR: Decimal = dec!(0.0000001);
+    const D_FLOOR: Decimal = dec!(0.000001);
+    const C_FLOOR: Decimal = dec!(0.00001);
+    const B_FLOOR: Decimal = dec!(0.0001);
+    const A_FLOOR: Decimal = dec!(0.001);
+    const S_FLOOR: Decimal = dec!(0.01);
+    pub fn new(fraction: Decimal) -> Self {
         match fraction {
             fraction if fraction < Self::F_FLOOR => OwnerLevel::G,
-            Self::F_FLOOR..Self::E_FLOOR => OwnerLevel::F,
-            Self::E_FLOOR..Self::D_FLOOR => OwnerLevel::E,
-            Self::D_FLOOR..Self::C_FLOOR => OwnerLevel::D,
-            Self::C_FLOOR..Self::B_FLOOR => OwnerLevel::C,
-            Self::B_FLOOR..Self::A_FLOOR => OwnerLevel::B,
-            Self::A_FLOOR..Self::S_FLOOR => OwnerLevel::A,
+            fraction if fraction < Self::E_FLOOR => OwnerLevel::F,
+            fraction if fraction < Self::D_FLOOR => OwnerLevel::E,
+            fraction if fraction < Self::C_FLOOR => OwnerLevel::D,
+            fraction if fraction < Self::B_FLOOR => OwnerLevel::C,
+            fraction if fraction < Self::A_FLOOR => OwnerLevel::B,
+            fraction if fraction < Self::S_FLOOR => OwnerLevel::A,
             _ => OwnerLevel::S,
         }
     }
-    fn ceiling(&self) -> f64 {
+    fn ceiling(&self) -> Decimal {
         match self {
-            OwnerLevel::S => 1.0,
+            OwnerLevel::S => Decimal::ONE,
             OwnerLevel::A => Self::S_FLOOR,
             OwnerLevel::B => Self::A_FLOOR,
             OwnerLevel::C => Self::B_FLOOR,
@@ -96,7 +102,7 @@ impl OwnerLevel {
         }
     }
 
-    fn floor(&self) -> f64 {
+    fn floor(&self) -> Decimal {
         match self {
             OwnerLevel::S => Self::S_FLOOR,
             OwnerLevel::A => Self::A_FLOOR,
@@ -105,7 +111,26 @@ impl OwnerLevel {
             OwnerLevel::D => Self::D_FLOOR,
             OwnerLevel::E => Self::E_FLOOR,
             OwnerLevel::F => Self::F_FLOOR,
-            OwnerLevel::G => 0.0,
+            OwnerLevel::G => Decimal::ZERO,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_boundaries_are_exact() {
+        let ownership = Ownership::new(dec!(0.0003), 3).unwrap();
+        assert_eq!(ownership.level, OwnerLevel::B);
+        assert_eq!(ownership.excess_shares, 0);
+        assert_eq!(ownership.deficit_shares, 1);
+        assert_eq!(Ownership::new(dec!(2), 100).unwrap().level, OwnerLevel::S);
+    }
+
+    #[test]
+    fn test_no_outstanding_supply() {
+        assert_eq!(Ownership::new(dec!(2), 0), None);
+    }
+}
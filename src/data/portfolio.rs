@@ -1,35 +1,142 @@
 use crate::api::ecs::Eid;
-use crate::data::{FormatError, ParseError};
-use chrono::{DateTime, Utc};
+use crate::data::{deserialize_lenient, FormatError, ParseError, Parsed};
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use thiserror::Error;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Lot {
-    pub account: String,
+    /// The id of the account holding the lot.
+    pub account: Eid,
     pub time: DateTime<Utc>,
     pub product: String,
-    pub quantity: f64,
+    pub quantity: Decimal,
+    /// Price paid per unit, or `None` when the cost basis was never recorded.
+    pub unit_cost: Option<Decimal>,
+    /// Commissions and other fees paid to acquire the lot.
+    pub fees: Option<Decimal>,
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum LotError {
+    #[error("Account is required")]
+    MissingAccount,
+
+    #[error("Product is required")]
+    MissingProduct,
+
+    #[error("Invalid time '{0}', expected YYYY-MM-DD or RFC 3339")]
+    InvalidTime(String),
+
+    #[error("Invalid quantity '{0}'")]
+    InvalidQuantity(String),
+
+    #[error("Quantity must be greater than zero")]
+    NonPositiveQuantity,
+
+    #[error("Invalid amount '{0}'")]
+    InvalidAmount(String),
+
+    #[error("Amounts must not be negative")]
+    NegativeAmount,
+}
+
+impl Lot {
+    pub fn validate(&self) -> Result<(), LotError> {
+        if self.account.as_str().trim().is_empty() {
+            return Err(LotError::MissingAccount);
+        }
+        if self.product.trim().is_empty() {
+            return Err(LotError::MissingProduct);
+        }
+        if self.quantity <= Decimal::ZERO {
+            return Err(LotError::NonPositiveQuantity);
+        }
+        for amount in [self.unit_cost, self.fees].into_iter().flatten() {
+            if amount < Decimal::ZERO {
+                return Err(LotError::NegativeAmount);
+            }
+        }
+        Ok(())
+    }
+
+    /// Total cost of acquiring the lot including fees, if the unit cost is known.
+    pub fn cost_basis(&self) -> Option<Decimal> {
+        self.unit_cost
+            .map(|unit_cost| unit_cost * self.quantity + self.fees.unwrap_or_default())
+    }
+}
+
+/// Parses a lot time from either a plain date (taken as midnight UTC) or an RFC 3339 timestamp.
+pub fn parse_lot_time(text: &str) -> Result<DateTime<Utc>, LotError> {
+    let text = text.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        let midnight = date.and_hms_opt(0, 0, 0).expect("midnight is a valid time");
+        return Ok(midnight.and_utc());
+    }
+    DateTime::parse_from_rfc3339(text)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|_| LotError::InvalidTime(text.to_string()))
+}
+
+pub fn parse_lot_quantity(text: &str) -> Result<Decimal, LotError> {
+    let text = text.trim();
+    let quantity = text
+        .parse::<Decimal>()
+        .map_err(|_| LotError::InvalidQuantity(text.to_string()))?;
+    if quantity <= Decimal::ZERO {
+        return Err(LotError::NonPositiveQuantity);
+    }
+    Ok(quantity)
+}
+
+/// Parses an optional money amount where blank text means the amount is unknown.
+pub fn parse_lot_amount(text: &str) -> Result<Option<Decimal>, LotError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    let amount = text
+        .parse::<Decimal>()
+        .map_err(|_| LotError::InvalidAmount(text.to_string()))?;
+    if amount < Decimal::ZERO {
+        return Err(LotError::NegativeAmount);
+    }
+    Ok(Some(amount))
 }
 
 pub fn parse_lots(csv_data: &[u8]) -> Result<HashMap<Eid, Lot>, ParseError> {
+    let rows = parse_lots_lenient(csv_data)?
+        .strict()
+        .map_err(ParseError::InvalidRow)?;
+    Ok(rows.into_iter().collect())
+}
+
+/// Reads every lot that parses, reporting the other rows as errors.
+pub fn parse_lots_lenient(csv_data: &[u8]) -> Result<Parsed<(Eid, Lot)>, ParseError> {
     let mut reader = csv::ReaderBuilder::new().from_reader(csv_data);
-    let rows: Vec<LotCsvRow> = reader
-        .deserialize()
-        .collect::<Result<Vec<LotCsvRow>, _>>()?;
-    let map = rows
+    let parsed = deserialize_lenient::<LotCsvRow, _>(&mut reader)?;
+    let rows = parsed
+        .rows
         .into_iter()
-        .map(|c| {
+        .map(|(_, c)| {
             let lot = Lot {
                 account: c.account,
                 time: c.time,
                 product: c.product,
                 quantity: c.quantity,
+                unit_cost: c.unit_cost,
+                fees: c.fees,
             };
             (c.eid, lot)
         })
         .collect();
-    Ok(map)
+    Ok(Parsed {
+        rows,
+        errors: parsed.errors,
+    })
 }
 
 pub fn format_lots(lots: HashMap<Eid, Lot>) -> Result<String, FormatError> {
@@ -40,6 +147,8 @@ pub fn format_lots(lots: HashMap<Eid, Lot>) -> Result<String, FormatError> {
             time: lot.time,
             product: lot.product,
             quantity: lot.quantity,
+            unit_cost: lot.unit_cost,
+            fees: lot.fees,
             eid,
         })
         .collect::<Vec<_>>();
@@ -54,15 +163,59 @@ pub fn format_lots(lots: HashMap<Eid, Lot>) -> Result<String, FormatError> {
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 struct LotCsvRow {
-    pub account: String,
+    pub account: Eid,
     pub time: DateTime<Utc>,
     pub product: String,
-    pub quantity: f64,
+    // Read as text so amounts keep the digits they were written with.
+    #[serde(with = "rust_decimal::serde::str")]
+    pub quantity: Decimal,
+    #[serde(default, with = "rust_decimal::serde::str_option")]
+    pub unit_cost: Option<Decimal>,
+    #[serde(default, with = "rust_decimal::serde::str_option")]
+    pub fees: Option<Decimal>,
     pub eid: Eid,
 }
 
 #[cfg(test)]
 mod tests {
+    use super::LotError;
+    use crate::api::ecs::Eid;
+    use crate::data::ParseError;
+    use chrono::{TimeZone, Utc};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_parse_lot_time() {
+        let midnight = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        assert_eq!(super::parse_lot_time("2024-03-01"), Ok(midnight));
+        let afternoon = Utc.with_ymd_and_hms(2024, 3, 1, 19, 30, 0).unwrap();
+        assert_eq!(
+            super::parse_lot_time("2024-03-01T14:30:00-05:00"),
+            Ok(afternoon)
+        );
+        assert_eq!(
+            super::parse_lot_time("03/01/2024"),
+            Err(LotError::InvalidTime("03/01/2024".into()))
+        );
+    }
+
+    #[test]
+    fn test_parse_lot_quantity() {
+        assert_eq!(super::parse_lot_quantity(" 12.5 "), Ok(dec!(12.5)));
+        assert_eq!(
+            super::parse_lot_quantity("0"),
+            Err(LotError::NonPositiveQuantity)
+        );
+        assert_eq!(
+            super::parse_lot_quantity("NaN"),
+            Err(LotError::InvalidQuantity("NaN".into()))
+        );
+        assert_eq!(
+            super::parse_lot_quantity("ten"),
+            Err(LotError::InvalidQuantity("ten".into()))
+        );
+    }
+
     #[test]
     fn test_parse_lots() {
         let csv_data =
@@ -71,4 +224,58 @@ mod tests {
         let lots = super::parse_lots(csv_data).unwrap();
         assert_eq!(lots.len(), 1);
     }
+
+    #[test]
+    fn test_parse_lots_with_cost_basis() {
+        let csv_data = "account,time,product,quantity,unit_cost,fees,eid\n\
+            foo,2021-01-01T00:00:00Z,AAPL,10,150.5,4.95,l0\n\
+            foo,2021-01-01T00:00:00Z,AAPL,5,,,l1"
+            .as_bytes();
+
+        let lots = super::parse_lots(csv_data).unwrap();
+        let priced = &lots[&Eid::from("l0")];
+        assert_eq!(priced.unit_cost, Some(dec!(150.5)));
+        assert_eq!(priced.cost_basis(), Some(dec!(1509.95)));
+        let unpriced = &lots[&Eid::from("l1")];
+        assert_eq!(unpriced.cost_basis(), None);
+    }
+
+    #[test]
+    fn test_parse_lots_lenient() {
+        let csv_data = "account,time,product,quantity,unit_cost,fees,eid\n\
+            foo,2021-01-01T00:00:00Z,AAPL,10,150.5,,l0\n\
+            foo,2021-01-01T00:00:00Z,AAPL,ten,150.5,,l1\n\
+            foo,2021-01-01T00:00:00Z,AAPL,5\n\
+            foo,2021-01-01T00:00:00Z,AAPL,5,,,l3"
+            .as_bytes();
+        let parsed = super::parse_lots_lenient(csv_data).unwrap();
+        assert_eq!(parsed.rows.len(), 2);
+        assert_eq!(parsed.errors.len(), 2);
+        assert_eq!(parsed.errors[0].line, 3);
+        assert_eq!(parsed.errors[0].column, None);
+        assert_eq!(parsed.errors[1].line, 4);
+        assert!(matches!(
+            super::parse_lots(csv_data),
+            Err(ParseError::InvalidRow(error)) if error.line == 3
+        ));
+    }
+
+    #[test]
+    fn test_lot_reads_numeric_json() {
+        let json = r#"{"account":"foo","time":"2021-01-01T00:00:00Z","product":"BTC",
+            "quantity":0.1,"unit_cost":40000.5,"fees":null}"#;
+        let lot = serde_json::from_str::<super::Lot>(json).unwrap();
+        assert_eq!(lot.quantity, dec!(0.1));
+        assert_eq!(lot.cost_basis(), Some(dec!(4000.05)));
+    }
+
+    #[test]
+    fn test_format_lots_round_trip() {
+        let csv_data = "account,time,product,quantity,unit_cost,fees,eid\n\
+            foo,2021-01-01T00:00:00Z,AAPL,10.0,150.5,,l0\n"
+            .as_bytes();
+        let lots = super::parse_lots(csv_data).unwrap();
+        let string = super::format_lots(lots.clone()).unwrap();
+        assert_eq!(string.as_bytes(), csv_data);
+    }
 }
@@ -0,0 +1,360 @@
+use crate::api::ecs::PriceUpdate;
+use crate::data::market::SharePrice;
+use crate::data::yf::{self, CsvParseError, MarketPrice};
+use crate::data::{deserialize_lenient, Parsed, RowError};
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::America::New_York;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PriceSourceError {
+    #[error("Unrecognized price file '{0}'")]
+    Unrecognized(String),
+
+    #[error("Csv read error: {0}")]
+    CsvReadError(#[from] csv::Error),
+
+    #[error("Yahoo portfolio error: {0}")]
+    YahooError(#[from] CsvParseError),
+
+    #[error("Serde error: {0}")]
+    SerdeJsonError(#[from] serde_json::Error),
+
+    #[error("Invalid time '{0}'")]
+    InvalidTime(String),
+
+    #[error("No symbol in file name '{0}'")]
+    MissingSymbol(String),
+}
+
+/// The outcome of importing a price file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PriceImport {
+    /// The name of the format the file was read as.
+    pub source: String,
+    pub update: PriceUpdate,
+    /// Rows of the file that could not be read.
+    pub errors: Vec<RowError>,
+}
+
+/// A file format that prices can be imported from.
+pub trait PriceSource {
+    fn name(&self) -> &'static str;
+    /// Whether an uploaded file looks like this format.
+    fn detect(&self, file_name: &str, bytes: &[u8]) -> bool;
+    /// Reads the prices in a file, reporting rows that do not parse rather than failing on them.
+    fn parse(&self, file_name: &str, bytes: &[u8])
+        -> Result<Parsed<MarketPrice>, PriceSourceError>;
+}
+
+/// Every known format, in the order they are tried.
+pub fn price_sources() -> Vec<Box<dyn PriceSource>> {
+    vec![
+        Box::new(YahooPortfolio),
+        Box::new(SimpleCsv),
+        Box::new(StooqDaily),
+        Box::new(CoinGeckoMarkets),
+    ]
+}
+
+/// Parses a file with the first format that recognizes it, returning that format's name.
+pub fn parse_prices(
+    file_name: &str,
+    bytes: &[u8],
+) -> Result<(&'static str, Parsed<MarketPrice>), PriceSourceError> {
+    let source = price_sources()
+        .into_iter()
+        .find(|source| source.detect(file_name, bytes))
+        .ok_or_else(|| PriceSourceError::Unrecognized(file_name.to_string()))?;
+    Ok((source.name(), source.parse(file_name, bytes)?))
+}
+
+/// The portfolio CSV exported from Yahoo Finance.
+pub struct YahooPortfolio;
+
+impl PriceSource for YahooPortfolio {
+    fn name(&self) -> &'static str {
+        "Yahoo portfolio"
+    }
+    fn detect(&self, _file_name: &str, bytes: &[u8]) -> bool {
+        has_columns(bytes, &["Symbol", "Current Price", "Date", "Time"])
+    }
+    fn parse(
+        &self,
+        _file_name: &str,
+        bytes: &[u8],
+    ) -> Result<Parsed<MarketPrice>, PriceSourceError> {
+        Ok(yf::parse_market_prices(bytes)?)
+    }
+}
+
+/// A `symbol,price,timestamp` CSV, with RFC 3339 timestamps or `YYYY-MM-DD` dates.
+pub struct SimpleCsv;
+
+impl PriceSource for SimpleCsv {
+    fn name(&self) -> &'static str {
+        "Symbol, price, timestamp CSV"
+    }
+    fn detect(&self, _file_name: &str, bytes: &[u8]) -> bool {
+        has_columns(bytes, &["symbol", "price", "timestamp"])
+    }
+    fn parse(
+        &self,
+        _file_name: &str,
+        bytes: &[u8],
+    ) -> Result<Parsed<MarketPrice>, PriceSourceError> {
+        #[derive(Deserialize)]
+        struct Row {
+            symbol: String,
+            #[serde(with = "rust_decimal::serde::str")]
+            price: Decimal,
+            timestamp: String,
+        }
+        let parsed = deserialize_lenient::<Row, _>(&mut csv_reader(bytes))?;
+        Ok(convert_rows(parsed, "timestamp", |row| {
+            Ok(MarketPrice {
+                symbol: row.symbol.to_uppercase(),
+                share_price: SharePrice {
+                    height: row.price,
+                    time: parse_timestamp(&row.timestamp)?,
+                },
+            })
+        }))
+    }
+}
+
+/// Stooq's daily history download, one symbol per file named like `aapl.us_d.csv`. Each close is
+/// taken as of 4pm in New York.
+pub struct StooqDaily;
+
+impl PriceSource for StooqDaily {
+    fn name(&self) -> &'static str {
+        "Stooq daily CSV"
+    }
+    fn detect(&self, _file_name: &str, bytes: &[u8]) -> bool {
+        has_columns(bytes, &["Date", "Open", "High", "Low", "Close"])
+    }
+    fn parse(
+        &self,
+        file_name: &str,
+        bytes: &[u8],
+    ) -> Result<Parsed<MarketPrice>, PriceSourceError> {
+        #[derive(Deserialize)]
+        struct Row {
+            #[serde(rename = "Date")]
+            date: String,
+            #[serde(rename = "Close", with = "rust_decimal::serde::str")]
+            close: Decimal,
+        }
+        let symbol = stooq_symbol(file_name)
+            .ok_or_else(|| PriceSourceError::MissingSymbol(file_name.to_string()))?;
+        let close = NaiveTime::from_hms_opt(16, 0, 0).expect("4pm is a valid time");
+        let parsed = deserialize_lenient::<Row, _>(&mut csv_reader(bytes))?;
+        Ok(convert_rows(parsed, "Date", |row| {
+            let date = NaiveDate::parse_from_str(&row.date, "%Y-%m-%d")
+                .map_err(|_| PriceSourceError::InvalidTime(row.date.clone()))?;
+            let time = New_York
+                .from_local_datetime(&date.and_time(close))
+                .earliest()
+                .ok_or_else(|| PriceSourceError::InvalidTime(row.date.clone()))?
+                .with_timezone(&Utc);
+            Ok(MarketPrice {
+                symbol: symbol.clone(),
+                share_price: SharePrice {
+                    height: row.close,
+                    time,
+                },
+            })
+        }))
+    }
+}
+
+/// `aapl.us_d.csv` and `btcusd_d.csv` name AAPL and BTCUSD.
+fn stooq_symbol(file_name: &str) -> Option<String> {
+    let stem = file_name.rsplit(['/', '\\']).next()?;
+    let stem = stem.split(['.', '_']).next()?;
+    (!stem.is_empty()).then(|| stem.to_uppercase())
+}
+
+/// The JSON array returned by CoinGecko's `/coins/markets` endpoint.
+pub struct CoinGeckoMarkets;
+
+impl PriceSource for CoinGeckoMarkets {
+    fn name(&self) -> &'static str {
+        "CoinGecko markets JSON"
+    }
+    fn detect(&self, _file_name: &str, bytes: &[u8]) -> bool {
+        let text = String::from_utf8_lossy(bytes);
+        text.trim_start().starts_with('[') && text.contains("\"current_price\"")
+    }
+    fn parse(
+        &self,
+        _file_name: &str,
+        bytes: &[u8],
+    ) -> Result<Parsed<MarketPrice>, PriceSourceError> {
+        #[derive(Deserialize)]
+        struct Coin {
+            symbol: String,
+            current_price: Option<Decimal>,
+            last_updated: Option<DateTime<Utc>>,
+        }
+        let coins = serde_json::from_slice::<Vec<Coin>>(bytes)?;
+        let prices = coins
+            .into_iter()
+            .filter_map(|coin| {
+                Some(MarketPrice {
+                    symbol: coin.symbol.to_uppercase(),
+                    share_price: SharePrice {
+                        height: coin.current_price?,
+                        time: coin.last_updated?,
+                    },
+                })
+            })
+            .collect();
+        Ok(Parsed {
+            rows: prices,
+            errors: Vec::new(),
+        })
+    }
+}
+
+/// Converts deserialized rows to prices, reporting failures against `column`.
+fn convert_rows<T>(
+    parsed: Parsed<(u64, T)>,
+    column: &str,
+    convert: impl Fn(T) -> Result<MarketPrice, PriceSourceError>,
+) -> Parsed<MarketPrice> {
+    let mut prices = Parsed {
+        rows: Vec::new(),
+        errors: parsed.errors,
+    };
+    for (line, row) in parsed.rows {
+        match convert(row) {
+            Ok(price) => prices.rows.push(price),
+            Err(e) => prices.errors.push(RowError::new(line, Some(column), e)),
+        }
+    }
+    prices.errors.sort_by_key(|error| error.line);
+    prices
+}
+
+fn csv_reader(bytes: &[u8]) -> csv::Reader<&[u8]> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(bytes)
+}
+
+/// Whether the first line of a CSV has every one of `columns`.
+fn has_columns(bytes: &[u8], columns: &[&str]) -> bool {
+    let Ok(headers) = csv_reader(bytes).headers().cloned() else {
+        return false;
+    };
+    columns
+        .iter()
+        .all(|column| headers.iter().any(|header| header == *column))
+}
+
+fn parse_timestamp(text: &str) -> Result<DateTime<Utc>, PriceSourceError> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|time| time.and_utc())
+        .ok_or_else(|| PriceSourceError::InvalidTime(text.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn detected(file_name: &str, text: &str) -> (&'static str, Vec<MarketPrice>) {
+        let (name, parsed) = parse_prices(file_name, text.as_bytes()).unwrap();
+        assert_eq!(parsed.errors, vec![]);
+        (name, parsed.rows)
+    }
+
+    #[test]
+    fn test_detect_yahoo_portfolio() {
+        let csv = "Symbol,Current Price,Date,Time,Change\nAAPL,190.5,2024/03/15,16:00 EDT,1.2\n";
+        let (name, prices) = detected("quotes.csv", csv);
+        assert_eq!(name, "Yahoo portfolio");
+        assert_eq!(prices[0].share_price.height, dec!(190.5));
+    }
+
+    #[test]
+    fn test_detect_simple_csv() {
+        let csv = "symbol,price,timestamp\nvti,250.1,2024-03-15\nBTC,67000,2024-03-15T12:00:00Z\n";
+        let (name, prices) = detected("prices.csv", csv);
+        assert_eq!(name, "Symbol, price, timestamp CSV");
+        assert_eq!(prices[0].symbol, "VTI");
+        assert_eq!(
+            prices[1].share_price.time,
+            Utc.with_ymd_and_hms(2024, 3, 15, 12, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_detect_stooq_daily() {
+        let csv = "Date,Open,High,Low,Close,Volume\n\
+            2024-03-14,172.9,174.3,172.0,173.0,72913507\n\
+            2024-03-15,171.2,172.6,170.3,172.6,121752699\n";
+        let (name, prices) = detected("aapl.us_d.csv", csv);
+        assert_eq!(name, "Stooq daily CSV");
+        assert_eq!(prices.len(), 2);
+        assert_eq!(prices[1].symbol, "AAPL");
+        assert_eq!(prices[1].share_price.height, dec!(172.6));
+        assert_eq!(
+            prices[1].share_price.time,
+            Utc.with_ymd_and_hms(2024, 3, 15, 20, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_detect_coingecko_markets() {
+        let json = r#"[
+            {"id": "bitcoin", "symbol": "btc", "current_price": 67000.5, "last_updated": "2024-03-15T12:00:00.000Z"},
+            {"id": "dead", "symbol": "ded", "current_price": null, "last_updated": null}
+        ]"#;
+        let (name, prices) = detected("markets.json", json);
+        assert_eq!(name, "CoinGecko markets JSON");
+        assert_eq!(prices.len(), 1);
+        assert_eq!(prices[0].symbol, "BTC");
+    }
+
+    #[test]
+    fn test_bad_rows_are_reported() {
+        let csv = "symbol,price,timestamp\n\
+            VTI,250.1,2024-03-15\n\
+            BND,n/a,2024-03-15\n\
+            BTC,67000,yesterday\n";
+        let (_, parsed) = parse_prices("prices.csv", csv.as_bytes()).unwrap();
+        assert_eq!(parsed.rows.len(), 1);
+        let errors = parsed
+            .errors
+            .iter()
+            .map(|e| (e.line, e.column.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(errors, vec![(3, None), (4, Some("timestamp"))]);
+
+        let csv = "Symbol,Current Price,Date,Time\n\
+            AAPL,190.5,2024/03/15,16:00 EDT\n\
+            MSFT,420.1,2024/03/15,16:00 Mars/Olympus\n";
+        let (_, parsed) = parse_prices("quotes.csv", csv.as_bytes()).unwrap();
+        assert_eq!(parsed.rows.len(), 1);
+        assert_eq!(parsed.errors[0].line, 3);
+        assert_eq!(parsed.errors[0].column.as_deref(), Some("Time"));
+    }
+
+    #[test]
+    fn test_unrecognized() {
+        assert!(matches!(
+            parse_prices("notes.txt", b"hello"),
+            Err(PriceSourceError::Unrecognized(_))
+        ));
+    }
+}
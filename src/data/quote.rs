@@ -0,0 +1,164 @@
+use crate::data::market::{Product, SharePrice};
+use crate::data::yf::MarketPrice;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// One entry in a quote provider's response, either a price or the reason there is none.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Quote {
+    pub symbol: String,
+    pub price: Option<Decimal>,
+    pub time: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SymbolRefresh {
+    Updated(SharePrice),
+    Failed(String),
+}
+
+/// The outcome of the last scheduled price refresh.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RefreshStatus {
+    pub time: DateTime<Utc>,
+    /// Set when the provider could not be reached at all.
+    pub error: Option<String>,
+    pub symbols: BTreeMap<String, SymbolRefresh>,
+}
+
+impl RefreshStatus {
+    pub fn failed(time: DateTime<Utc>, error: String) -> Self {
+        RefreshStatus {
+            time,
+            error: Some(error),
+            symbols: BTreeMap::new(),
+        }
+    }
+}
+
+/// Every symbol to ask a provider for: each product's symbol and its aliases.
+pub fn quote_symbols(products: &[Product]) -> Vec<String> {
+    let mut symbols = Vec::<String>::new();
+    for symbol in products.iter().flat_map(Product::symbols) {
+        if !symbols.contains(&symbol.to_string()) {
+            symbols.push(symbol.to_string());
+        }
+    }
+    symbols
+}
+
+/// Matches a provider's quotes to products by symbol or alias, preferring a quote with a price.
+/// Quotes without a time are taken as of `now`, and products the provider left out are reported
+/// as failed.
+pub fn resolve_quotes(
+    products: &[Product],
+    quotes: Vec<Quote>,
+    now: DateTime<Utc>,
+) -> (Vec<MarketPrice>, RefreshStatus) {
+    let by_symbol = quotes
+        .into_iter()
+        .map(|quote| (quote.symbol.to_uppercase(), quote))
+        .collect::<BTreeMap<_, _>>();
+    let mut prices = Vec::new();
+    let mut status = BTreeMap::new();
+    for product in products {
+        let symbol = product.symbol().to_string();
+        let quotes = product
+            .symbols()
+            .filter_map(|symbol| by_symbol.get(&symbol.to_uppercase()))
+            .collect::<Vec<_>>();
+        let quote = quotes
+            .iter()
+            .find(|quote| quote.price.is_some() && quote.error.is_none())
+            .or(quotes.first())
+            .map(|quote| (*quote).clone());
+        let refresh = match quote {
+            None => SymbolRefresh::Failed("No quote returned".to_string()),
+            Some(Quote {
+                error: Some(error), ..
+            }) => SymbolRefresh::Failed(error),
+            Some(Quote {
+                price: Some(price),
+                time,
+                ..
+            }) if price >= Decimal::ZERO => {
+                let share_price = SharePrice {
+                    height: price,
+                    time: time.unwrap_or(now),
+                };
+                prices.push(MarketPrice {
+                    symbol: symbol.clone(),
+                    share_price: share_price.clone(),
+                });
+                SymbolRefresh::Updated(share_price)
+            }
+            Some(_) => SymbolRefresh::Failed("Invalid price".to_string()),
+        };
+        status.insert(symbol.clone(), refresh);
+    }
+    let status = RefreshStatus {
+        time: now,
+        error: None,
+        symbols: status,
+    };
+    (prices, status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::market::ProductKind;
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_resolve_quotes() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 15, 20, 0, 0).unwrap();
+        let quotes = serde_json::from_str::<Vec<Quote>>(
+            r#"[
+                {"symbol": "aapl", "price": 172.6},
+                {"symbol": "VTI", "price": 250.0, "time": "2024-03-15T19:59:00Z"},
+                {"symbol": "XYZ", "error": "Unknown symbol"},
+                {"symbol": "ETH", "error": "Unknown symbol"},
+                {"symbol": "ETH-USD", "price": 3500.0}
+            ]"#,
+        )
+        .unwrap();
+        let products = [
+            (ProductKind::Stock, "AAPL", ""),
+            (ProductKind::Etf, "VTI", ""),
+            (ProductKind::Stock, "XYZ", ""),
+            (ProductKind::Coin, "BTC", ""),
+            (ProductKind::Coin, "ETH", "ETH-USD"),
+        ]
+        .map(|(kind, symbol, alias)| {
+            let share_price = SharePrice {
+                height: dec!(1),
+                time: now,
+            };
+            let mut product = Product::new(kind, symbol.into(), symbol.into(), 1, share_price);
+            product.set_aliases(crate::data::market::parse_aliases(alias));
+            product
+        });
+        assert_eq!(
+            quote_symbols(&products),
+            ["AAPL", "VTI", "XYZ", "BTC", "ETH", "ETH-USD"].map(String::from)
+        );
+        let (prices, status) = resolve_quotes(&products, quotes, now);
+        assert_eq!(prices.len(), 3);
+        assert_eq!(prices[0].share_price.time, now);
+        assert_eq!(prices[2].symbol, "ETH");
+        assert_eq!(prices[2].share_price.height, dec!(3500));
+        assert_eq!(
+            status.symbols["XYZ"],
+            SymbolRefresh::Failed("Unknown symbol".into())
+        );
+        assert_eq!(
+            status.symbols["BTC"],
+            SymbolRefresh::Failed("No quote returned".into())
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use crate::api::ecs::Eid;
+use crate::data::{FormatError, ParseError};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The part of a lot given up in a sale, with what it cost and what it brought in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RealizedGain {
+    pub account: Eid,
+    pub product: String,
+    pub acquired: DateTime<Utc>,
+    pub sold: DateTime<Utc>,
+    pub quantity: Decimal,
+    /// Sale proceeds net of the sale's share of fees.
+    pub proceeds: Decimal,
+    /// Cost of the relieved shares including their share of purchase fees, if known.
+    pub cost_basis: Option<Decimal>,
+    /// The lot the shares were relieved from.
+    pub lot: Eid,
+}
+
+impl RealizedGain {
+    pub fn gain(&self) -> Option<Decimal> {
+        self.cost_basis.map(|cost_basis| self.proceeds - cost_basis)
+    }
+}
+
+pub fn parse_realized(csv_data: &[u8]) -> Result<HashMap<Eid, RealizedGain>, ParseError> {
+    let mut reader = csv::ReaderBuilder::new().from_reader(csv_data);
+    let rows: Vec<RealizedCsvRow> = reader
+        .deserialize()
+        .collect::<Result<Vec<RealizedCsvRow>, _>>()?;
+    let map = rows
+        .into_iter()
+        .map(|c| {
+            let realized = RealizedGain {
+                account: c.account,
+                product: c.product,
+                acquired: c.acquired,
+                sold: c.sold,
+                quantity: c.quantity,
+                proceeds: c.proceeds,
+                cost_basis: c.cost_basis,
+                lot: c.lot,
+            };
+            (c.eid, realized)
+        })
+        .collect();
+    Ok(map)
+}
+
+pub fn format_realized(realized: HashMap<Eid, RealizedGain>) -> Result<String, FormatError> {
+    let rows = realized
+        .into_iter()
+        .map(|(eid, realized)| RealizedCsvRow {
+            account: realized.account,
+            product: realized.product,
+            acquired: realized.acquired,
+            sold: realized.sold,
+            quantity: realized.quantity,
+            proceeds: realized.proceeds,
+            cost_basis: realized.cost_basis,
+            lot: realized.lot,
+            eid,
+        })
+        .collect::<Vec<_>>();
+    let mut writer = csv::WriterBuilder::new().from_writer(vec![]);
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    let data = writer.into_inner()?;
+    let string = String::from_utf8(data)?;
+    Ok(string)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct RealizedCsvRow {
+    pub account: Eid,
+    pub product: String,
+    pub acquired: DateTime<Utc>,
+    pub sold: DateTime<Utc>,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub quantity: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub proceeds: Decimal,
+    #[serde(with = "rust_decimal::serde::str_option")]
+    pub cost_basis: Option<Decimal>,
+    pub lot: Eid,
+    pub eid: Eid,
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_realized_round_trip() {
+        let csv_data = "account,product,acquired,sold,quantity,proceeds,cost_basis,lot,eid\n\
+            foo,AAPL,2021-01-01T00:00:00Z,2022-06-01T00:00:00Z,10.0,1500.0,1000.0,l0,r0\n"
+            .as_bytes();
+        let realized = super::parse_realized(csv_data).unwrap();
+        assert_eq!(realized.values().next().unwrap().gain(), Some(dec!(500)));
+        let string = super::format_realized(realized).unwrap();
+        assert_eq!(string.as_bytes(), csv_data);
+    }
+}
@@ -0,0 +1,295 @@
+use crate::api::ecs::Eid;
+use crate::data::portfolio::Lot;
+use crate::data::realized::RealizedGain;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sale {
+    pub account: Eid,
+    pub product: String,
+    pub time: DateTime<Utc>,
+    pub quantity: Decimal,
+    pub unit_price: Decimal,
+    pub fees: Option<Decimal>,
+}
+
+/// Chooses which lots a sale relieves first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ReliefMethod {
+    Fifo,
+    Lifo,
+    HighestCost,
+    SpecificLots(Vec<Eid>),
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum SaleError {
+    #[error("Sale quantity must be greater than zero")]
+    NonPositiveQuantity,
+
+    #[error("Sale price and fees must be non-negative numbers")]
+    InvalidAmount,
+
+    #[error("Unknown lot: {0:?}")]
+    UnknownLot(Eid),
+
+    #[error("Lot {0:?} does not hold this product in this account before the sale")]
+    IneligibleLot(Eid),
+
+    #[error("Cannot sell {requested} when only {available} is held")]
+    InsufficientQuantity {
+        requested: Decimal,
+        available: Decimal,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaleOutcome {
+    /// All lots after the sale, with consumed lots removed and split lots reduced.
+    pub lots: HashMap<Eid, Lot>,
+    pub realized: Vec<RealizedGain>,
+}
+
+impl Sale {
+    pub fn validate(&self) -> Result<(), SaleError> {
+        if self.quantity <= Decimal::ZERO {
+            return Err(SaleError::NonPositiveQuantity);
+        }
+        let amounts = [Some(self.unit_price), self.fees];
+        if amounts
+            .into_iter()
+            .flatten()
+            .any(|amount| amount < Decimal::ZERO)
+        {
+            return Err(SaleError::InvalidAmount);
+        }
+        Ok(())
+    }
+
+    fn is_eligible(&self, lot: &Lot) -> bool {
+        lot.account == self.account && lot.product == self.product && lot.time <= self.time
+    }
+
+    /// Relieves lots for this sale, splitting the last lot touched if only part of it is sold.
+    pub fn relieve(
+        &self,
+        lots: &HashMap<Eid, Lot>,
+        method: &ReliefMethod,
+    ) -> Result<SaleOutcome, SaleError> {
+        self.validate()?;
+        let order = self.relief_order(lots, method)?;
+        let available = order.iter().map(|eid| lots[eid].quantity).sum::<Decimal>();
+        if available < self.quantity {
+            return Err(SaleError::InsufficientQuantity {
+                requested: self.quantity,
+                available,
+            });
+        }
+        let fees = self.fees.unwrap_or_default();
+        let mut remaining = self.quantity;
+        let mut outcome = SaleOutcome {
+            lots: lots.clone(),
+            realized: Vec::new(),
+        };
+        for eid in order {
+            if remaining.is_zero() {
+                break;
+            }
+            let lot = &lots[&eid];
+            let quantity = remaining.min(lot.quantity);
+            let portion = quantity / lot.quantity;
+            let proceeds = quantity * self.unit_price - fees * quantity / self.quantity;
+            let relieved_fees = lot.fees.unwrap_or_default() * portion;
+            let cost_basis = lot
+                .unit_cost
+                .map(|unit_cost| unit_cost * quantity + relieved_fees);
+            outcome.realized.push(RealizedGain {
+                account: lot.account.clone(),
+                product: lot.product.clone(),
+                acquired: lot.time,
+                sold: self.time,
+                quantity,
+                proceeds,
+                cost_basis,
+                lot: eid.clone(),
+            });
+            if lot.quantity == quantity {
+                outcome.lots.remove(&eid);
+            } else {
+                let kept = Lot {
+                    quantity: lot.quantity - quantity,
+                    fees: lot.fees.map(|fees| fees - relieved_fees),
+                    ..lot.clone()
+                };
+                outcome.lots.insert(eid, kept);
+            }
+            remaining -= quantity;
+        }
+        Ok(outcome)
+    }
+
+    fn relief_order(
+        &self,
+        lots: &HashMap<Eid, Lot>,
+        method: &ReliefMethod,
+    ) -> Result<Vec<Eid>, SaleError> {
+        if let ReliefMethod::SpecificLots(eids) = method {
+            let mut order: Vec<Eid> = Vec::new();
+            for eid in eids {
+                let lot = lots
+                    .get(eid)
+                    .ok_or_else(|| SaleError::UnknownLot(eid.clone()))?;
+                if !self.is_eligible(lot) {
+                    return Err(SaleError::IneligibleLot(eid.clone()));
+                }
+                if !order.contains(eid) {
+                    order.push(eid.clone());
+                }
+            }
+            return Ok(order);
+        }
+        let mut eligible = lots
+            .iter()
+            .filter(|(_, lot)| self.is_eligible(lot))
+            .collect::<Vec<_>>();
+        eligible.sort_by(|(_, a), (_, b)| match method {
+            ReliefMethod::Fifo => a.time.cmp(&b.time),
+            ReliefMethod::Lifo => b.time.cmp(&a.time),
+            ReliefMethod::HighestCost => match (a.unit_cost, b.unit_cost) {
+                (Some(a_cost), Some(b_cost)) => b_cost.cmp(&a_cost),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+            .then(a.time.cmp(&b.time)),
+            ReliefMethod::SpecificLots(_) => Ordering::Equal,
+        });
+        Ok(eligible.into_iter().map(|(eid, _)| eid.clone()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+
+    fn lot(day: u32, quantity: Decimal, unit_cost: Decimal) -> Lot {
+        Lot {
+            account: "brokerage".into(),
+            time: Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap(),
+            product: "AAPL".into(),
+            quantity,
+            unit_cost: Some(unit_cost),
+            fees: None,
+        }
+    }
+
+    fn lots() -> HashMap<Eid, Lot> {
+        HashMap::from([
+            (Eid::from("a"), lot(1, dec!(10), dec!(100))),
+            (Eid::from("b"), lot(2, dec!(10), dec!(300))),
+            (Eid::from("c"), lot(3, dec!(10), dec!(200))),
+        ])
+    }
+
+    fn sale(quantity: Decimal) -> Sale {
+        Sale {
+            account: "brokerage".into(),
+            product: "AAPL".into(),
+            time: Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap(),
+            quantity,
+            unit_price: dec!(250),
+            fees: None,
+        }
+    }
+
+    fn relieved(outcome: &SaleOutcome) -> Vec<(Eid, Decimal)> {
+        outcome
+            .realized
+            .iter()
+            .map(|r| (r.lot.clone(), r.quantity))
+            .collect()
+    }
+
+    #[test]
+    fn test_fifo_splits_partial_lot() {
+        let outcome = sale(dec!(15))
+            .relieve(&lots(), &ReliefMethod::Fifo)
+            .unwrap();
+        assert_eq!(
+            relieved(&outcome),
+            vec![(Eid::from("a"), dec!(10)), (Eid::from("b"), dec!(5))]
+        );
+        assert!(!outcome.lots.contains_key(&Eid::from("a")));
+        assert_eq!(outcome.lots[&Eid::from("b")].quantity, dec!(5));
+        assert_eq!(outcome.realized[0].gain(), Some(dec!(1500)));
+        assert_eq!(outcome.realized[1].gain(), Some(dec!(-250)));
+    }
+
+    #[test]
+    fn test_lifo_and_highest_cost() {
+        let lifo = sale(dec!(5)).relieve(&lots(), &ReliefMethod::Lifo).unwrap();
+        assert_eq!(relieved(&lifo), vec![(Eid::from("c"), dec!(5))]);
+        let highest = sale(dec!(5))
+            .relieve(&lots(), &ReliefMethod::HighestCost)
+            .unwrap();
+        assert_eq!(relieved(&highest), vec![(Eid::from("b"), dec!(5))]);
+    }
+
+    #[test]
+    fn test_specific_lots() {
+        let method = ReliefMethod::SpecificLots(vec![Eid::from("c"), Eid::from("a")]);
+        let outcome = sale(dec!(12)).relieve(&lots(), &method).unwrap();
+        assert_eq!(
+            relieved(&outcome),
+            vec![(Eid::from("c"), dec!(10)), (Eid::from("a"), dec!(2))]
+        );
+        let unknown = ReliefMethod::SpecificLots(vec![Eid::from("z")]);
+        assert_eq!(
+            sale(dec!(1)).relieve(&lots(), &unknown),
+            Err(SaleError::UnknownLot(Eid::from("z")))
+        );
+    }
+
+    #[test]
+    fn test_fees_are_prorated() {
+        let mut lots = lots();
+        lots.get_mut(&Eid::from("a")).unwrap().fees = Some(dec!(10));
+        let sale = Sale {
+            fees: Some(dec!(4)),
+            ..sale(dec!(5))
+        };
+        let outcome = sale.relieve(&lots, &ReliefMethod::Fifo).unwrap();
+        assert_eq!(outcome.realized[0].proceeds, dec!(1246));
+        assert_eq!(outcome.realized[0].cost_basis, Some(dec!(505)));
+        assert_eq!(outcome.lots[&Eid::from("a")].fees, Some(dec!(5)));
+    }
+
+    #[test]
+    fn test_insufficient_quantity() {
+        assert_eq!(
+            sale(dec!(31)).relieve(&lots(), &ReliefMethod::Fifo),
+            Err(SaleError::InsufficientQuantity {
+                requested: dec!(31),
+                available: dec!(30)
+            })
+        );
+    }
+
+    #[test]
+    fn test_fractional_sales_empty_the_lot() {
+        let lots = HashMap::from([(Eid::from("a"), lot(1, dec!(0.3), dec!(40000)))]);
+        let first = sale(dec!(0.1)).relieve(&lots, &ReliefMethod::Fifo).unwrap();
+        assert_eq!(first.lots[&Eid::from("a")].quantity, dec!(0.2));
+        let second = sale(dec!(0.2))
+            .relieve(&first.lots, &ReliefMethod::Fifo)
+            .unwrap();
+        assert!(second.lots.is_empty());
+    }
+}
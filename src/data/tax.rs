@@ -0,0 +1,436 @@
+use crate::api::ecs::Eid;
+use crate::data::account::{account_name, is_taxable, Account};
+use crate::data::market::SharePrice;
+use crate::data::portfolio::Lot;
+use crate::data::realized::RealizedGain;
+use crate::data::term::{is_long_term, WashSale};
+use crate::data::FormatError;
+use chrono::{DateTime, Datelike, Utc};
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Marginal rates in percent, e.g. `24.0` for 24%.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaxRates {
+    pub short_term: f64,
+    pub long_term: f64,
+}
+
+/// How withdrawals from an account are taxed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxTreatment {
+    /// Gains are taxed when sold, at short- or long-term rates.
+    #[default]
+    Taxable,
+    /// The whole withdrawal is taxed as ordinary income, as in a traditional IRA or 401(k).
+    TaxDeferred,
+    /// Withdrawals are not taxed, as in a Roth IRA.
+    TaxFree,
+}
+
+impl TaxTreatment {
+    pub const ALL: [TaxTreatment; 3] = [
+        TaxTreatment::Taxable,
+        TaxTreatment::TaxDeferred,
+        TaxTreatment::TaxFree,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TaxTreatment::Taxable => "Taxable",
+            TaxTreatment::TaxDeferred => "Tax deferred",
+            TaxTreatment::TaxFree => "Tax free",
+        }
+    }
+}
+
+/// A user's federal and state tax rates.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaxSettings {
+    pub federal: TaxRates,
+    pub state: TaxRates,
+    /// Treatments by account name, saved before accounts had kinds. Moved onto the accounts
+    /// when they are migrated.
+    #[serde(
+        default,
+        rename = "accounts",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub legacy_treatments: BTreeMap<String, TaxTreatment>,
+}
+
+impl TaxSettings {
+    /// Combined federal and state rate as a fraction.
+    fn rate(&self, long_term: bool) -> Decimal {
+        let percent = if long_term {
+            self.federal.long_term + self.state.long_term
+        } else {
+            self.federal.short_term + self.state.short_term
+        };
+        Decimal::from_f64(percent).unwrap_or_default() / Decimal::ONE_HUNDRED
+    }
+
+    /// Estimated tax owed if the lot were sold at `share_price` at `now`. A loss owes nothing, and
+    /// a lot without a cost basis is taxed as if its basis were zero.
+    pub fn liquidation_tax(
+        &self,
+        lot: &Lot,
+        treatment: TaxTreatment,
+        share_price: &SharePrice,
+        now: DateTime<Utc>,
+    ) -> Decimal {
+        let market_value = lot.quantity * share_price.height;
+        match treatment {
+            TaxTreatment::Taxable => {
+                let gain = market_value - lot.cost_basis().unwrap_or_default();
+                gain.max(Decimal::ZERO) * self.rate(is_long_term(lot.time, now))
+            }
+            TaxTreatment::TaxDeferred => market_value.max(Decimal::ZERO) * self.rate(false),
+            TaxTreatment::TaxFree => Decimal::ZERO,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GainTotals {
+    pub proceeds: Decimal,
+    pub cost_basis: Decimal,
+    /// Losses disallowed by wash sales, which are added back into `gain`.
+    pub adjustment: Decimal,
+    pub gain: Decimal,
+    /// Number of sales left out of the totals because their cost basis is unknown.
+    pub unknown_basis: usize,
+}
+
+impl GainTotals {
+    fn add(&mut self, realized: &RealizedGain, adjustment: Decimal) {
+        match realized.cost_basis {
+            Some(cost_basis) => {
+                self.proceeds += realized.proceeds;
+                self.cost_basis += cost_basis;
+                self.adjustment += adjustment;
+                self.gain += realized.proceeds - cost_basis + adjustment;
+            }
+            None => self.unknown_basis += 1,
+        }
+    }
+}
+
+/// The loss each sale may not deduct because of wash sales, keyed by the realized gain.
+pub fn disallowed_losses(wash_sales: &[WashSale]) -> HashMap<Eid, Decimal> {
+    let mut losses = HashMap::new();
+    for wash in wash_sales {
+        *losses.entry(wash.sale.clone()).or_default() += wash.disallowed_loss;
+    }
+    losses
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaxYearSummary {
+    pub year: i32,
+    pub short_term: GainTotals,
+    pub long_term: GainTotals,
+}
+
+impl TaxYearSummary {
+    pub fn total_gain(&self) -> Decimal {
+        self.short_term.gain + self.long_term.gain
+    }
+}
+
+/// Totals realized gains in taxable accounts by the calendar year of the sale, newest year first,
+/// adding back losses disallowed by wash sales.
+pub fn tax_year_summaries(
+    realized: &HashMap<Eid, RealizedGain>,
+    wash_sales: &[WashSale],
+    accounts: &HashMap<Eid, Account>,
+) -> Vec<TaxYearSummary> {
+    let disallowed = disallowed_losses(wash_sales);
+    let mut years = BTreeMap::<i32, TaxYearSummary>::new();
+    for (eid, gain) in realized {
+        if !is_taxable(accounts, &gain.account) {
+            continue;
+        }
+        let adjustment = disallowed.get(eid).copied().unwrap_or_default();
+        let year = gain.sold.year();
+        let summary = years.entry(year).or_insert_with(|| TaxYearSummary {
+            year,
+            short_term: GainTotals::default(),
+            long_term: GainTotals::default(),
+        });
+        if is_long_term(gain.acquired, gain.sold) {
+            summary.long_term.add(gain, adjustment);
+        } else {
+            summary.short_term.add(gain, adjustment);
+        }
+    }
+    years.into_values().rev().collect()
+}
+
+/// Formats one tax year's sales in taxable accounts with the columns of IRS Form 8949, short-term
+/// sales first. Sales with a loss disallowed by wash sales get code W and the loss as their
+/// adjustment.
+pub fn format_form_8949(
+    realized: &HashMap<Eid, RealizedGain>,
+    wash_sales: &[WashSale],
+    accounts: &HashMap<Eid, Account>,
+    year: i32,
+) -> Result<String, FormatError> {
+    let disallowed = disallowed_losses(wash_sales);
+    let mut sales = realized
+        .iter()
+        .filter(|(_, gain)| gain.sold.year() == year && is_taxable(accounts, &gain.account))
+        .collect::<Vec<_>>();
+    sales.sort_by_key(|(eid, gain)| {
+        (
+            is_long_term(gain.acquired, gain.sold),
+            gain.sold,
+            gain.acquired,
+            *eid,
+        )
+    });
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for (eid, gain) in sales {
+        let mut row = Form8949Row::from(gain);
+        row.account = account_name(accounts, &gain.account);
+        if let Some(adjustment) = disallowed.get(eid) {
+            row.codes = "W".to_string();
+            row.adjustment = format_cents(*adjustment);
+            row.gain = gain
+                .gain()
+                .map(|gain| format_cents(gain + adjustment))
+                .unwrap_or_default();
+        }
+        writer.serialize(row)?;
+    }
+    let data = writer.into_inner()?;
+    let string = String::from_utf8(data)?;
+    Ok(string)
+}
+
+#[derive(Serialize)]
+struct Form8949Row {
+    #[serde(rename = "Term")]
+    term: &'static str,
+    #[serde(rename = "(a) Description of property")]
+    description: String,
+    #[serde(rename = "(b) Date acquired")]
+    acquired: String,
+    #[serde(rename = "(c) Date sold or disposed of")]
+    sold: String,
+    #[serde(rename = "(d) Proceeds")]
+    proceeds: String,
+    #[serde(rename = "(e) Cost or other basis")]
+    cost_basis: String,
+    #[serde(rename = "(f) Code(s)")]
+    codes: String,
+    #[serde(rename = "(g) Amount of adjustment")]
+    adjustment: String,
+    #[serde(rename = "(h) Gain or (loss)")]
+    gain: String,
+    #[serde(rename = "Account")]
+    account: String,
+}
+
+impl From<&RealizedGain> for Form8949Row {
+    fn from(value: &RealizedGain) -> Self {
+        let term = if is_long_term(value.acquired, value.sold) {
+            "Long"
+        } else {
+            "Short"
+        };
+        Form8949Row {
+            term,
+            description: format!("{} {}", value.quantity, value.product),
+            acquired: value.acquired.format("%m/%d/%Y").to_string(),
+            sold: value.sold.format("%m/%d/%Y").to_string(),
+            proceeds: format_cents(value.proceeds),
+            cost_basis: value.cost_basis.map(format_cents).unwrap_or_default(),
+            codes: "".to_string(),
+            adjustment: "".to_string(),
+            gain: value.gain().map(format_cents).unwrap_or_default(),
+            account: value.account.as_str().to_string(),
+        }
+    }
+}
+
+fn format_cents(amount: Decimal) -> String {
+    let cents = amount.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero);
+    format!("{:.2}", cents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::account::AccountKind;
+    use chrono::{TimeZone, Utc};
+    use rust_decimal_macros::dec;
+
+    fn realized(
+        acquired: (i32, u32, u32),
+        sold: (i32, u32, u32),
+        cost: Option<Decimal>,
+    ) -> RealizedGain {
+        RealizedGain {
+            account: "a0".into(),
+            product: "AAPL".into(),
+            acquired: Utc
+                .with_ymd_and_hms(acquired.0, acquired.1, acquired.2, 0, 0, 0)
+                .unwrap(),
+            sold: Utc
+                .with_ymd_and_hms(sold.0, sold.1, sold.2, 0, 0, 0)
+                .unwrap(),
+            quantity: dec!(10),
+            proceeds: dec!(1000),
+            cost_basis: cost,
+            lot: Eid::from("l0"),
+        }
+    }
+
+    /// Keys the gains `r0`, `r1`, … in order.
+    fn keyed(gains: Vec<RealizedGain>) -> HashMap<Eid, RealizedGain> {
+        gains
+            .into_iter()
+            .enumerate()
+            .map(|(i, gain)| (Eid::from(format!("r{}", i).as_str()), gain))
+            .collect()
+    }
+
+    fn wash(sale: &str, disallowed_loss: Decimal) -> WashSale {
+        WashSale {
+            sale: sale.into(),
+            replacement: "l9".into(),
+            product: "AAPL".into(),
+            quantity: dec!(5),
+            disallowed_loss,
+        }
+    }
+
+    #[test]
+    fn test_liquidation_tax() {
+        let settings = TaxSettings {
+            federal: TaxRates {
+                short_term: 24.0,
+                long_term: 15.0,
+            },
+            state: TaxRates {
+                short_term: 6.0,
+                long_term: 5.0,
+            },
+            legacy_treatments: BTreeMap::new(),
+        };
+        let lot = |year, unit_cost| Lot {
+            account: Eid::from("a0"),
+            time: Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap(),
+            product: "AAPL".into(),
+            quantity: dec!(10),
+            unit_cost,
+            fees: None,
+        };
+        let share_price = SharePrice {
+            height: dec!(200),
+            time: Utc.with_ymd_and_hms(2025, 6, 1, 0, 0, 0).unwrap(),
+        };
+        let now = share_price.time;
+        let tax =
+            |lot: Lot, treatment| settings.liquidation_tax(&lot, treatment, &share_price, now);
+        let taxable = TaxTreatment::Taxable;
+        assert_eq!(tax(lot(2020, Some(dec!(100))), taxable), dec!(200));
+        assert_eq!(tax(lot(2025, Some(dec!(100))), taxable), dec!(300));
+        assert_eq!(tax(lot(2020, Some(dec!(300))), taxable), dec!(0));
+        assert_eq!(tax(lot(2020, None), taxable), dec!(400));
+        assert_eq!(
+            tax(lot(2020, Some(dec!(100))), TaxTreatment::TaxDeferred),
+            dec!(600)
+        );
+        assert_eq!(
+            tax(lot(2020, Some(dec!(100))), TaxTreatment::TaxFree),
+            dec!(0)
+        );
+    }
+
+    #[test]
+    fn test_tax_year_summaries() {
+        let gains = keyed(vec![
+            realized((2023, 1, 1), (2024, 6, 1), Some(dec!(400))),
+            realized((2024, 1, 1), (2024, 6, 1), Some(dec!(1200))),
+            realized((2024, 1, 1), (2024, 12, 31), None),
+            realized((2024, 1, 1), (2025, 3, 1), Some(dec!(900))),
+        ]);
+        let summaries = tax_year_summaries(&gains, &[], &HashMap::new());
+        assert_eq!(
+            summaries.iter().map(|s| s.year).collect::<Vec<_>>(),
+            vec![2025, 2024]
+        );
+        let year_2024 = &summaries[1];
+        assert_eq!(year_2024.long_term.gain, dec!(600));
+        assert_eq!(year_2024.short_term.gain, dec!(-200));
+        assert_eq!(year_2024.short_term.unknown_basis, 1);
+        assert_eq!(year_2024.total_gain(), dec!(400));
+
+        let washes = [wash("r1", dec!(100)), wash("r1", dec!(50))];
+        let summaries = tax_year_summaries(&gains, &washes, &HashMap::new());
+        let year_2024 = &summaries[1];
+        assert_eq!(year_2024.short_term.adjustment, dec!(150));
+        assert_eq!(year_2024.short_term.gain, dec!(-50));
+        assert_eq!(year_2024.total_gain(), dec!(550));
+    }
+
+    #[test]
+    fn test_format_form_8949() {
+        let gains = keyed(vec![
+            realized((2023, 1, 1), (2024, 6, 1), Some(dec!(400))),
+            realized((2024, 1, 1), (2024, 6, 1), Some(dec!(1200))),
+            realized((2024, 1, 1), (2025, 3, 1), Some(dec!(900))),
+        ]);
+        let accounts = HashMap::from([(
+            Eid::from("a0"),
+            Account {
+                name: "brokerage".into(),
+                kind: AccountKind::Taxable,
+            },
+        )]);
+        let string = format_form_8949(&gains, &[], &accounts, 2024).unwrap();
+        assert_eq!(
+            string,
+            "Term,(a) Description of property,(b) Date acquired,(c) Date sold or disposed of,(d) Proceeds,(e) Cost or other basis,(f) Code(s),(g) Amount of adjustment,(h) Gain or (loss),Account\n\
+            Short,10 AAPL,01/01/2024,06/01/2024,1000.00,1200.00,,,-200.00,brokerage\n\
+            Long,10 AAPL,01/01/2023,06/01/2024,1000.00,400.00,,,600.00,brokerage\n"
+        );
+        let string = format_form_8949(&gains, &[wash("r1", dec!(150))], &accounts, 2024).unwrap();
+        assert!(string.contains(
+            "Short,10 AAPL,01/01/2024,06/01/2024,1000.00,1200.00,W,150.00,-50.00,brokerage\n"
+        ));
+    }
+
+    #[test]
+    fn test_tax_advantaged_sales_not_reported() {
+        let mut ira_loss = realized((2024, 1, 1), (2024, 6, 1), Some(dec!(1200)));
+        ira_loss.account = "ira".into();
+        let mut ira_only_year = realized((2024, 1, 1), (2025, 3, 1), Some(dec!(900)));
+        ira_only_year.account = "ira".into();
+        let gains = keyed(vec![
+            realized((2023, 1, 1), (2024, 6, 1), Some(dec!(400))),
+            ira_loss,
+            ira_only_year,
+        ]);
+        let account = |name: &str, kind| Account {
+            name: name.into(),
+            kind,
+        };
+        let accounts = HashMap::from([
+            (Eid::from("a0"), account("brokerage", AccountKind::Taxable)),
+            (Eid::from("ira"), account("IRA", AccountKind::Ira)),
+        ]);
+        let summaries = tax_year_summaries(&gains, &[], &accounts);
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].short_term, GainTotals::default());
+        assert_eq!(summaries[0].total_gain(), dec!(600));
+        let string = format_form_8949(&gains, &[], &accounts, 2024).unwrap();
+        assert!(!string.contains("IRA"));
+        assert_eq!(string.lines().count(), 2);
+    }
+}
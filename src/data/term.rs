@@ -1,36 +1,48 @@
+use crate::api::ecs::Eid;
+use crate::data::account::{is_taxable, Account};
 use crate::data::market::Product;
 use crate::data::portfolio::Lot;
+use crate::data::realized::RealizedGain;
+use crate::data::ParseError;
 use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+/// Holdings kept longer than this are long term.
+pub const SHORT_DURATION: Duration = Duration::days(365);
+
+pub fn is_long_term(acquired: DateTime<Utc>, disposed: DateTime<Utc>) -> bool {
+    acquired < disposed - SHORT_DURATION
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct TermReport {
     pub symbol: String,
-    pub long_term: f64,
-    pub short_term: f64,
-    pub wash: f64,
+    pub long_term: Decimal,
+    pub short_term: Decimal,
+    /// Quantity bought recently enough that a loss sale of the product now would be a wash sale.
+    pub recent: Decimal,
     pub short_exit: Option<DateTime<Utc>>,
-    pub wash_exit: Option<DateTime<Utc>>,
+    pub recent_exit: Option<DateTime<Utc>>,
 }
 
 impl TermReport {
     pub fn new(symbol: &str, lots: &[Lot], now: DateTime<Utc>) -> Self {
-        const WASH_DURATION: Duration = Duration::days(32);
-        const SHORT_DURATION: Duration = Duration::days(365);
-        let one_year_ago = now - SHORT_DURATION;
-        let wash_start = now - WASH_DURATION;
+        const RECENT_DURATION: Duration = Duration::days(32);
+        let recent_start = now - RECENT_DURATION;
         let mut report = TermReport {
             symbol: symbol.to_string(),
-            long_term: 0.0,
-            short_term: 0.0,
-            wash: 0.0,
+            long_term: Decimal::ZERO,
+            short_term: Decimal::ZERO,
+            recent: Decimal::ZERO,
             short_exit: None,
-            wash_exit: None,
+            recent_exit: None,
         };
         for lot in lots {
-            if lot.time < one_year_ago {
+            if is_long_term(lot.time, now) {
                 report.long_term += lot.quantity;
-            } else if lot.time < wash_start {
+            } else if lot.time < recent_start {
                 report.short_term += lot.quantity;
                 let this_exit = lot.time + SHORT_DURATION;
                 report.short_exit = match report.short_exit {
@@ -38,9 +50,9 @@ impl TermReport {
                     Some(previous) => Some(previous.max(this_exit)),
                 };
             } else {
-                report.wash += lot.quantity;
-                let this_exit = lot.time + WASH_DURATION;
-                report.wash_exit = match report.wash_exit {
+                report.recent += lot.quantity;
+                let this_exit = lot.time + RECENT_DURATION;
+                report.recent_exit = match report.recent_exit {
                     None => Some(this_exit),
                     Some(previous) => Some(previous.max(this_exit)),
                 };
@@ -64,3 +76,274 @@ pub fn term_reports(
     }
     reports
 }
+
+/// Purchases this close to a loss sale, before or after, make it a wash sale.
+pub const WASH_WINDOW: Duration = Duration::days(30);
+
+/// Groups of products the user treats as substantially identical for wash sales.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IdenticalProducts(HashMap<String, String>);
+
+impl IdenticalProducts {
+    pub fn are_identical(&self, a: &str, b: &str) -> bool {
+        if a == b {
+            return true;
+        }
+        match (self.0.get(a), self.0.get(b)) {
+            (Some(a_group), Some(b_group)) => a_group == b_group,
+            _ => false,
+        }
+    }
+}
+
+pub fn parse_identical_products(csv_data: &[u8]) -> Result<IdenticalProducts, ParseError> {
+    #[derive(Deserialize)]
+    struct Row {
+        symbol: String,
+        group: String,
+    }
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(csv_data);
+    let rows = reader.deserialize().collect::<Result<Vec<Row>, _>>()?;
+    let groups = rows
+        .into_iter()
+        .map(|row| (row.symbol, row.group))
+        .collect();
+    Ok(IdenticalProducts(groups))
+}
+
+/// Part of a loss sale disallowed because replacement shares were bought inside the wash window.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WashSale {
+    /// The realized loss being washed.
+    pub sale: Eid,
+    /// The lot holding the replacement shares.
+    pub replacement: Eid,
+    pub product: String,
+    pub quantity: Decimal,
+    /// Loss that may not be deducted, which is added to the replacement lot's cost basis instead.
+    pub disallowed_loss: Decimal,
+}
+
+struct Purchase {
+    eid: Eid,
+    product: String,
+    time: DateTime<Utc>,
+    quantity: Decimal,
+}
+
+/// Matches loss sales in taxable accounts, oldest first, against replacement purchases across
+/// all accounts. Losses inside tax-advantaged accounts are never deductible, so they cannot be
+/// washed.
+///
+/// Purchases are rebuilt from current lots plus the realized records relieved from them, so
+/// replacement shares count even after they have been sold. Each purchased share replaces at
+/// most one sold share.
+pub fn wash_sales(
+    realized: &HashMap<Eid, RealizedGain>,
+    lots: &HashMap<Eid, Lot>,
+    accounts: &HashMap<Eid, Account>,
+    identical: &IdenticalProducts,
+) -> Vec<WashSale> {
+    let mut purchases = lots
+        .iter()
+        .map(|(eid, lot)| {
+            let purchase = Purchase {
+                eid: eid.clone(),
+                product: lot.product.clone(),
+                time: lot.time,
+                quantity: lot.quantity,
+            };
+            (eid.clone(), purchase)
+        })
+        .collect::<HashMap<_, _>>();
+    for gain in realized.values() {
+        purchases
+            .entry(gain.lot.clone())
+            .or_insert_with(|| Purchase {
+                eid: gain.lot.clone(),
+                product: gain.product.clone(),
+                time: gain.acquired,
+                quantity: Decimal::ZERO,
+            })
+            .quantity += gain.quantity;
+    }
+    let mut purchases = purchases.into_values().collect::<Vec<_>>();
+    purchases.sort_by(|a, b| a.time.cmp(&b.time).then(a.eid.cmp(&b.eid)));
+    let mut unused = purchases
+        .iter()
+        .map(|purchase| (purchase.eid.clone(), purchase.quantity))
+        .collect::<HashMap<_, _>>();
+
+    let mut losses = realized
+        .iter()
+        .filter(|(_, gain)| {
+            is_taxable(accounts, &gain.account)
+                && gain.gain().is_some_and(|gain| gain < Decimal::ZERO)
+        })
+        .collect::<Vec<_>>();
+    losses.sort_by(|(a_eid, a), (b_eid, b)| a.sold.cmp(&b.sold).then(a_eid.cmp(b_eid)));
+
+    let mut washes = Vec::new();
+    for (sale_eid, sale) in losses {
+        let loss_per_share = -sale.gain().unwrap_or_default() / sale.quantity;
+        let mut remaining = sale.quantity;
+        for purchase in purchases.iter() {
+            if remaining <= Decimal::ZERO {
+                break;
+            }
+            if purchase.eid == sale.lot
+                || !identical.are_identical(&purchase.product, &sale.product)
+                || (purchase.time - sale.sold).abs() > WASH_WINDOW
+            {
+                continue;
+            }
+            // Shares given up in the same sale cannot replace themselves.
+            let sold_together = realized
+                .values()
+                .filter(|other| other.lot == purchase.eid && other.sold == sale.sold)
+                .map(|other| other.quantity)
+                .sum::<Decimal>();
+            let available = unused[&purchase.eid].min(purchase.quantity - sold_together);
+            let quantity = remaining.min(available);
+            if quantity <= Decimal::ZERO {
+                continue;
+            }
+            *unused.get_mut(&purchase.eid).unwrap() -= quantity;
+            remaining -= quantity;
+            washes.push(WashSale {
+                sale: sale_eid.clone(),
+                replacement: purchase.eid.clone(),
+                product: purchase.product.clone(),
+                quantity,
+                disallowed_loss: loss_per_share * quantity,
+            });
+        }
+    }
+    washes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+
+    fn day(month: u32, day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, month, day, 0, 0, 0).unwrap()
+    }
+
+    fn lot(product: &str, account: &str, time: DateTime<Utc>, quantity: Decimal) -> Lot {
+        Lot {
+            account: account.into(),
+            time,
+            product: product.into(),
+            quantity,
+            unit_cost: Some(dec!(100)),
+            fees: None,
+        }
+    }
+
+    fn loss(lot: &str, sold: DateTime<Utc>, quantity: Decimal) -> RealizedGain {
+        RealizedGain {
+            account: "taxable".into(),
+            product: "VOO".into(),
+            acquired: day(1, 2),
+            sold,
+            quantity,
+            proceeds: dec!(80) * quantity,
+            cost_basis: Some(dec!(100) * quantity),
+            lot: Eid::from(lot),
+        }
+    }
+
+    #[test]
+    fn test_wash_sale_across_accounts() {
+        let realized = HashMap::from([(Eid::from("s0"), loss("l0", day(6, 1), dec!(10)))]);
+        let lots = HashMap::from([
+            (Eid::from("l1"), lot("VOO", "ira", day(6, 20), dec!(4))),
+            (Eid::from("l2"), lot("VOO", "ira", day(8, 1), dec!(10))),
+        ]);
+        let washes = wash_sales(
+            &realized,
+            &lots,
+            &HashMap::new(),
+            &IdenticalProducts::default(),
+        );
+        assert_eq!(
+            washes,
+            vec![WashSale {
+                sale: Eid::from("s0"),
+                replacement: Eid::from("l1"),
+                product: "VOO".into(),
+                quantity: dec!(4),
+                disallowed_loss: dec!(80),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_no_wash_sale_for_tax_advantaged_loss() {
+        use crate::data::account::AccountKind;
+        let realized = HashMap::from([(
+            Eid::from("s0"),
+            RealizedGain {
+                account: "ira".into(),
+                ..loss("l0", day(6, 1), dec!(10))
+            },
+        )]);
+        let lots = HashMap::from([(Eid::from("l1"), lot("VOO", "taxable", day(6, 20), dec!(4)))]);
+        let accounts = HashMap::from([(
+            Eid::from("ira"),
+            Account {
+                name: "IRA".into(),
+                kind: AccountKind::Ira,
+            },
+        )]);
+        let identical = IdenticalProducts::default();
+        assert!(wash_sales(&realized, &lots, &accounts, &identical).is_empty());
+        assert_eq!(
+            wash_sales(&realized, &lots, &HashMap::new(), &identical).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_substantially_identical_products() {
+        let identical =
+            parse_identical_products("symbol,group\nVOO,sp500\nIVV,sp500\n".as_bytes()).unwrap();
+        assert!(identical.are_identical("VOO", "IVV"));
+        assert!(!identical.are_identical("VOO", "QQQ"));
+        let realized = HashMap::from([(Eid::from("s0"), loss("l0", day(6, 1), dec!(10)))]);
+        let lots = HashMap::from([
+            (Eid::from("l1"), lot("IVV", "taxable", day(5, 10), dec!(20))),
+            (Eid::from("l2"), lot("QQQ", "taxable", day(5, 10), dec!(20))),
+        ]);
+        let washes = wash_sales(&realized, &lots, &HashMap::new(), &identical);
+        assert_eq!(washes.len(), 1);
+        assert_eq!(washes[0].replacement, Eid::from("l1"));
+        assert_eq!(washes[0].disallowed_loss, dec!(200));
+    }
+
+    #[test]
+    fn test_shares_sold_together_are_not_replacements() {
+        let realized = HashMap::from([
+            (Eid::from("s0"), loss("l0", day(6, 1), dec!(5))),
+            (
+                Eid::from("s1"),
+                RealizedGain {
+                    acquired: day(5, 20),
+                    ..loss("l1", day(6, 1), dec!(5))
+                },
+            ),
+        ]);
+        let washes = wash_sales(
+            &realized,
+            &HashMap::new(),
+            &HashMap::new(),
+            &IdenticalProducts::default(),
+        );
+        assert!(washes.is_empty());
+    }
+}
@@ -1,6 +1,8 @@
 use crate::data::market::SharePrice;
-use chrono::{DateTime, NaiveDateTime, TimeZone};
+use crate::data::{deserialize_lenient, Parsed, RowError};
+use chrono::{DateTime, FixedOffset, LocalResult, NaiveDateTime, TimeZone, Utc};
 use chrono_tz::Tz;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -18,62 +20,200 @@ pub enum CsvParseError {
     #[error("Chrono parse error: {0}")]
     ChronoParseError(#[from] chrono::ParseError),
 
-    #[error("Chrono tz parse error: {0}")]
-    ChronoTzParseError(#[from] chrono_tz::ParseError),
+    #[error("No time zone in '{0}'")]
+    MissingTimeZone(String),
+
+    #[error("Unknown time zone '{0}'")]
+    UnknownTimeZone(String),
+
+    #[error("{0} is ambiguous in {1}, which repeats that hour when clocks go back")]
+    AmbiguousTime(NaiveDateTime, String),
+
+    #[error("{0} does not exist in {1}, which skips that hour when clocks go forward")]
+    NonexistentTime(NaiveDateTime, String),
+}
+
+/// The zone a Yahoo time is given in. Abbreviations name a fixed offset, since they already say
+/// whether daylight saving applies, while IANA names need the date to find the offset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Zone {
+    Fixed(FixedOffset),
+    Named(Tz),
+}
+
+/// Common zone abbreviations and their UTC offsets in minutes. Where an abbreviation is shared,
+/// as with CST or IST, the meaning used by US and Indian exchanges wins.
+const ABBREVIATIONS: &[(&str, i32)] = &[
+    ("UTC", 0),
+    ("GMT", 0),
+    ("Z", 0),
+    ("WET", 0),
+    ("WEST", 60),
+    ("BST", 60),
+    ("IST", 330),
+    ("CET", 60),
+    ("CEST", 120),
+    ("EET", 120),
+    ("EEST", 180),
+    ("SAST", 120),
+    ("MSK", 180),
+    ("GST", 240),
+    ("PKT", 300),
+    ("ICT", 420),
+    ("WIB", 420),
+    ("HKT", 480),
+    ("SGT", 480),
+    ("AWST", 480),
+    ("JST", 540),
+    ("KST", 540),
+    ("ACST", 570),
+    ("ACDT", 630),
+    ("AEST", 600),
+    ("AEDT", 660),
+    ("NZST", 720),
+    ("NZDT", 780),
+    ("NST", -210),
+    ("NDT", -150),
+    ("AST", -240),
+    ("ADT", -180),
+    ("BRT", -180),
+    ("ART", -180),
+    ("EST", -300),
+    ("EDT", -240),
+    ("CST", -360),
+    ("CDT", -300),
+    ("MST", -420),
+    ("MDT", -360),
+    ("PST", -480),
+    ("PDT", -420),
+    ("AKST", -540),
+    ("AKDT", -480),
+    ("HST", -600),
+];
+
+impl Zone {
+    /// Reads an abbreviation like `EDT`, an IANA name like `America/New_York`, or an offset like
+    /// `+05:30`, `-0400` or `UTC+2`.
+    fn parse(text: &str) -> Result<Zone, CsvParseError> {
+        let unknown = || CsvParseError::UnknownTimeZone(text.to_string());
+        let upper = text.to_uppercase();
+        if let Some((_, minutes)) = ABBREVIATIONS.iter().find(|(abbrev, _)| *abbrev == upper) {
+            return fixed_offset(*minutes).ok_or_else(unknown);
+        }
+        if let Ok(tz) = text.parse::<Tz>() {
+            return Ok(Zone::Named(tz));
+        }
+        let offset = ["UTC", "GMT"]
+            .iter()
+            .find_map(|prefix| upper.strip_prefix(prefix))
+            .unwrap_or(&upper);
+        parse_offset(offset).ok_or_else(unknown)
+    }
+
+    fn to_utc(self, local: NaiveDateTime) -> Result<DateTime<Utc>, CsvParseError> {
+        match self {
+            Zone::Fixed(offset) => offset
+                .from_local_datetime(&local)
+                .single()
+                .map(|time| time.with_timezone(&Utc))
+                .ok_or_else(|| CsvParseError::NonexistentTime(local, offset.to_string())),
+            Zone::Named(tz) => match tz.from_local_datetime(&local) {
+                LocalResult::Single(time) => Ok(time.with_timezone(&Utc)),
+                LocalResult::Ambiguous(_, _) => {
+                    Err(CsvParseError::AmbiguousTime(local, tz.to_string()))
+                }
+                LocalResult::None => Err(CsvParseError::NonexistentTime(local, tz.to_string())),
+            },
+        }
+    }
 }
 
-pub fn parse_market_prices(csv_bytes: &[u8]) -> Result<Vec<MarketPrice>, CsvParseError> {
+fn fixed_offset(minutes: i32) -> Option<Zone> {
+    FixedOffset::east_opt(minutes * 60).map(Zone::Fixed)
+}
+
+/// Parses `+HH`, `+HHMM` or `+HH:MM`, with either sign.
+fn parse_offset(text: &str) -> Option<Zone> {
+    let (sign, digits) = match text.split_at_checked(1)? {
+        ("+", digits) => (1, digits),
+        ("-", digits) => (-1, digits),
+        _ => return None,
+    };
+    let digits = digits.replace(':', "");
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = match digits.len() {
+        1 | 2 => (digits.parse::<i32>().ok()?, 0),
+        4 => (
+            digits[..2].parse::<i32>().ok()?,
+            digits[2..].parse::<i32>().ok()?,
+        ),
+        _ => return None,
+    };
+    if hours > 14 || minutes >= 60 {
+        return None;
+    }
+    fixed_offset(sign * (hours * 60 + minutes))
+}
+
+/// Reads the prices in a Yahoo portfolio export, reporting rows that do not parse as errors.
+pub fn parse_market_prices(csv_bytes: &[u8]) -> Result<Parsed<MarketPrice>, CsvParseError> {
     #[derive(Debug, Deserialize)]
     struct Row {
         #[serde(rename = "Symbol")]
         symbol: String,
-        #[serde(rename = "Current Price")]
-        price: f64,
+        #[serde(rename = "Current Price", with = "rust_decimal::serde::str")]
+        price: Decimal,
         #[serde(rename = "Date")]
         date: String,
         #[serde(rename = "Time")]
         time: String,
     }
-    let rows = csv::Reader::from_reader(csv_bytes)
-        .deserialize()
-        .collect::<Result<Vec<Row>, _>>()?;
-    let mut samples = Vec::new();
-    for row in rows {
+    let mut reader = csv::Reader::from_reader(csv_bytes);
+    let parsed = deserialize_lenient::<Row, _>(&mut reader)?;
+    let mut samples = Parsed {
+        rows: Vec::new(),
+        errors: parsed.errors,
+    };
+    for (line, row) in parsed.rows {
+        let time = match parse_date_time(&row.date, &row.time) {
+            Ok(time) => time,
+            Err(e) => {
+                let column = match e {
+                    CsvParseError::ChronoParseError(_) => "Date",
+                    _ => "Time",
+                };
+                samples.errors.push(RowError::new(line, Some(column), e));
+                continue;
+            }
+        };
         let share_price = SharePrice {
             height: row.price,
-            time: parse_date_time(&row.date, &row.time)?,
+            time,
         };
         let sample = MarketPrice {
             symbol: row.symbol,
             share_price,
         };
-        samples.push(sample);
+        samples.rows.push(sample);
     }
+    samples.errors.sort_by_key(|error| error.line);
     Ok(samples)
 }
 
-fn parse_date_time(date: &str, time: &str) -> Result<DateTime<chrono::Utc>, CsvParseError> {
-    let string = format!("{} {}", date, time);
-    let parts = string.rsplitn(2, ' ').collect::<Vec<_>>();
-    let (tz_abbrev, date_time_str) = (parts[0], parts[1]);
-    let tz: Tz = match tz_abbrev {
-        "EST" | "EDT" => "America/New_York",
-        "CST" | "CDT" => "America/Chicago",
-        "MST" | "MDT" => "America/Denver",
-        "PST" | "PDT" => "America/Los_Angeles",
-        "BST" => "Europe/London",
-        "UTC" => "UTC",
-        _ => "UTC", // Fallback guess
-    }
-    .parse()?;
-
-    let native_dt = NaiveDateTime::parse_from_str(date_time_str, "%Y/%m/%d %H:%M")?;
-    let utc = tz
-        .from_local_datetime(&native_dt)
-        .earliest()
-        .expect("Invalid time for timezone")
-        .with_timezone(&chrono::Utc);
-    Ok(utc)
+/// Reads a date like `2026/03/13` and a time like `14:07 EDT`, where the zone may be any that
+/// `Zone::parse` accepts.
+fn parse_date_time(date: &str, time: &str) -> Result<DateTime<Utc>, CsvParseError> {
+    let (clock, zone) = time
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or_else(|| CsvParseError::MissingTimeZone(time.to_string()))?;
+    let zone = Zone::parse(zone.trim())?;
+    let local = format!("{} {}", date.trim(), clock);
+    let local = NaiveDateTime::parse_from_str(&local, "%Y/%m/%d %H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(&local, "%Y/%m/%d %H:%M:%S"))?;
+    zone.to_utc(local)
 }
 
 #[cfg(test)]
@@ -81,11 +221,63 @@ mod tests {
     use super::*;
     use chrono::Datelike;
 
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
     #[test]
     fn test_chrono_parse() {
         let date = "2026/03/13";
         let time = "14:07 EDT";
         let fixed = parse_date_time(date, time).unwrap();
         assert_eq!(fixed.year(), 2026);
+        assert_eq!(fixed, utc(2026, 3, 13, 18, 7));
+    }
+
+    #[test]
+    fn test_time_zone_forms() {
+        let parse = |time| parse_date_time("2024/07/01", time).unwrap();
+        assert_eq!(parse("09:30 IST"), utc(2024, 7, 1, 4, 0));
+        assert_eq!(parse("09:30 aest"), utc(2024, 6, 30, 23, 30));
+        assert_eq!(parse("09:30 Europe/London"), utc(2024, 7, 1, 8, 30));
+        assert_eq!(parse("09:30 +05:30"), utc(2024, 7, 1, 4, 0));
+        assert_eq!(parse("09:30 -0400"), utc(2024, 7, 1, 13, 30));
+        assert_eq!(parse("09:30:15 UTC+2").timestamp() % 60, 15);
+        assert!(matches!(
+            parse_date_time("2024/07/01", "09:30 XYZ"),
+            Err(CsvParseError::UnknownTimeZone(zone)) if zone == "XYZ"
+        ));
+        assert!(matches!(
+            parse_date_time("2024/07/01", "09:30"),
+            Err(CsvParseError::MissingTimeZone(_))
+        ));
+    }
+
+    #[test]
+    fn test_dst_transitions() {
+        let new_york = "America/New_York";
+        // Clocks went from 2:00 to 3:00 on March 10, 2024.
+        assert!(matches!(
+            parse_date_time("2024/03/10", &format!("02:30 {}", new_york)),
+            Err(CsvParseError::NonexistentTime(_, _))
+        ));
+        assert_eq!(
+            parse_date_time("2024/03/10", &format!("03:30 {}", new_york)).unwrap(),
+            utc(2024, 3, 10, 7, 30)
+        );
+        // Clocks went from 2:00 back to 1:00 on November 3, 2024.
+        assert!(matches!(
+            parse_date_time("2024/11/03", &format!("01:30 {}", new_york)),
+            Err(CsvParseError::AmbiguousTime(_, _))
+        ));
+        // An abbreviation says which side of the change is meant.
+        assert_eq!(
+            parse_date_time("2024/11/03", "01:30 EDT").unwrap(),
+            utc(2024, 11, 3, 5, 30)
+        );
+        assert_eq!(
+            parse_date_time("2024/11/03", "01:30 EST").unwrap(),
+            utc(2024, 11, 3, 6, 30)
+        );
     }
 }
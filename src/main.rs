@@ -36,6 +36,11 @@ fn main() {
     {
         launch(App);
     }
+    #[cfg(feature = "sqlite")]
+    if std::env::args().nth(1).as_deref() == Some("migrate-sqlite") {
+        let users = std::env::args().skip(2).collect();
+        std::process::exit(backend::migrate::run(users));
+    }
     #[cfg(feature = "server")]
     {
         use tower_sessions::cookie::time::Duration;
@@ -43,6 +48,7 @@ fn main() {
         let is_prod = std::env::var("APP_ENV").unwrap_or_default() == "production";
         info!("Running in production mode: {is_prod}");
         serve(|| async move {
+            backend::refresh::spawn();
             let session_store = MemoryStore::default();
             let session_layer = SessionManagerLayer::new(session_store)
                 .with_secure(is_prod)
@@ -0,0 +1,186 @@
+use super::lots::account_choices;
+use crate::api::ecs::Eid;
+use crate::api::query_accounts;
+use crate::data::account::{Account, AccountKind};
+use dioxus::prelude::*;
+
+#[component]
+pub fn Accounts() -> Element {
+    let mut editing = use_signal(|| None::<(Option<Eid>, Account)>);
+    match editing() {
+        Some((eid, account)) => rsx!(EditAccount {
+            eid,
+            account,
+            on_end: move |_| editing.set(None),
+        }),
+        None => rsx!(AccountsView {
+            on_edit: move |edit: (Option<Eid>, Account)| editing.set(Some(edit)),
+        }),
+    }
+}
+
+#[component]
+fn AccountsView(on_edit: EventHandler<(Option<Eid>, Account)>) -> Element {
+    let mut loader = use_loader(|| async move { query_accounts().await })?;
+    let mut drop_account = use_action(move |eid| async move {
+        use crate::api::drop_account;
+        drop_account(eid).await.map(|_| loader.restart())
+    });
+    let accounts = loader();
+    rsx! {
+        div { class: "title", "Accounts" }
+        if let Some(Err(error)) = drop_account.value() {
+            article { class: "message is-danger",
+                div { class: "message-body", "{error}" }
+            }
+        }
+        table { class: "table is-bordered is-striped is-hoverable is-narrow",
+            thead {
+                tr {
+                    th { "Name" }
+                    th { "Kind" }
+                    th { "Tax treatment" }
+                    th { "Action" }
+                }
+            }
+            tbody {
+                for (eid, _) in account_choices(&accounts) {
+                    tr { key: "{eid.as_str()}",
+                        td { "{accounts[&eid].name}" }
+                        td { "{accounts[&eid].kind.label()}" }
+                        td { "{accounts[&eid].kind.treatment().label()}" }
+                        td {
+                            div { class: "buttons are-small",
+                                button { class: "button is-primary is-outlined",
+                                    onclick: {
+                                        let edit = (Some(eid.clone()), accounts[&eid].clone());
+                                        move |_| on_edit.call(edit.clone())
+                                    },
+                                    "Edit"
+                                }
+                                button { class: "button is-primary is-outlined",
+                                    onclick: {
+                                        let eid = eid.clone();
+                                        move |_| drop_account.call(eid.clone())
+                                    },
+                                    "Delete"
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        div { class: "buttons are-small",
+            button { class: "button is-primary is-outlined",
+                onclick: move |_| {
+                    let account = Account {
+                        name: String::new(),
+                        kind: AccountKind::default(),
+                    };
+                    on_edit.call((None, account))
+                },
+                "Add"
+            }
+        }
+    }
+}
+
+#[component]
+fn EditAccount(eid: Option<Eid>, account: Account, on_end: EventHandler<()>) -> Element {
+    let mut name_state = use_signal(|| account.name.clone());
+    let mut kind_state = use_signal(|| account.kind);
+    let mut save_error = use_signal(|| None::<String>);
+    let validated = use_memo(move || {
+        let account = Account {
+            name: name_state().trim().to_string(),
+            kind: kind_state(),
+        };
+        account.validate().map(|_| account)
+    });
+    let title = match eid {
+        Some(_) => "Edit Account",
+        None => "Add Account",
+    };
+    let mut save = use_action(move |account: Account| {
+        let eid = eid.clone();
+        async move {
+            use crate::api::{add_account, update_account};
+            let saved = match eid {
+                Some(eid) => update_account(eid, account).await,
+                None => add_account(account).await.map(|_| ()),
+            };
+            match saved {
+                Ok(_) => on_end.call(()),
+                Err(e) => save_error.set(Some(e.to_string())),
+            }
+            Ok::<(), anyhow::Error>(())
+        }
+    });
+    let message = match (save_error(), validated()) {
+        (Some(error), _) => error,
+        (None, Ok(_)) => "Ready to save".to_string(),
+        (None, Err(e)) => e.to_string(),
+    };
+    rsx! {
+        div { class: "modal is-active",
+            div { class: "modal-background" }
+            div { class: "modal-card",
+                header { class: "modal-card-head",
+                    p { class: "modal-card-title", "{title}" }
+                    button { class: "delete", aria_label: "close", onclick: move |_| on_end.call(()) }
+                }
+                section { class: "modal-card-body",
+                    p { class: "has-text-grey", "{message}" }
+                    div { class: "field",
+                        label { class: "label", "Name" }
+                        div { class: "control",
+                            input { class:"input", type: "text", value: "{name_state}",
+                                oninput: move |e| name_state.set(e.value())
+                            }
+                        }
+                    }
+                    div { class: "field",
+                        label { class: "label", "Kind" }
+                        div { class: "control",
+                            div { class: "select is-fullwidth",
+                                select {
+                                    onchange: move |e| {
+                                        let index = e.value().parse::<usize>().unwrap_or_default();
+                                        kind_state.set(AccountKind::ALL[index]);
+                                    },
+                                    for (index, kind) in AccountKind::ALL.iter().enumerate() {
+                                        option {
+                                            value: "{index}",
+                                            selected: *kind == kind_state(),
+                                            "{kind.label()}"
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                footer { class: "modal-card-foot",
+                    div { class: "buttons",
+                        button { class: "button is-primary",
+                            class: if save.pending() { "is-loading" },
+                            disabled: validated().is_err() || save.pending(),
+                            onclick: move |_| {
+                                if let Ok(account) = validated() {
+                                    save_error.set(None);
+                                    save.call(account);
+                                }
+                            },
+                            "Save"
+                        }
+                        button { class: "button is-light",
+                            onclick: move |_| on_end.call(()),
+                            "Cancel"
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
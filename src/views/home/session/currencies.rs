@@ -0,0 +1,158 @@
+use super::import_prices::RowErrors;
+use super::net_worth::format_date;
+use crate::api::{
+    query_currency_settings, query_fx_rates, update_currency_settings, update_fx_rates,
+};
+use crate::data::fx::{is_currency_code, CurrencySettings, FxImport};
+use dioxus::prelude::*;
+
+#[component]
+pub fn Currencies() -> Element {
+    let settings = use_loader(|| async move { query_currency_settings().await })?;
+    rsx! {
+        h1 { class: "title", "Currencies" }
+        ReportingCurrencyForm { settings: settings() }
+        ExchangeRates {}
+    }
+}
+
+#[component]
+fn ReportingCurrencyForm(settings: CurrencySettings) -> Element {
+    let mut currency = use_signal(|| settings.reporting_currency.clone());
+    let validated = use_memo(move || {
+        let code = currency().trim().to_uppercase();
+        if is_currency_code(&code) {
+            Ok(CurrencySettings {
+                reporting_currency: code,
+            })
+        } else {
+            Err(format!(
+                "'{}' is not a three-letter code like USD",
+                currency()
+            ))
+        }
+    });
+    let mut save = use_action(move |settings: CurrencySettings| async move {
+        update_currency_settings(settings).await
+    });
+    let message = match (save.value(), validated()) {
+        (_, Err(e)) => e,
+        (Some(Err(e)), _) => e.to_string(),
+        (Some(Ok(_)), _) => "Saved".to_string(),
+        (None, Ok(_)) => "Net worth and holdings are shown in this currency".to_string(),
+    };
+    rsx! {
+        h5 { class: "title is-5", "Reporting currency" }
+        p { class: "block has-text-grey", "{message}" }
+        div { class: "field has-addons",
+            div { class: "control",
+                input { class: "input",
+                    r#type: "text",
+                    value: "{currency}",
+                    oninput: move |e| currency.set(e.value())
+                }
+            }
+            div { class: "control",
+                button { class: "button is-primary",
+                    class: if save.pending() { "is-loading" },
+                    disabled: validated().is_err() || save.pending(),
+                    onclick: move |_| {
+                        if let Ok(settings) = validated() {
+                            save.call(settings);
+                        }
+                    },
+                    "Save"
+                }
+            }
+        }
+    }
+}
+
+/// The known rates, and an upload that adds rates from a price file quoting currency pairs.
+#[component]
+fn ExchangeRates() -> Element {
+    let mut rates = use_loader(|| async move { query_fx_rates().await })?;
+    let mut status = use_signal::<String>(|| "Ready".to_string());
+    let mut report = use_signal(|| None::<FxImport>);
+    let mut upload = use_action(move |name: String, content| async move {
+        status.set("Importing…".to_string());
+        report.set(None);
+        match update_fx_rates(name.clone(), content).await {
+            Ok(import) => {
+                status.set(format!(
+                    "Done importing '{}' as {}: updated {} rates",
+                    name,
+                    import.source,
+                    import.updated.len()
+                ));
+                report.set(Some(import));
+                rates.restart();
+            }
+            Err(e) => {
+                status.set(format!("Failed to import '{}': {}", name, e));
+            }
+        };
+        Ok::<(), anyhow::Error>(())
+    });
+    rsx! {
+        h5 { class: "title is-5 mt-5", "Exchange rates" }
+        table { class: "table is-striped is-narrow",
+            thead {
+                tr {
+                    th { "Pair" }
+                    th { "Rate" }
+                    th { "As of" }
+                }
+            }
+            tbody {
+                for rate in rates().rates().iter().cloned() {
+                    tr {
+                        td { "{rate.base}/{rate.quote}" }
+                        td { "{rate.rate}" }
+                        td { "{format_date(rate.time)}" }
+                    }
+                }
+            }
+        }
+        p { class: "block",
+            "Import rates from any price file whose symbols name currency pairs, like EURUSD=X or EUR/USD."
+        }
+        article { class: "message is-info",
+            div { class: "message-body", "{status()}" }
+        }
+        div { class: "file",
+            label { class: "file-label",
+                input { class: "file-input",
+                    type: "file",
+                    accept: "text/csv,application/json,.csv,.json",
+                    multiple: false,
+                    onchange: move |e| {
+                        async move {
+                            let file_data = e.files();
+                            if let Some(file) = file_data.first() {
+                                if let Ok(file_content) = file.read_string().await {
+                                    let file_name = file.name();
+                                    upload.call(file_name, file_content);
+                                }
+                            }
+                        }
+                    }
+                }
+                span { class: "file-cta",
+                    span { class: "file-icon", "⬆️" }
+                    span { class: "file-label", "Choose Rate File…"}
+                }
+            }
+        }
+        if let Some(import) = report() {
+            if !import.skipped.is_empty() {
+                p { class: "block mt-5 has-text-danger",
+                    "Skipped symbols that are not currency pairs with a positive rate: {import.skipped.join(\", \")}"
+                }
+            }
+            if !import.errors.is_empty() {
+                RowErrors { errors: import.errors }
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use crate::api::{query_history, undo_last_change};
+use dioxus::prelude::*;
+
+#[component]
+pub fn History() -> Element {
+    let mut loader = use_loader(move || async move { query_history().await })?;
+    let mut undo = use_action(move || async move {
+        let description = undo_last_change().await?;
+        loader.restart();
+        Ok::<Option<String>, ServerFnError>(description)
+    });
+    let mut items = loader();
+    items.reverse();
+    let status = match undo.value() {
+        Some(Ok(description)) => match description() {
+            Some(description) => format!("Undid: {}", description),
+            None => "Nothing left to undo".to_string(),
+        },
+        Some(Err(error)) => error.to_string(),
+        None => "Changes to lots and prices, newest first".to_string(),
+    };
+    rsx! {
+        div { class: "block level",
+            div { class: "level-left",
+                h1 { class: "level-item title", "History" }
+            }
+            div { class: "level-right",
+                button { class: "level-item button is-warning is-outlined",
+                    class: if undo.pending() { "is-loading" },
+                    disabled: undo.pending(),
+                    onclick: move |_| undo.call(),
+                    "Undo last change"
+                }
+            }
+        }
+        p { class: "block has-text-grey", "{status}" }
+        table { class: "table is-striped is-narrow",
+            thead {
+                tr {
+                    th { "#" }
+                    th { "Time" }
+                    th { "Change" }
+                }
+            }
+            tbody {
+                for item in items {
+                    tr { key: "{item.seq}",
+                        class: if item.undone { "has-text-grey-light" },
+                        td { "{item.seq}" }
+                        td { {item.time.format("%Y-%m-%d %H:%M").to_string()} }
+                        td {
+                            if item.undone {
+                                s { "{item.description}" }
+                            } else {
+                                "{item.description}"
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,14 +1,24 @@
-use crate::api::{query_lots, query_products};
+use super::net_worth::{format_date, format_money};
+use crate::api::ecs::{Eid, LotItem};
+use crate::api::{
+    query_accounts, query_currency_settings, query_fx_rates, query_lots, query_products,
+    query_wash_sales,
+};
 use crate::bulma::BulmaColor;
 use crate::components::pill::LabelPill;
 use crate::components::progress::ProgressIndicator;
 use crate::components::ProductLabel;
+use crate::data::account::{account_name, Account};
+use crate::data::fx::FxTable;
+use crate::data::gain::UnrealizedGain;
 use crate::data::market::Product;
 use crate::data::ownership::Ownership;
 use crate::data::portfolio::Lot;
-use crate::data::term::{term_reports, TermReport};
+use crate::data::term::{term_reports, TermReport, WashSale};
 use chrono::{DateTime, Utc};
 use dioxus::prelude::*;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
 use std::collections::HashMap;
 
 #[component]
@@ -27,7 +37,21 @@ pub fn Holdings() -> Element {
             .map(|item| item.0)
             .collect::<Vec<_>>()
     });
-    let mut holding_rows = holding_rows(lots(), products_by_symbol(), Utc::now());
+    let wash_sales = use_loader(|| async move { query_wash_sales().await })?;
+    let wash_adjustments = use_memo(move || wash_adjustments(&lot_items(), &wash_sales()));
+    let accounts = use_loader(|| async move { query_accounts().await })?;
+    let fx = use_loader(|| async move { query_fx_rates().await })?;
+    let currency = use_loader(|| async move { query_currency_settings().await })?;
+    let mut expanded = use_signal(|| None::<String>);
+    let mut holding_rows = holding_rows(
+        lots(),
+        products_by_symbol(),
+        &accounts(),
+        wash_adjustments(),
+        &fx(),
+        &currency().reporting_currency,
+        Utc::now(),
+    );
     holding_rows.sort_by(|a, b| match (a.ownership, b.ownership) {
         (Some(_), None) => std::cmp::Ordering::Less,
         (None, Some(_)) => std::cmp::Ordering::Greater,
@@ -43,15 +67,32 @@ pub fn Holdings() -> Element {
                         th { "Asset" }
                         th { "Level" }
                         th { "Term" }
+                        th { "Gain" }
                     }
                 }
                 tbody {
-                    { holding_rows.iter().map(|row| rsx! {
+                    for row in holding_rows.iter() {
                         tr {
                             // Product
                             td {
                                 ProductLabel{ symbol: row.symbol.clone(), name: row.name.clone()}
-                                LabelPill { label: row.accounts.clone(), value: row.quantity.to_string(), color: BulmaColor::Light }
+                                if row.stale {
+                                    span { class: "tag is-warning mb-2",
+                                        title: "Price as of {format_date(row.price_time)}",
+                                        "Stale price"
+                                    }
+                                }
+                                a {
+                                    title: "Show accounts",
+                                    onclick: {
+                                        let symbol = row.symbol.clone();
+                                        move |_| {
+                                            let open = expanded() != Some(symbol.clone());
+                                            expanded.set(open.then(|| symbol.clone()));
+                                        }
+                                    },
+                                    LabelPill { label: row.accounts.clone(), value: row.quantity.to_string(), color: BulmaColor::Light }
+                                }
                             }
                             // Level
                             td {
@@ -64,17 +105,74 @@ pub fn Holdings() -> Element {
                             td {
                                 TermIndicator{ term_report: row.term_report.clone() }
                             }
+                            // Gain
+                            td {
+                                GainIndicator{ gain: row.gain.clone(), wash: row.wash.clone(), currency: row.currency.clone() }
+                            }
                         }
-                    }) }
+                        if expanded() == Some(row.symbol.clone()) {
+                            for holding in row.breakdown.iter() {
+                                tr { class: "has-background-white-ter",
+                                    td { class: "pl-5",
+                                        LabelPill {
+                                            label: holding.name.clone(),
+                                            value: holding.quantity.to_string(),
+                                            color: BulmaColor::Light,
+                                            tail: Some(holding.kind.clone()),
+                                        }
+                                    }
+                                    td {}
+                                    td {}
+                                    td {
+                                        GainIndicator{ gain: holding.gain.clone(), wash: None, currency: row.currency.clone() }
+                                    }
+                                }
+                            }
+                        }
+                    }
                 }
             }
         }
     }
 }
 
+#[derive(Debug, Clone, Default, PartialEq)]
+struct WashAdjustment {
+    lots: usize,
+    basis_adjustment: Decimal,
+}
+
+/// Totals the disallowed losses carried into replacement lots that are still held, by product.
+fn wash_adjustments(
+    lot_items: &[LotItem],
+    wash_sales: &[WashSale],
+) -> HashMap<String, WashAdjustment> {
+    let lots = lot_items
+        .iter()
+        .map(|item| (item.to_eid(), &item.0))
+        .collect::<HashMap<_, _>>();
+    let mut adjustments = HashMap::<String, WashAdjustment>::new();
+    for wash in wash_sales {
+        let Some(lot) = lots.get(&wash.replacement) else {
+            continue;
+        };
+        let adjustment = adjustments.entry(lot.product.clone()).or_default();
+        adjustment.lots += 1;
+        if lot.cost_basis().is_some() {
+            let held = (lot.quantity / wash.quantity).min(Decimal::ONE);
+            adjustment.basis_adjustment += wash.disallowed_loss * held;
+        }
+    }
+    adjustments
+}
+
 fn holding_rows(
     lots: Vec<Lot>,
     products: HashMap<String, Product>,
+    accounts: &HashMap<Eid, Account>,
+    wash_adjustments: HashMap<String, WashAdjustment>,
+    fx: &FxTable,
+    reporting_currency: &str,
     now: DateTime<Utc>,
 ) -> Vec<HoldingRow> {
     let lots_by_product: HashMap<String, Vec<Lot>> =
@@ -92,21 +190,44 @@ fn holding_rows(
         .map(|(symbol, lots)| {
             let product = products.get(&symbol).unwrap();
             let name = product.name().to_string();
-            let quantity = lots
-                .iter()
-                .fold(0.0, |quantity, lot| quantity + lot.quantity);
-            let ownership = match product.supply() {
-                Some(value) => Some(Ownership::new(quantity, value)),
-                None => None,
-            };
+            let quantity = lots.iter().map(|lot| lot.quantity).sum::<Decimal>();
+            let ownership = product
+                .supply()
+                .and_then(|value| Ownership::new(quantity, value));
             let term_report = term_reports.get(&symbol).unwrap().clone();
+            let mut gain = UnrealizedGain::new(&lots, product.share_price());
+            let mut wash = wash_adjustments.get(&symbol).cloned();
+            if let Some(wash) = &wash {
+                gain.adjust_basis(wash.basis_adjustment);
+            }
+            let mut breakdown = account_holdings(&lots, product, accounts);
+            // Amounts stay in the product's own currency when there is no rate to convert them.
+            let currency = match fx.rate(product.currency(), reporting_currency) {
+                Some(rate) => {
+                    gain = gain.convert(rate);
+                    if let Some(wash) = wash.as_mut() {
+                        wash.basis_adjustment *= rate;
+                    }
+                    for holding in breakdown.iter_mut() {
+                        holding.gain = holding.gain.convert(rate);
+                    }
+                    reporting_currency.to_string()
+                }
+                None => product.currency().to_string(),
+            };
             HoldingRow {
+                stale: product.is_stale(now),
+                price_time: product.share_price().time,
                 symbol,
                 name,
-                accounts: format_accounts(&lots),
-                quantity: quantity.floor() as usize,
+                accounts: format_accounts(&lots, accounts),
+                breakdown,
+                quantity: quantity.floor().to_usize().unwrap_or_default(),
                 ownership,
                 term_report,
+                gain,
+                wash,
+                currency,
             }
         })
         .collect::<Vec<_>>();
@@ -114,17 +235,17 @@ fn holding_rows(
     rows
 }
 
-fn format_accounts(lots: &Vec<Lot>) -> String {
+fn format_accounts(lots: &Vec<Lot>, accounts: &HashMap<Eid, Account>) -> String {
     let mut account_shares = {
-        let mut map = HashMap::<String, f64>::new();
+        let mut map = HashMap::<String, Decimal>::new();
         for lot in lots {
-            *map.entry(lot.account.clone()).or_default() += lot.quantity;
+            *map.entry(account_name(accounts, &lot.account)).or_default() += lot.quantity;
         }
         let vec = map.into_iter().collect::<Vec<_>>();
         vec
     };
 
-    account_shares.sort_by(|a, b| a.1.total_cmp(&b.1));
+    account_shares.sort_by_key(|a| a.1);
     let first = account_shares.remove(account_shares.len() - 1);
     if account_shares.is_empty() {
         first.0.clone()
@@ -133,31 +254,76 @@ fn format_accounts(lots: &Vec<Lot>) -> String {
     }
 }
 
+/// A product's lots in one account.
+fn account_holdings(
+    lots: &[Lot],
+    product: &Product,
+    accounts: &HashMap<Eid, Account>,
+) -> Vec<AccountHolding> {
+    let mut lots_by_account = HashMap::<Eid, Vec<Lot>>::new();
+    for lot in lots {
+        lots_by_account
+            .entry(lot.account.clone())
+            .or_default()
+            .push(lot.clone());
+    }
+    let mut holdings = lots_by_account
+        .into_iter()
+        .map(|(eid, lots)| AccountHolding {
+            name: account_name(accounts, &eid),
+            kind: accounts
+                .get(&eid)
+                .map(|account| account.kind.label())
+                .unwrap_or_default()
+                .to_string(),
+            quantity: lots.iter().map(|lot| lot.quantity).sum(),
+            gain: UnrealizedGain::new(&lots, product.share_price()),
+        })
+        .collect::<Vec<_>>();
+    holdings.sort_by(|a, b| a.name.cmp(&b.name));
+    holdings
+}
+
+#[derive(Debug, Clone)]
+struct AccountHolding {
+    name: String,
+    kind: String,
+    quantity: Decimal,
+    gain: UnrealizedGain,
+}
+
 #[derive(Debug, Clone)]
 struct HoldingRow {
     symbol: String,
     name: String,
+    stale: bool,
+    price_time: DateTime<Utc>,
     accounts: String,
+    breakdown: Vec<AccountHolding>,
     quantity: usize,
     ownership: Option<Ownership>,
     term_report: TermReport,
+    gain: UnrealizedGain,
+    wash: Option<WashAdjustment>,
+    /// The currency the gain is shown in.
+    currency: String,
 }
 
 #[component]
 fn TermIndicator(term_report: TermReport) -> Element {
-    let long_term = term_report.long_term.ceil() as usize;
+    let long_term = whole_shares(term_report.long_term);
     let long_exit = Some("\u{00a0}\u{00a0}∞\u{00a0}\u{00a0}".to_string());
-    let short_term = term_report.short_term.ceil() as usize;
+    let short_term = whole_shares(term_report.short_term);
     let short_exit = term_report
         .short_exit
         .map(|exit| exit.format("%b %-d").to_string());
-    let wash = term_report.wash.ceil() as usize;
-    let wash_exit = term_report
-        .wash_exit
+    let recent = whole_shares(term_report.recent);
+    let recent_exit = term_report
+        .recent_exit
         .map(|exit| exit.format("%b %-d").to_string());
     rsx! {
-        if wash > 0 {
-            LabelPill { label: "Wash", value: wash, color: BulmaColor::Danger, tail: wash_exit }
+        if recent > 0 {
+            LabelPill { label: "Recent", value: recent, color: BulmaColor::Danger, tail: recent_exit }
         }
         if short_term > 0 {
             LabelPill { label: "Short", value: short_term, color: BulmaColor::Warning, tail: short_exit }
@@ -168,6 +334,39 @@ fn TermIndicator(term_report: TermReport) -> Element {
     }
 }
 
+/// Rounds a quantity up so that any fraction of a share still shows.
+fn whole_shares(quantity: Decimal) -> usize {
+    quantity.ceil().to_usize().unwrap_or_default()
+}
+
+#[component]
+fn GainIndicator(gain: UnrealizedGain, wash: Option<WashAdjustment>, currency: String) -> Element {
+    let has_basis = gain.cost_basis > Decimal::ZERO || gain.market_value > Decimal::ZERO;
+    let (label, color) = if gain.gain < Decimal::ZERO {
+        ("Loss", BulmaColor::Danger)
+    } else {
+        ("Gain", BulmaColor::Success)
+    };
+    let amount = format_money(gain.gain.abs(), &currency);
+    let unknown_basis = whole_shares(gain.unknown_basis);
+    rsx! {
+        if has_basis {
+            LabelPill { label, value: amount, color }
+        }
+        if unknown_basis > 0 {
+            LabelPill { label: "No basis", value: unknown_basis, color: BulmaColor::Light }
+        }
+        if let Some(wash) = wash {
+            LabelPill {
+                label: "Wash",
+                value: wash.lots,
+                color: BulmaColor::Danger,
+                tail: Some(format!("+{}", format_money(wash.basis_adjustment, &currency))),
+            }
+        }
+    }
+}
+
 #[component]
 fn OwnershipTags(ownership: Ownership) -> Element {
     rsx! {
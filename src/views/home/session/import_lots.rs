@@ -0,0 +1,291 @@
+use super::lots::account_choices;
+use crate::api::ecs::Eid;
+use crate::api::{import_lots, preview_lot_import, query_accounts, query_products};
+use crate::data::broker::{BrokerPreset, ColumnMapping, ImportPreview};
+use crate::data::portfolio::Lot;
+use dioxus::prelude::*;
+use std::collections::HashSet;
+
+#[component]
+pub fn ImportLots() -> Element {
+    let accounts = use_loader(|| async move { query_accounts().await })?;
+    let products = use_loader(|| async move { query_products().await })?;
+    let account_choices = account_choices(&accounts());
+    let mut preset_state = use_signal(|| BrokerPreset::Generic);
+    let mut mapping_state = use_signal(|| BrokerPreset::Generic.mapping());
+    let mut account_state = use_signal({
+        let first = account_choices.first().map(|(eid, _)| eid.clone());
+        move || first
+    });
+    let mut preview_state = use_signal(|| None::<ImportPreview>);
+    let mut included = use_signal(HashSet::<usize>::new);
+    let mut status = use_signal(|| "Choose a preset and an account, then a CSV export".to_string());
+
+    let mut preview = use_action(move |name: String, content: String| async move {
+        let Some(account) = account_state() else {
+            status.set("Add an account before importing lots".to_string());
+            return Ok::<(), anyhow::Error>(());
+        };
+        status.set(format!("Reading '{}'…", name));
+        match preview_lot_import(content, mapping_state(), account).await {
+            Ok(result) => {
+                let known = products()
+                    .iter()
+                    .map(|product| product.symbol().to_string())
+                    .collect::<HashSet<_>>();
+                // New lots for known products are selected; duplicates are left for review.
+                included.set(
+                    result
+                        .lots
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, imported)| {
+                            imported.duplicate_of.is_none() && known.contains(&imported.lot.product)
+                        })
+                        .map(|(index, _)| index)
+                        .collect(),
+                );
+                status.set(format!(
+                    "Read {} lots from '{}', skipped {} rows",
+                    result.lots.len(),
+                    name,
+                    result.skipped.len()
+                ));
+                preview_state.set(Some(result));
+            }
+            Err(e) => {
+                preview_state.set(None);
+                status.set(format!("Failed to read '{}': {}", name, e));
+            }
+        }
+        Ok(())
+    });
+    let mut commit = use_action(move |lots: Vec<Lot>| async move {
+        match import_lots(lots).await {
+            Ok(eids) => {
+                preview_state.set(None);
+                included.set(HashSet::new());
+                status.set(format!("Imported {} lots", eids.len()));
+            }
+            Err(e) => status.set(e.to_string()),
+        }
+        Ok::<(), anyhow::Error>(())
+    });
+    let chosen = move || {
+        let included = included();
+        preview_state()
+            .map(|preview| {
+                preview
+                    .lots
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(index, _)| included.contains(index))
+                    .map(|(_, imported)| imported.lot)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+    };
+    let known = products()
+        .iter()
+        .map(|product| product.symbol().to_string())
+        .collect::<HashSet<_>>();
+    rsx! {
+        h1 { class: "title", "Import Lots" }
+        h2 { class: "subtitle", "Add purchases from a broker's transaction export" }
+        article { class: "message is-info",
+            div { class: "message-body", "{status}" }
+        }
+        div { class: "columns",
+            div { class: "field column is-one-third",
+                label { class: "label", "Preset" }
+                div { class: "control",
+                    div { class: "select is-fullwidth",
+                        select {
+                            onchange: move |e| {
+                                let index = e.value().parse::<usize>().unwrap_or_default();
+                                let preset = BrokerPreset::ALL[index];
+                                preset_state.set(preset);
+                                mapping_state.set(preset.mapping());
+                            },
+                            for (index, preset) in BrokerPreset::ALL.iter().enumerate() {
+                                option {
+                                    value: "{index}",
+                                    selected: *preset == preset_state(),
+                                    "{preset.label()}"
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            div { class: "field column is-one-third",
+                label { class: "label", "Account" }
+                div { class: "control",
+                    div { class: "select is-fullwidth",
+                        select {
+                            onchange: move |e| account_state.set(Some(Eid::from(e.value().as_str()))),
+                            for (eid, name) in account_choices.iter() {
+                                option {
+                                    value: "{eid.as_str()}",
+                                    selected: Some(eid) == account_state().as_ref(),
+                                    "{name}"
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        MappingFields { mapping: mapping_state }
+        div { class: "block file",
+            label { class: "file-label",
+                input { class: "file-input",
+                    type: "file",
+                    accept: "text/csv",
+                    multiple: false,
+                    onchange: move |e| {
+                        async move {
+                            let file_data = e.files();
+                            if let Some(file) = file_data.first() {
+                                if let Ok(file_content) = file.read_string().await {
+                                    preview.call(file.name(), file_content);
+                                }
+                            }
+                        }
+                    }
+                }
+                span { class: "file-cta",
+                    span { class: "file-icon", "⬆️" }
+                    span { class: "file-label", "Choose Transactions CSV…" }
+                }
+            }
+        }
+        if let Some(result) = preview_state() {
+            table { class: "table is-striped is-narrow",
+                thead {
+                    tr {
+                        th { "Import" }
+                        th { "Line" }
+                        th { "Date" }
+                        th { "Product" }
+                        th { "Quantity" }
+                        th { "Unit cost" }
+                        th { "Fees" }
+                        th { "Note" }
+                    }
+                }
+                tbody {
+                    for (index, imported) in result.lots.iter().enumerate() {
+                        tr {
+                            td {
+                                input { type: "checkbox",
+                                    checked: included().contains(&index),
+                                    disabled: !known.contains(&imported.lot.product),
+                                    onchange: move |e: FormEvent| {
+                                        if e.checked() {
+                                            included.write().insert(index);
+                                        } else {
+                                            included.write().remove(&index);
+                                        }
+                                    }
+                                }
+                            }
+                            td { "{imported.line}" }
+                            td { {imported.lot.time.format("%Y-%m-%d").to_string()} }
+                            td { "{imported.lot.product}" }
+                            td { "{imported.lot.quantity}" }
+                            td { {imported.lot.unit_cost.map(|cost| format!("{:.2}", cost)).unwrap_or_default()} }
+                            td { {imported.lot.fees.map(|fees| format!("{:.2}", fees)).unwrap_or_default()} }
+                            td {
+                                if !known.contains(&imported.lot.product) {
+                                    span { class: "tag is-danger is-light", "Unknown product" }
+                                } else if imported.duplicate_of.is_some() {
+                                    span { class: "tag is-warning is-light", "Possible duplicate" }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            if !result.skipped.is_empty() {
+                details { class: "block",
+                    summary { "Skipped {result.skipped.len()} rows" }
+                    ul {
+                        for row in result.skipped.iter() {
+                            li { "Line {row.line}: {row.reason}" }
+                        }
+                    }
+                }
+            }
+            button { class: "button is-primary",
+                class: if commit.pending() { "is-loading" },
+                disabled: included().is_empty() || commit.pending(),
+                onclick: move |_| commit.call(chosen()),
+                "Import {included().len()} lots"
+            }
+        }
+    }
+}
+
+#[component]
+fn MappingFields(mapping: Signal<ColumnMapping>) -> Element {
+    let optional = |value: String| {
+        let value = value.trim().to_string();
+        (!value.is_empty()).then_some(value)
+    };
+    rsx! {
+        div { class: "columns is-multiline",
+            MappingField {
+                label: "Date column",
+                value: mapping().date,
+                on_change: move |value| mapping.write().date = value,
+            }
+            MappingField {
+                label: "Symbol column",
+                value: mapping().symbol,
+                on_change: move |value| mapping.write().symbol = value,
+            }
+            MappingField {
+                label: "Quantity column",
+                value: mapping().quantity,
+                on_change: move |value| mapping.write().quantity = value,
+            }
+            MappingField {
+                label: "Price column",
+                value: mapping().price.unwrap_or_default(),
+                on_change: move |value| mapping.write().price = optional(value),
+            }
+            MappingField {
+                label: "Fee columns, comma separated",
+                value: mapping().fees.join(", "),
+                on_change: move |value: String| {
+                    mapping.write().fees = value
+                        .split(',')
+                        .filter_map(|name| optional(name.to_string()))
+                        .collect();
+                },
+            }
+            MappingField {
+                label: "Action column",
+                value: mapping().action.unwrap_or_default(),
+                on_change: move |value| mapping.write().action = optional(value),
+            }
+        }
+    }
+}
+
+#[component]
+fn MappingField(label: String, value: String, on_change: EventHandler<String>) -> Element {
+    rsx! {
+        div { class: "field column is-one-third",
+            label { class: "label", "{label}" }
+            div { class: "control",
+                input { class: "input",
+                    r#type: "text",
+                    value: "{value}",
+                    oninput: move |e| on_change.call(e.value())
+                }
+            }
+        }
+    }
+}
@@ -1,14 +1,33 @@
-use crate::api::update_product_prices;
+use super::net_worth::format_date;
+use crate::api::ecs::PriceUpdate;
+use crate::api::{query_refresh_status, update_product_prices};
+use crate::components::SharePriceLabel;
+use crate::data::quote::SymbolRefresh;
+use crate::data::RowError;
 use dioxus::prelude::*;
 
 #[component]
 pub fn ImportPrices() -> Element {
     let mut status = use_signal::<String>(|| "Ready".to_string());
-    let mut upload = use_action(move |name, content| async move {
+    let mut report = use_signal(|| None::<PriceUpdate>);
+    let mut row_errors = use_signal(Vec::<RowError>::new);
+    let mut upload = use_action(move |name: String, content| async move {
         status.set("Importing…".to_string());
-        match update_product_prices(content).await {
-            Ok(_) => {
-                status.set(format!("Done importing '{}'", name));
+        report.set(None);
+        row_errors.set(Vec::new());
+        match update_product_prices(name.clone(), content).await {
+            Ok(import) => {
+                let update = import.update;
+                let products = update.matched.len() + update.unpriced.len();
+                status.set(format!(
+                    "Done importing '{}' as {}: priced {} of {} products",
+                    name,
+                    import.source,
+                    update.matched.len(),
+                    products
+                ));
+                report.set(Some(update));
+                row_errors.set(import.errors);
             }
             Err(e) => {
                 status.set(format!("Failed to import '{}': {}", name, e));
@@ -18,16 +37,17 @@ pub fn ImportPrices() -> Element {
     });
     rsx! {
         h1 { class: "title", "Import Prices"}
-        h2 { class: "subtitle", "Update prices from portfolio CSV"}
+        h2 { class: "subtitle", "Update prices from a Yahoo portfolio, Stooq or symbol/price CSV, or CoinGecko markets JSON"}
 
-        article { class: "message is-info",
+        article { class: "message",
+            class: if report().is_some_and(|update| update.matched.is_empty()) { "is-warning" } else { "is-info" },
             div { class: "message-body", "{status()}" }
         }
         div { class: "file",
             label { class: "file-label",
                 input { class: "file-input",
                     type: "file",
-                    accept: "text/csv",
+                    accept: "text/csv,application/json,.csv,.json",
                     multiple: false,
                     onchange: move |e| {
                         async move {
@@ -43,7 +63,133 @@ pub fn ImportPrices() -> Element {
                 }
                 span { class: "file-cta",
                     span { class: "file-icon", "⬆️" }
-                    span { class: "file-label", "Choose Portfolio CSV…"}
+                    span { class: "file-label", "Choose Price File…"}
+                }
+            }
+        }
+        if !row_errors().is_empty() {
+            RowErrors { errors: row_errors() }
+        }
+        if let Some(update) = report() {
+            ImportReport { update }
+        }
+        RefreshStatusView {}
+    }
+}
+
+/// Lists every product and file symbol touched by an import. Symbols that match no product can
+/// be added as aliases on the Products page.
+#[component]
+fn ImportReport(update: PriceUpdate) -> Element {
+    rsx! {
+        table { class: "table is-striped is-narrow mt-5",
+            thead {
+                tr {
+                    th { "Symbol" }
+                    th { "Result" }
+                    th { "Old price" }
+                    th { "New price" }
+                }
+            }
+            tbody {
+                for change in update.matched {
+                    tr {
+                        td { "{change.symbol}" }
+                        if change.before == change.after {
+                            td { class: "has-text-grey", "Unchanged" }
+                        } else {
+                            td { class: "has-text-success", "Updated" }
+                        }
+                        td { SharePriceLabel { share_price: change.before } }
+                        td { SharePriceLabel { share_price: change.after } }
+                    }
+                }
+                for symbol in update.unpriced {
+                    tr {
+                        td { "{symbol}" }
+                        td { class: "has-text-grey", "No price in file" }
+                        td {}
+                        td {}
+                    }
+                }
+                for symbol in update.unmatched {
+                    tr {
+                        td { "{symbol}" }
+                        td { class: "has-text-danger", "No matching product or alias" }
+                        td {}
+                        td {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Rows of the file that could not be read; the rest were still imported.
+#[component]
+pub fn RowErrors(errors: Vec<RowError>) -> Element {
+    rsx! {
+        details { class: "block mt-5", open: true,
+            summary { class: "has-text-danger", "Skipped {errors.len()} rows that could not be read" }
+            table { class: "table is-narrow",
+                thead {
+                    tr {
+                        th { "Line" }
+                        th { "Column" }
+                        th { "Problem" }
+                    }
+                }
+                tbody {
+                    for error in errors {
+                        tr {
+                            td { "{error.line}" }
+                            td { {error.column.unwrap_or_default()} }
+                            td { "{error.message}" }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The outcome of the last scheduled refresh, when a quote provider is configured.
+#[component]
+fn RefreshStatusView() -> Element {
+    let status = use_loader(|| async move { query_refresh_status().await })?;
+    let Some(status) = status() else {
+        return rsx!();
+    };
+    rsx! {
+        h2 { class: "subtitle mt-5", "Scheduled refresh" }
+        p { class: "block has-text-grey",
+            "Last run {format_date(status.time)}"
+        }
+        if let Some(error) = status.error {
+            article { class: "message is-danger",
+                div { class: "message-body", "{error}" }
+            }
+        }
+        table { class: "table is-striped is-narrow",
+            thead {
+                tr {
+                    th { "Symbol" }
+                    th { "Result" }
+                }
+            }
+            tbody {
+                for (symbol, refresh) in status.symbols {
+                    tr {
+                        td { "{symbol}" }
+                        match refresh {
+                            SymbolRefresh::Updated(price) => rsx! {
+                                td { class: "has-text-success", "{price.height}" }
+                            },
+                            SymbolRefresh::Failed(error) => rsx! {
+                                td { class: "has-text-danger", "{error}" }
+                            },
+                        }
+                    }
                 }
             }
         }
@@ -1,32 +1,108 @@
-use crate::api::{query_lots, query_products};
+use super::sell_lots::SellLots;
+use crate::api::ecs::{Eid, LotItem};
+use crate::api::{query_accounts, query_lots, query_products};
+use crate::data::account::{account_name, Account};
+use crate::data::portfolio::{parse_lot_amount, parse_lot_quantity, parse_lot_time, Lot, LotError};
+use chrono::SecondsFormat;
 use dioxus::prelude::*;
+use rust_decimal::Decimal;
 use std::cmp::Ordering;
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq, Default)]
 struct Editor {
+    pub eid: Option<Eid>,
+    pub accounts: Vec<(Eid, String)>,
     pub account: String,
     pub time: String,
     pub products: Vec<String>,
     pub product: String,
     pub quantity: String,
+    pub unit_cost: String,
+    pub fees: String,
 }
 impl Editor {
-    pub fn new(products: Vec<String>) -> Self {
+    pub fn new(products: Vec<String>, accounts: Vec<(Eid, String)>) -> Self {
         let product = products[0].clone();
+        let account = accounts
+            .first()
+            .map(|(eid, _)| eid.as_str().to_string())
+            .unwrap_or_default();
         Self {
-            account: "".to_string(),
+            eid: None,
+            accounts,
+            account,
             time: "".to_string(),
             products,
             product,
             quantity: "".to_string(),
+            unit_cost: "".to_string(),
+            fees: "".to_string(),
         }
     }
+    pub fn edit(item: &LotItem, products: Vec<String>, accounts: Vec<(Eid, String)>) -> Self {
+        let lot = &item.0;
+        Self {
+            eid: Some(item.to_eid()),
+            accounts,
+            account: lot.account.as_str().to_string(),
+            time: lot.time.to_rfc3339_opts(SecondsFormat::Secs, true),
+            products,
+            product: lot.product.clone(),
+            quantity: lot.quantity.to_string(),
+            unit_cost: format_amount(lot.unit_cost),
+            fees: format_amount(lot.fees),
+        }
+    }
+    pub fn to_lot(&self) -> Result<Lot, LotError> {
+        let lot = Lot {
+            account: Eid::from(self.account.as_str()),
+            time: parse_lot_time(&self.time)?,
+            product: self.product.clone(),
+            quantity: parse_lot_quantity(&self.quantity)?,
+            unit_cost: parse_lot_amount(&self.unit_cost)?,
+            fees: parse_lot_amount(&self.fees)?,
+        };
+        lot.validate()?;
+        Ok(lot)
+    }
+}
+
+fn format_amount(amount: Option<Decimal>) -> String {
+    amount.map(|amount| amount.to_string()).unwrap_or_default()
+}
+
+/// Account ids and names, sorted by name, for choosing an account.
+pub(super) fn account_choices(accounts: &HashMap<Eid, Account>) -> Vec<(Eid, String)> {
+    let mut choices = accounts
+        .iter()
+        .map(|(eid, account)| (eid.clone(), account.name.clone()))
+        .collect::<Vec<_>>();
+    choices.sort_by(|a, b| a.1.cmp(&b.1));
+    choices
 }
 
 #[component]
 pub fn Lots() -> Element {
     let products = use_loader(|| async move { query_products().await })?;
+    let accounts = use_loader(|| async move { query_accounts().await })?;
     let mut editor_signal = use_signal(|| None::<Editor>);
+    let mut selling = use_signal(|| false);
+    let product_symbols = move || {
+        let mut product_symbols = products()
+            .iter()
+            .map(|product| product.symbol().to_string())
+            .collect::<Vec<_>>();
+        product_symbols.sort();
+        product_symbols
+    };
+    if selling() {
+        return rsx!(SellLots {
+            products: product_symbols(),
+            accounts: account_choices(&accounts()),
+            on_end: move |_| selling.set(false),
+        });
+    }
     match editor_signal() {
         Some(editor) => rsx!(EditLot {
             editor,
@@ -36,15 +112,16 @@ pub fn Lots() -> Element {
             }
         }),
         None => rsx!(LotsView {
-            on_edit: move |_| {
-                let mut product_symbols = products()
-                    .iter()
-                    .map(|product| product.symbol().to_string())
-                    .collect::<Vec<_>>();
-                product_symbols.sort();
-                let editor = Editor::new(product_symbols);
+            on_edit: move |item: Option<LotItem>| {
+                let editor = match item {
+                    Some(item) => {
+                        Editor::edit(&item, product_symbols(), account_choices(&accounts()))
+                    }
+                    None => Editor::new(product_symbols(), account_choices(&accounts())),
+                };
                 editor_signal.set(Some(editor))
             },
+            on_sell: move |_| selling.set(true),
         }),
     }
 }
@@ -57,44 +134,85 @@ enum Ending {
 
 #[component]
 fn EditLot(editor: Editor, on_end: EventHandler<Ending>) -> Element {
-    let mut account_state = use_signal(|| editor.account);
-    let mut time_state = use_signal(|| editor.time);
-    let mut product_state = use_signal(|| editor.product);
-    let mut quantity_state = use_signal(|| editor.quantity);
-    let mut valid = use_signal(|| "".to_string());
-    let _products = editor.products.clone();
-    use_effect(move || {
-        let product = product_state();
-        let account = account_state();
-        let time = time_state();
-        let quantity = quantity_state();
-        let desc = format!(
-            "Account: {}, Time: {}, Product: {}, Quantity: {}",
-            account, time, product, quantity
-        );
-        valid.set(desc);
+    let mut account_state = use_signal(|| editor.account.clone());
+    let mut time_state = use_signal(|| editor.time.clone());
+    let mut product_state = use_signal(|| editor.product.clone());
+    let mut quantity_state = use_signal(|| editor.quantity.clone());
+    let mut unit_cost_state = use_signal(|| editor.unit_cost.clone());
+    let mut fees_state = use_signal(|| editor.fees.clone());
+    let mut save_error = use_signal(|| None::<String>);
+    let validated = use_memo(move || {
+        Editor {
+            account: account_state(),
+            time: time_state(),
+            product: product_state(),
+            quantity: quantity_state(),
+            unit_cost: unit_cost_state(),
+            fees: fees_state(),
+            ..Editor::default()
+        }
+        .to_lot()
     });
+    let eid = editor.eid.clone();
+    let title = match eid {
+        Some(_) => "Edit Lot",
+        None => "Add Lot",
+    };
+    let mut save = use_action(move |lot: Lot| {
+        let eid = eid.clone();
+        async move {
+            use crate::api::{add_lot, update_lot};
+            let saved = match eid {
+                Some(eid) => update_lot(eid, lot).await,
+                None => add_lot(lot).await.map(|_| ()),
+            };
+            match saved {
+                Ok(_) => on_end.call(Ending::Save),
+                Err(e) => save_error.set(Some(e.to_string())),
+            }
+            Ok::<(), anyhow::Error>(())
+        }
+    });
+    let message = match (save_error(), validated()) {
+        (Some(error), _) => error,
+        (None, Ok(_)) => "Ready to save".to_string(),
+        (None, Err(e)) => e.to_string(),
+    };
     let products = editor.products.clone();
+    let accounts = editor.accounts.clone();
     rsx! {
         div { class: "modal is-active",
             div { class: "modal-background" }
             div { class: "modal-card",
                 header { class: "modal-card-head",
-                    p { class: "modal-card-title", "Edit Lot" }
+                    p { class: "modal-card-title", "{title}" }
                     button { class: "delete", aria_label: "close", onclick: move |_| on_end.call(Ending::Cancel) }
                 }
                 section { class: "modal-card-body",
-                    p { class: "has-text-grey", "{valid}" }
+                    p { class: "has-text-grey", "{message}" }
                     div { class: "field",
                         label { class: "label", "Account" }
                         div { class: "control",
-                            input { class:"input", type: "text", oninput: move |e| account_state.set(e.value()) }
+                            div { class: "select is-fullwidth",
+                                select {
+                                    onchange: move |e| account_state.set(e.value()),
+                                    for (eid, name) in accounts.iter() {
+                                        option {
+                                            value: "{eid.as_str()}",
+                                            selected: eid.as_str() == account_state(),
+                                            "{name}"
+                                        }
+                                    }
+                                }
+                            }
                         }
                     }
                     div { class: "field",
                         label { class: "label", "Time" }
                         div { class: "control",
-                            input { class:"input", type: "text", oninput: move |e| time_state.set(e.value()) }
+                            input { class:"input", type: "text", placeholder: "YYYY-MM-DD", value: "{time_state}",
+                                oninput: move |e| time_state.set(e.value())
+                            }
                         }
                     }
                     div { class: "columns",
@@ -118,15 +236,42 @@ fn EditLot(editor: Editor, on_end: EventHandler<Ending>) -> Element {
                         div { class: "field column is-half",
                             label { class: "label", "Quantity" }
                             div { class: "control",
-                                input { class:"input", type: "number", oninput: move |e| quantity_state.set(e.value()) }
+                                input { class:"input", type: "number", value: "{quantity_state}",
+                                    oninput: move |e| quantity_state.set(e.value())
+                                }
+                            }
+                        }
+                    }
+                    div { class: "columns",
+                        div { class: "field column is-half",
+                            label { class: "label", "Unit cost" }
+                            div { class: "control",
+                                input { class:"input", type: "number", placeholder: "Unknown", value: "{unit_cost_state}",
+                                    oninput: move |e| unit_cost_state.set(e.value())
+                                }
+                            }
+                        }
+                        div { class: "field column is-half",
+                            label { class: "label", "Fees" }
+                            div { class: "control",
+                                input { class:"input", type: "number", placeholder: "None", value: "{fees_state}",
+                                    oninput: move |e| fees_state.set(e.value())
+                                }
                             }
                         }
                     }
                 }
                 footer { class: "modal-card-foot",
                     div { class: "buttons",
-                        button { class: "button is-primary", disabled: true,
-                            onclick: move |_| on_end.call(Ending::Save),
+                        button { class: "button is-primary",
+                            class: if save.pending() { "is-loading" },
+                            disabled: validated().is_err() || save.pending(),
+                            onclick: move |_| {
+                                if let Ok(lot) = validated() {
+                                    save_error.set(None);
+                                    save.call(lot);
+                                }
+                            },
                             "Save"
                         }
                         button { class: "button is-light",
@@ -141,8 +286,11 @@ fn EditLot(editor: Editor, on_end: EventHandler<Ending>) -> Element {
 }
 
 #[component]
-fn LotsView(on_edit: EventHandler<()>) -> Element {
+fn LotsView(on_edit: EventHandler<Option<LotItem>>, on_sell: EventHandler<()>) -> Element {
     let mut loader = use_loader(move || async move { query_lots().await })?;
+    let accounts = use_loader(|| async move { query_accounts().await })?;
+    let accounts = accounts();
+    let name = |eid: &Eid| account_name(&accounts, eid);
     let mut drop_lot = use_action(move |eid| async move {
         use crate::api::drop_lot;
         drop_lot(eid).await.and_then(|_| {
@@ -152,7 +300,7 @@ fn LotsView(on_edit: EventHandler<()>) -> Element {
     });
     let mut items = loader();
     items.sort_by(|a, b| {
-        let by_account = a.0.account.cmp(&b.0.account);
+        let by_account = name(&a.0.account).cmp(&name(&b.0.account));
         if by_account == Ordering::Equal {
             let by_product = a.0.product.cmp(&b.0.product);
             if by_product == Ordering::Equal {
@@ -175,6 +323,11 @@ fn LotsView(on_edit: EventHandler<()>) -> Element {
     });
     rsx! {
         div { class: "title", "Lots"}
+        if let Some(Err(error)) = drop_lot.value() {
+            article { class: "message is-danger",
+                div { class: "message-body", "{error}" }
+            }
+        }
         table { class: "table is-bordered is-striped is-hoverable is-narrow",
             thead {
                 tr {
@@ -182,26 +335,37 @@ fn LotsView(on_edit: EventHandler<()>) -> Element {
                     th { "Time" }
                     th { "Product" }
                     th { "Quantity" }
+                    th { "Unit cost" }
                     th { "Action" }
                 }
             }
             tbody {
                 for item in items.iter() {
                     tr {
-                        td { "{item.0.account}" }
+                        td { {name(&item.0.account)} }
                         td { "{item.0.time}" }
                         td { "{item.0.product}" }
                         td { "{item.0.quantity}" }
+                        td { {format_amount(item.0.unit_cost)} }
                         td {
-                            button { class: "button is-primary is-outlined is-small",
-                                onclick: {
-                                    let eid = item.to_eid();
-                                    move |_| {
-                                        let eid = eid.clone();
-                                        drop_lot.call(eid);
-                                    }
-                                },
-                                "Delete"
+                            div { class: "buttons are-small",
+                                button { class: "button is-primary is-outlined",
+                                    onclick: {
+                                        let item = item.clone();
+                                        move |_| on_edit.call(Some(item.clone()))
+                                    },
+                                    "Edit"
+                                }
+                                button { class: "button is-primary is-outlined",
+                                    onclick: {
+                                        let eid = item.to_eid();
+                                        move |_| {
+                                            let eid = eid.clone();
+                                            drop_lot.call(eid);
+                                        }
+                                    },
+                                    "Delete"
+                                }
                             }
                         }
                     }
@@ -209,9 +373,15 @@ fn LotsView(on_edit: EventHandler<()>) -> Element {
                 }
             }
         }
-        button { class: "button is-primary is-small is-outlined",
-            onclick: move |_| { on_edit.call(())},
-            "Add"
+        div { class: "buttons are-small",
+            button { class: "button is-primary is-outlined",
+                onclick: move |_| { on_edit.call(None)},
+                "Add"
+            }
+            button { class: "button is-primary is-outlined",
+                onclick: move |_| { on_sell.call(())},
+                "Sell"
+            }
         }
     }
 }
@@ -9,22 +9,47 @@ use products::Products;
 mod net_worth;
 use net_worth::NetWorthPage;
 
+mod realized_gains;
+use realized_gains::RealizedGains;
+
+mod accounts;
+use accounts::Accounts;
+
 mod lots;
 use lots::Lots;
+mod sell_lots;
 
 mod side_menu;
 use side_menu::SideMenu;
 
+mod tax_settings;
+use tax_settings::TaxSettingsPage;
+
+mod currencies;
+use currencies::Currencies;
+
+mod history;
+use history::History;
+
 mod import_prices;
 use import_prices::ImportPrices;
 
+mod import_lots;
+use import_lots::ImportLots;
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 enum Tab {
     Holdings,
     Products,
     Lots,
+    Accounts,
     NetWorth,
+    RealizedGains,
     ImportPrices,
+    ImportLots,
+    History,
+    TaxSettings,
+    Currencies,
 }
 
 #[component]
@@ -41,8 +66,14 @@ pub fn Session(session: ReadSignal<SessionState>) -> Element {
                         Tab::Holdings => rsx! (Holdings {}),
                         Tab::Products => rsx! (Products {}),
                         Tab::Lots => rsx!(Lots {}),
+                        Tab::Accounts => rsx!(Accounts {}),
                         Tab::NetWorth => rsx!(NetWorthPage {}),
+                        Tab::RealizedGains => rsx!(RealizedGains {}),
                         Tab::ImportPrices => rsx!(ImportPrices {}),
+                        Tab::ImportLots => rsx!(ImportLots {}),
+                        Tab::History => rsx!(History {}),
+                        Tab::TaxSettings => rsx!(TaxSettingsPage {}),
+                        Tab::Currencies => rsx!(Currencies {}),
                     }
             }
         }
@@ -1,6 +1,12 @@
-use crate::api::{query_lots, query_products};
-use crate::data::net_worth::NetWorthReport;
+use crate::api::{
+    query_accounts, query_currency_settings, query_fx_rates, query_lots, query_net_worth_history,
+    query_products, query_tax_settings,
+};
+use crate::data::fx::USD;
+use crate::data::net_worth::{ChartRange, NetWorthReport, NetWorthSnapshot};
 use dioxus::prelude::*;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
 
 #[component]
 pub fn NetWorthPage() -> Element {
@@ -10,23 +16,160 @@ pub fn NetWorthPage() -> Element {
             .await
             .map(|items| items.into_iter().map(|item| item.0).collect::<Vec<_>>())
     })?;
-    let report = NetWorthReport::new(&lots(), &products());
-    let pre_tax = format_dollars(report.pre_tax);
+    let accounts = use_loader(|| async move { query_accounts().await })?;
+    let tax = use_loader(|| async move { query_tax_settings().await })?;
+    let fx = use_loader(|| async move { query_fx_rates().await })?;
+    let currency = use_loader(|| async move { query_currency_settings().await })?;
+    let currency = currency().reporting_currency;
+    let report = NetWorthReport::new(&lots(), &products(), &accounts(), &tax(), &fx(), &currency);
+    let pre_tax = format_money(report.pre_tax, &currency);
+    let after_tax = format_money(report.after_tax, &currency);
     let as_of = format_date(report.as_of);
+    let oldest_as_of = format_date(report.oldest_as_of);
     let unpriced_products = report.unpriced_products.len();
+    let mut stale_products = report.stale_products.iter().cloned().collect::<Vec<_>>();
+    stale_products.sort();
+    let stale_products = stale_products.join(", ");
+    let mut unconverted_products = report
+        .unconverted_products
+        .iter()
+        .cloned()
+        .collect::<Vec<_>>();
+    unconverted_products.sort();
+    let unconverted_products = unconverted_products.join(", ");
+    let oldest_age = report
+        .price_ages(chrono::Utc::now())
+        .map(|(_, oldest)| format_age(oldest))
+        .unwrap_or_default();
     rsx! {
         h1 { class: "title", "Net Worth" }
         h5 { class: "title is-5", "Summary" }
         nav { class: "level is-mobile",
             LevelDetailItem { label: "Pre tax".to_string(), content: pre_tax}
+            LevelDetailItem { label: "After tax".to_string(), content: after_tax }
             LevelDetailItem { label: "As of".to_string(), content: as_of }
+            LevelDetailItem { label: "Oldest price".to_string(), content: oldest_as_of }
             LevelDetailItem { label: "Unpriced".to_string(), content: unpriced_products.to_string() }
         }
+        if !stale_products.is_empty() {
+            article { class: "message is-warning",
+                div { class: "message-body",
+                    "Stale prices for {stale_products}; the oldest is {oldest_age} old."
+                }
+            }
+        }
+        if !unconverted_products.is_empty() {
+            article { class: "message is-warning",
+                div { class: "message-body",
+                    "No exchange rate to {currency} for {unconverted_products}, so they are left out."
+                }
+            }
+        }
+        NetWorthChart { currency: currency.clone() }
+    }
+}
+
+const CHART_WIDTH: f64 = 600.0;
+const CHART_HEIGHT: f64 = 200.0;
+
+#[component]
+fn NetWorthChart(currency: String) -> Element {
+    let history = use_loader(|| async move { query_net_worth_history().await })?;
+    let mut range = use_signal(|| ChartRange::OneYear);
+    let history = history()
+        .into_iter()
+        .filter(|snapshot| snapshot.currency == currency)
+        .collect::<Vec<_>>();
+    let snapshots = range().select(&history, chrono::Utc::now());
+    rsx! {
+        div { class: "block level",
+            div { class: "level-left",
+                h5 { class: "level-item title is-5", "Trend" }
+            }
+            div { class: "level-right",
+                div { class: "level-item buttons has-addons",
+                    for choice in ChartRange::ALL {
+                        button { class: "button is-small",
+                            class: if choice == range() { "is-selected is-link" },
+                            onclick: move |_| range.set(choice),
+                            "{choice.label()}"
+                        }
+                    }
+                }
+            }
+        }
+        if snapshots.len() < 2 {
+            p { class: "has-text-grey", "Not enough history for this range yet" }
+        } else {
+            svg {
+                width: "100%",
+                height: "{CHART_HEIGHT}",
+                view_box: "0 0 {CHART_WIDTH} {CHART_HEIGHT}",
+                preserve_aspect_ratio: "none",
+                xmlns: "http://www.w3.org/2000/svg",
+                polyline {
+                    points: "{chart_points(&snapshots)}",
+                    fill: "none",
+                    stroke: "hsl(217, 71%, 53%)",
+                    stroke_width: "2",
+                    vector_effect: "non-scaling-stroke",
+                }
+            }
+            ChartAxis { snapshots: snapshots.clone(), currency }
+        }
     }
 }
 
 #[component]
-fn LevelDetailItem(label: String, content: String) -> Element {
+fn ChartAxis(snapshots: Vec<NetWorthSnapshot>, currency: String) -> Element {
+    let (low, high) = value_bounds(&snapshots);
+    let first = snapshots
+        .first()
+        .map(|s| format_date(s.time))
+        .unwrap_or_default();
+    let last = snapshots
+        .last()
+        .map(|s| format_date(s.time))
+        .unwrap_or_default();
+    rsx! {
+        nav { class: "level is-mobile is-size-7 has-text-grey",
+            div { class: "level-left", div { class: "level-item", "{first}" } }
+            div { class: "level-item", "Low {format_money(low, &currency)} · High {format_money(high, &currency)}" }
+            div { class: "level-right", div { class: "level-item", "{last}" } }
+        }
+    }
+}
+
+fn value_bounds(snapshots: &[NetWorthSnapshot]) -> (Decimal, Decimal) {
+    snapshots
+        .iter()
+        .fold((Decimal::MAX, Decimal::MIN), |(low, high), s| {
+            (low.min(s.pre_tax), high.max(s.pre_tax))
+        })
+}
+
+/// Scales snapshots, oldest first, into SVG polyline points filling the chart.
+fn chart_points(snapshots: &[NetWorthSnapshot]) -> String {
+    let (Some(first), Some(last)) = (snapshots.first(), snapshots.last()) else {
+        return String::new();
+    };
+    let span = (last.time - first.time).num_seconds().max(1) as f64;
+    let (low, high) = value_bounds(snapshots);
+    let height = if high > low { high - low } else { Decimal::ONE };
+    snapshots
+        .iter()
+        .map(|s| {
+            let x = (s.time - first.time).num_seconds() as f64 / span * CHART_WIDTH;
+            let portion = ((s.pre_tax - low) / height).to_f64().unwrap_or_default();
+            let y = CHART_HEIGHT - portion * CHART_HEIGHT;
+            format!("{:.1},{:.1}", x, y)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[component]
+pub fn LevelDetailItem(label: String, content: String) -> Element {
     rsx! {
         div { class: "level-item has-text-centered",
             div {
@@ -37,12 +180,38 @@ fn LevelDetailItem(label: String, content: String) -> Element {
     }
 }
 
+/// Formats a price's age in its largest whole unit, like `3d` or `5h`.
+pub fn format_age(age: chrono::Duration) -> String {
+    if age.num_days() > 0 {
+        format!("{}d", age.num_days())
+    } else if age.num_hours() > 0 {
+        format!("{}h", age.num_hours())
+    } else {
+        format!("{}m", age.num_minutes().max(0))
+    }
+}
+
 pub fn format_date(date: chrono::DateTime<chrono::Utc>) -> String {
     date.format("%-m/%-d/%y").to_string()
 }
 
-pub fn format_dollars(amount: f64) -> String {
-    let digits = amount.round().to_string();
+pub fn format_dollars(amount: Decimal) -> String {
+    format_money(amount, USD)
+}
+
+/// Formats an amount like `format_dollars`, marked with the currency's sign where it has a common
+/// one and with its code otherwise.
+pub fn format_money(amount: Decimal, currency: &str) -> String {
+    let prefix = match currency {
+        "USD" => "$".to_string(),
+        "EUR" => "€".to_string(),
+        "GBP" => "£".to_string(),
+        "JPY" => "¥".to_string(),
+        code => format!("{}\u{202f}", code),
+    };
+    let digits = amount
+        .round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero)
+        .to_string();
     let unit = match digits.len() {
         0 => Unit::Zero,
         1..=3 => Unit::Ones,
@@ -52,7 +221,15 @@ pub fn format_dollars(amount: f64) -> String {
         13..=15 => Unit::Trillions,
         _ => Unit::ALot,
     };
-    unit.format_digits(&digits, "$")
+    unit.format_digits(&digits, &prefix)
+}
+
+pub fn format_signed_dollars(amount: Decimal) -> String {
+    if amount < Decimal::ZERO {
+        format!("-{}", format_dollars(-amount))
+    } else {
+        format_dollars(amount)
+    }
 }
 
 enum Unit {
@@ -1,37 +1,343 @@
 use crate::api::query_products;
 use crate::components::{ProductLabel, SharePriceLabel};
+use crate::data::fx::USD;
+use crate::data::market::{
+    parse_aliases, parse_product_supply, parse_share_price, Product, ProductError, ProductKind,
+    SharePrice,
+};
+use chrono::Utc;
 use dioxus::prelude::*;
 
+#[derive(Debug, Clone, PartialEq)]
+struct Editor {
+    /// The symbol of the product being edited, or `None` when adding one.
+    pub editing: Option<String>,
+    pub kind: ProductKind,
+    pub symbol: String,
+    pub name: String,
+    pub aliases: String,
+    pub supply: String,
+    pub share_price: String,
+    pub currency: String,
+    pub original_price: Option<SharePrice>,
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Self {
+            editing: None,
+            kind: ProductKind::Stock,
+            symbol: "".to_string(),
+            name: "".to_string(),
+            aliases: "".to_string(),
+            supply: "".to_string(),
+            share_price: "".to_string(),
+            currency: USD.to_string(),
+            original_price: None,
+        }
+    }
+    pub fn edit(product: &Product) -> Self {
+        Self {
+            editing: Some(product.symbol().to_string()),
+            kind: product.kind(),
+            symbol: product.symbol().to_string(),
+            name: product.name().to_string(),
+            aliases: product.aliases().join(" "),
+            supply: product
+                .supply()
+                .map(|supply| supply.to_string())
+                .unwrap_or_default(),
+            share_price: product.share_price().height.to_string(),
+            currency: product.currency().to_string(),
+            original_price: Some(product.share_price().clone()),
+        }
+    }
+    /// A new share price is taken as of now; an unchanged one keeps its time.
+    pub fn to_product(&self) -> Result<Product, ProductError> {
+        let supply = if self.kind.has_supply() {
+            parse_product_supply(&self.supply)?
+        } else {
+            0
+        };
+        let height = parse_share_price(&self.share_price)?;
+        let share_price = match &self.original_price {
+            Some(original) if original.height == height => original.clone(),
+            _ => SharePrice {
+                height,
+                time: Utc::now(),
+            },
+        };
+        let mut product = Product::new(
+            self.kind,
+            self.symbol.trim().to_uppercase(),
+            self.name.trim().to_string(),
+            supply,
+            share_price,
+        );
+        product.set_aliases(parse_aliases(&self.aliases));
+        product.set_currency(self.currency.trim().to_uppercase());
+        product.validate()?;
+        Ok(product)
+    }
+}
+
 #[component]
 pub fn Products() -> Element {
-    let product_loader = use_loader(|| async move { query_products().await })?;
+    let mut editor_signal = use_signal(|| None::<Editor>);
+    match editor_signal() {
+        Some(editor) => rsx!(EditProduct {
+            editor,
+            on_end: move |_| editor_signal.set(None),
+        }),
+        None => rsx!(ProductsView {
+            on_edit: move |product: Option<Product>| {
+                let editor = match product {
+                    Some(product) => Editor::edit(&product),
+                    None => Editor::new(),
+                };
+                editor_signal.set(Some(editor))
+            },
+        }),
+    }
+}
+
+#[component]
+fn ProductsView(on_edit: EventHandler<Option<Product>>) -> Element {
+    let mut product_loader = use_loader(|| async move { query_products().await })?;
+    let mut drop_product = use_action(move |symbol| async move {
+        use crate::api::drop_product;
+        drop_product(symbol).await.map(|_| product_loader.restart())
+    });
     let products = use_memo(move || {
         let mut products = product_loader().clone();
 
         products.sort_by(|a, b| a.symbol().cmp(&b.symbol()));
         products
     });
+    let now = Utc::now();
     rsx! {
         div { class: "block level",
             div { class: "level-left",
                 h1 { class: "level-item title", "Products" }
             }
+            div { class: "level-right",
+                button { class: "level-item button is-primary is-outlined",
+                    onclick: move |_| on_edit.call(None),
+                    "Add"
+                }
+            }
+        }
+        if let Some(Err(error)) = drop_product.value() {
+            article { class: "message is-danger",
+                div { class: "message-body", "{error}" }
+            }
         }
         div { class: "block",
             table { class: "table is-striped",
                 thead {
                     tr {
                         th { "Symbol" }
+                        th { "Kind" }
+                        th { "Aliases" }
                         th { "Supply" }
                         th { "Share price" }
+                        th { "Currency" }
+                        th { "Action" }
                     }
                 }
                 tbody {
                     for product in products() {
                         tr {
                             td { ProductLabel{ symbol: product.symbol(), name: product.name() } }
+                            td { "{product.kind().label()}" }
+                            td { {product.aliases().join(", ")} }
                             td { SupplyLabel{ supply: product.supply() } }
-                            td { SharePriceLabel{ share_price: product.share_price().clone()} }
+                            td { SharePriceLabel{ share_price: product.share_price().clone(), stale: product.is_stale(now) } }
+                            td { "{product.currency()}" }
+                            td {
+                                div { class: "buttons are-small",
+                                    button { class: "button is-primary is-outlined",
+                                        onclick: {
+                                            let product = product.clone();
+                                            move |_| on_edit.call(Some(product.clone()))
+                                        },
+                                        "Edit"
+                                    }
+                                    button { class: "button is-primary is-outlined",
+                                        onclick: {
+                                            let symbol = product.symbol().to_string();
+                                            move |_| drop_product.call(symbol.clone())
+                                        },
+                                        "Delete"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn EditProduct(editor: Editor, on_end: EventHandler<()>) -> Element {
+    let mut kind_state = use_signal(|| editor.kind);
+    let mut symbol_state = use_signal(|| editor.symbol.clone());
+    let mut name_state = use_signal(|| editor.name.clone());
+    let mut aliases_state = use_signal(|| editor.aliases.clone());
+    let mut supply_state = use_signal(|| editor.supply.clone());
+    let mut share_price_state = use_signal(|| editor.share_price.clone());
+    let mut currency_state = use_signal(|| editor.currency.clone());
+    let mut save_error = use_signal(|| None::<String>);
+    let editing = editor.editing.clone();
+    let original_price = editor.original_price.clone();
+    let validated = use_memo(move || {
+        Editor {
+            editing: None,
+            kind: kind_state(),
+            symbol: symbol_state(),
+            name: name_state(),
+            aliases: aliases_state(),
+            supply: supply_state(),
+            share_price: share_price_state(),
+            currency: currency_state(),
+            original_price: original_price.clone(),
+        }
+        .to_product()
+    });
+    let title = match editing {
+        Some(_) => "Edit Product",
+        None => "Add Product",
+    };
+    let is_editing = editing.is_some();
+    let mut save = use_action(move |product: Product| {
+        let editing = editing.clone();
+        async move {
+            use crate::api::{add_product, update_product};
+            let saved = match editing {
+                Some(symbol) => update_product(symbol, product).await,
+                None => add_product(product).await,
+            };
+            match saved {
+                Ok(_) => on_end.call(()),
+                Err(e) => save_error.set(Some(e.to_string())),
+            }
+            Ok::<(), anyhow::Error>(())
+        }
+    });
+    let message = match (save_error(), validated()) {
+        (Some(error), _) => error,
+        (None, Ok(_)) => "Ready to save".to_string(),
+        (None, Err(e)) => e.to_string(),
+    };
+    rsx! {
+        div { class: "modal is-active",
+            div { class: "modal-background" }
+            div { class: "modal-card",
+                header { class: "modal-card-head",
+                    p { class: "modal-card-title", "{title}" }
+                    button { class: "delete", aria_label: "close", onclick: move |_| on_end.call(()) }
+                }
+                section { class: "modal-card-body",
+                    p { class: "has-text-grey", "{message}" }
+                    div { class: "columns",
+                        div { class: "field column is-half",
+                            label { class: "label", "Symbol" }
+                            div { class: "control",
+                                input { class:"input", type: "text", value: "{symbol_state}",
+                                    disabled: is_editing,
+                                    oninput: move |e| symbol_state.set(e.value())
+                                }
+                            }
+                        }
+                        div { class: "field column is-half",
+                            label { class: "label", "Kind" }
+                            div { class: "control",
+                                div { class: "select is-fullwidth",
+                                    select {
+                                        onchange: move |e| {
+                                            let index = e.value().parse::<usize>().unwrap_or_default();
+                                            kind_state.set(ProductKind::ALL[index]);
+                                        },
+                                        for (index, kind) in ProductKind::ALL.iter().enumerate() {
+                                            option {
+                                                value: "{index}",
+                                                selected: *kind == kind_state(),
+                                                "{kind.label()}"
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    div { class: "field",
+                        label { class: "label", "Name" }
+                        div { class: "control",
+                            input { class:"input", type: "text", value: "{name_state}",
+                                oninput: move |e| name_state.set(e.value())
+                            }
+                        }
+                    }
+                    div { class: "field",
+                        label { class: "label", "Aliases" }
+                        div { class: "control",
+                            input { class:"input", type: "text", value: "{aliases_state}",
+                                placeholder: "e.g. BRK-B ETH-USD",
+                                oninput: move |e| aliases_state.set(e.value())
+                            }
+                        }
+                        p { class: "help", "Other symbols price files use for this product, separated by spaces" }
+                    }
+                    div { class: "columns",
+                        div { class: "field column",
+                            label { class: "label",
+                                if kind_state() == ProductKind::Coin { "Total supply" } else { "Outstanding shares" }
+                            }
+                            div { class: "control",
+                                input { class:"input", type: "text", value: "{supply_state}",
+                                    disabled: !kind_state().has_supply(),
+                                    placeholder: if !kind_state().has_supply() { "N/A" },
+                                    oninput: move |e| supply_state.set(e.value())
+                                }
+                            }
+                        }
+                        div { class: "field column",
+                            label { class: "label", "Share price" }
+                            div { class: "control",
+                                input { class:"input", type: "number", value: "{share_price_state}",
+                                    oninput: move |e| share_price_state.set(e.value())
+                                }
+                            }
+                        }
+                        div { class: "field column is-one-fifth",
+                            label { class: "label", "Currency" }
+                            div { class: "control",
+                                input { class:"input", type: "text", value: "{currency_state}",
+                                    placeholder: "USD",
+                                    oninput: move |e| currency_state.set(e.value())
+                                }
+                            }
+                        }
+                    }
+                }
+                footer { class: "modal-card-foot",
+                    div { class: "buttons",
+                        button { class: "button is-primary",
+                            class: if save.pending() { "is-loading" },
+                            disabled: validated().is_err() || save.pending(),
+                            onclick: move |_| {
+                                if let Ok(product) = validated() {
+                                    save_error.set(None);
+                                    save.call(product);
+                                }
+                            },
+                            "Save"
+                        }
+                        button { class: "button is-light",
+                            onclick: move |_| on_end.call(()),
+                            "Cancel"
                         }
                     }
                 }
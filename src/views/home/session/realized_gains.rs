@@ -0,0 +1,121 @@
+use super::net_worth::{format_date, format_signed_dollars, LevelDetailItem};
+use crate::api::{query_accounts, query_realized, query_wash_sales};
+use crate::data::account::account_name;
+use crate::data::tax::{disallowed_losses, format_form_8949, tax_year_summaries};
+use crate::data::term::is_long_term;
+use chrono::Datelike;
+use dioxus::prelude::*;
+
+#[component]
+pub fn RealizedGains() -> Element {
+    let realized = use_loader(|| async move { query_realized().await })?;
+    let accounts = use_loader(|| async move { query_accounts().await })?;
+    let wash_sales = use_loader(|| async move { query_wash_sales().await })?;
+    let summaries = use_memo(move || tax_year_summaries(&realized(), &wash_sales(), &accounts()));
+    let mut year_state = use_signal(|| None::<i32>);
+    let summary = summaries()
+        .into_iter()
+        .find(|summary| Some(summary.year) == year_state())
+        .or_else(|| summaries().first().cloned());
+    let Some(summary) = summary else {
+        return rsx! {
+            h1 { class: "title", "Realized Gains" }
+            p { class: "has-text-grey", "No sales recorded yet" }
+        };
+    };
+    let year = summary.year;
+    let disallowed = disallowed_losses(&wash_sales());
+    let mut sales = realized()
+        .into_iter()
+        .filter(|(_, gain)| gain.sold.year() == year)
+        .map(|(eid, gain)| {
+            let adjustment = disallowed.get(&eid).copied();
+            (gain, adjustment)
+        })
+        .collect::<Vec<_>>();
+    sales.sort_by_key(|(gain, _)| (gain.sold, gain.product.clone()));
+    let export = format_form_8949(&realized(), &wash_sales(), &accounts(), year)
+        .map(|csv| format!("data:text/csv;charset=utf-8,{}", percent_encode(&csv)));
+    let unknown_basis = summary.short_term.unknown_basis + summary.long_term.unknown_basis;
+    rsx! {
+        div { class: "block level",
+            div { class: "level-left",
+                h1 { class: "level-item title", "Realized Gains" }
+            }
+            div { class: "level-right",
+                div { class: "level-item select",
+                    select {
+                        onchange: move |e| year_state.set(e.value().parse().ok()),
+                        for summary in summaries().iter() {
+                            option {
+                                value: "{summary.year}",
+                                selected: summary.year == year,
+                                "{summary.year}"
+                            }
+                        }
+                    }
+                }
+                if let Ok(href) = export {
+                    a { class: "level-item button is-primary is-outlined",
+                        href: "{href}",
+                        download: "form-8949-{year}.csv",
+                        "Export CSV"
+                    }
+                }
+            }
+        }
+        h5 { class: "title is-5", "Summary" }
+        nav { class: "level is-mobile",
+            LevelDetailItem { label: "Short term".to_string(), content: format_signed_dollars(summary.short_term.gain) }
+            LevelDetailItem { label: "Long term".to_string(), content: format_signed_dollars(summary.long_term.gain) }
+            LevelDetailItem { label: "Total".to_string(), content: format_signed_dollars(summary.total_gain()) }
+            LevelDetailItem { label: "No basis".to_string(), content: unknown_basis.to_string() }
+        }
+        table { class: "table is-striped is-narrow",
+            thead {
+                tr {
+                    th { "Sold" }
+                    th { "Acquired" }
+                    th { "Product" }
+                    th { "Account" }
+                    th { "Quantity" }
+                    th { "Term" }
+                    th { "Proceeds" }
+                    th { "Cost basis" }
+                    th { "Gain" }
+                }
+            }
+            tbody {
+                for (gain, adjustment) in sales.iter() {
+                    tr {
+                        td { {format_date(gain.sold)} }
+                        td { {format_date(gain.acquired)} }
+                        td { "{gain.product}" }
+                        td { {account_name(&accounts(), &gain.account)} }
+                        td { "{gain.quantity}" }
+                        td { if is_long_term(gain.acquired, gain.sold) { "Long" } else { "Short" } }
+                        td { "{gain.proceeds:.2}" }
+                        td { {gain.cost_basis.map(|cost| format!("{:.2}", cost)).unwrap_or_default()} }
+                        td {
+                            {gain.gain().map(|gain| format!("{:.2}", gain + adjustment.unwrap_or_default())).unwrap_or_default()}
+                            if adjustment.is_some() {
+                                span { class: "tag is-warning ml-1", title: "Loss disallowed by a wash sale", "W" }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
@@ -0,0 +1,306 @@
+use crate::api::ecs::{Eid, LotItem};
+use crate::api::query_lots;
+use crate::data::portfolio::{parse_lot_amount, parse_lot_quantity, parse_lot_time};
+use crate::data::sale::{ReliefMethod, Sale};
+use dioxus::prelude::*;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Method {
+    Fifo,
+    Lifo,
+    HighestCost,
+    SpecificLots,
+}
+
+impl Method {
+    const ALL: [Method; 4] = [
+        Method::Fifo,
+        Method::Lifo,
+        Method::HighestCost,
+        Method::SpecificLots,
+    ];
+    fn label(&self) -> &'static str {
+        match self {
+            Method::Fifo => "First in, first out",
+            Method::Lifo => "Last in, first out",
+            Method::HighestCost => "Highest cost",
+            Method::SpecificLots => "Specific lots",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SaleForm {
+    account: String,
+    product: String,
+    time: String,
+    quantity: String,
+    unit_price: String,
+    fees: String,
+    method: Method,
+    chosen: Vec<Eid>,
+}
+
+impl SaleForm {
+    fn to_sale(&self) -> Result<(Sale, ReliefMethod), String> {
+        if self.account.is_empty() {
+            return Err("Choose an account holding the product".to_string());
+        }
+        let unit_price = parse_lot_amount(&self.unit_price)
+            .map_err(|e| e.to_string())?
+            .ok_or("Unit price is required".to_string())?;
+        let sale = Sale {
+            account: Eid::from(self.account.as_str()),
+            product: self.product.clone(),
+            time: parse_lot_time(&self.time).map_err(|e| e.to_string())?,
+            quantity: parse_lot_quantity(&self.quantity).map_err(|e| e.to_string())?,
+            unit_price,
+            fees: parse_lot_amount(&self.fees).map_err(|e| e.to_string())?,
+        };
+        let method = match self.method {
+            Method::Fifo => ReliefMethod::Fifo,
+            Method::Lifo => ReliefMethod::Lifo,
+            Method::HighestCost => ReliefMethod::HighestCost,
+            Method::SpecificLots if self.chosen.is_empty() => {
+                return Err("Choose the lots to sell".to_string())
+            }
+            Method::SpecificLots => ReliefMethod::SpecificLots(self.chosen.clone()),
+        };
+        sale.validate().map_err(|e| e.to_string())?;
+        Ok((sale, method))
+    }
+}
+
+#[component]
+pub fn SellLots(
+    products: Vec<String>,
+    accounts: Vec<(Eid, String)>,
+    on_end: EventHandler<()>,
+) -> Element {
+    let lot_items = use_loader(|| async move { query_lots().await })?;
+    let mut product_state = use_signal(|| products.first().cloned().unwrap_or_default());
+    let mut account_state = use_signal(|| "".to_string());
+    let mut time_state = use_signal(|| "".to_string());
+    let mut quantity_state = use_signal(|| "".to_string());
+    let mut unit_price_state = use_signal(|| "".to_string());
+    let mut fees_state = use_signal(|| "".to_string());
+    let mut method_state = use_signal(|| Method::Fifo);
+    let mut chosen_state = use_signal(Vec::<Eid>::new);
+    let mut save_error = use_signal(|| None::<String>);
+
+    let accounts = use_memo(move || {
+        let product = product_state();
+        let items = lot_items();
+        accounts
+            .iter()
+            .filter(|(eid, _)| {
+                items
+                    .iter()
+                    .any(|item| item.0.product == product && &item.0.account == eid)
+            })
+            .map(|(eid, name)| (eid.as_str().to_string(), name.clone()))
+            .collect::<Vec<_>>()
+    });
+    use_effect(move || {
+        let accounts = accounts();
+        if !accounts
+            .iter()
+            .any(|(eid, _)| *eid == *account_state.peek())
+        {
+            account_state.set(
+                accounts
+                    .first()
+                    .map(|(eid, _)| eid.clone())
+                    .unwrap_or_default(),
+            );
+            chosen_state.set(Vec::new());
+        }
+    });
+    let candidates = use_memo(move || {
+        let product = product_state();
+        let account = account_state();
+        let mut items = lot_items()
+            .into_iter()
+            .filter(|item| item.0.product == product && item.0.account.as_str() == account)
+            .collect::<Vec<LotItem>>();
+        items.sort_by_key(|item| item.0.time);
+        items
+    });
+    let validated = use_memo(move || {
+        SaleForm {
+            account: account_state(),
+            product: product_state(),
+            time: time_state(),
+            quantity: quantity_state(),
+            unit_price: unit_price_state(),
+            fees: fees_state(),
+            method: method_state(),
+            chosen: chosen_state(),
+        }
+        .to_sale()
+    });
+    let mut save = use_action(move |sale: Sale, method: ReliefMethod| async move {
+        use crate::api::sell_lots;
+        match sell_lots(sale, method).await {
+            Ok(_) => on_end.call(()),
+            Err(e) => save_error.set(Some(e.to_string())),
+        }
+        Ok::<(), anyhow::Error>(())
+    });
+    let message = match (save_error(), validated()) {
+        (Some(error), _) => error,
+        (None, Ok(_)) => "Ready to sell".to_string(),
+        (None, Err(e)) => e,
+    };
+    rsx! {
+        div { class: "modal is-active",
+            div { class: "modal-background" }
+            div { class: "modal-card",
+                header { class: "modal-card-head",
+                    p { class: "modal-card-title", "Sell" }
+                    button { class: "delete", aria_label: "close", onclick: move |_| on_end.call(()) }
+                }
+                section { class: "modal-card-body",
+                    p { class: "has-text-grey", "{message}" }
+                    div { class: "columns",
+                        div { class: "field column is-half",
+                            label { class: "label", "Product" }
+                            div { class: "control",
+                                div { class: "select is-fullwidth",
+                                    select {
+                                        onchange: move |e| product_state.set(e.value()),
+                                        for product in products.iter() {
+                                            option {
+                                                value: "{product}",
+                                                selected: product == &product_state(),
+                                                "{product}"
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        div { class: "field column is-half",
+                            label { class: "label", "Account" }
+                            div { class: "control",
+                                div { class: "select is-fullwidth",
+                                    select {
+                                        onchange: move |e| {
+                                            account_state.set(e.value());
+                                            chosen_state.set(Vec::new());
+                                        },
+                                        for (eid, name) in accounts().iter() {
+                                            option {
+                                                value: "{eid}",
+                                                selected: eid == &account_state(),
+                                                "{name}"
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    div { class: "field",
+                        label { class: "label", "Time" }
+                        div { class: "control",
+                            input { class:"input", type: "text", placeholder: "YYYY-MM-DD", value: "{time_state}",
+                                oninput: move |e| time_state.set(e.value())
+                            }
+                        }
+                    }
+                    div { class: "columns",
+                        div { class: "field column is-one-third",
+                            label { class: "label", "Quantity" }
+                            div { class: "control",
+                                input { class:"input", type: "number", value: "{quantity_state}",
+                                    oninput: move |e| quantity_state.set(e.value())
+                                }
+                            }
+                        }
+                        div { class: "field column is-one-third",
+                            label { class: "label", "Unit price" }
+                            div { class: "control",
+                                input { class:"input", type: "number", value: "{unit_price_state}",
+                                    oninput: move |e| unit_price_state.set(e.value())
+                                }
+                            }
+                        }
+                        div { class: "field column is-one-third",
+                            label { class: "label", "Fees" }
+                            div { class: "control",
+                                input { class:"input", type: "number", placeholder: "None", value: "{fees_state}",
+                                    oninput: move |e| fees_state.set(e.value())
+                                }
+                            }
+                        }
+                    }
+                    div { class: "field",
+                        label { class: "label", "Relief method" }
+                        div { class: "control",
+                            div { class: "select is-fullwidth",
+                                select {
+                                    onchange: move |e| {
+                                        let index = e.value().parse::<usize>().unwrap_or_default();
+                                        method_state.set(Method::ALL[index]);
+                                    },
+                                    for (index, method) in Method::ALL.iter().enumerate() {
+                                        option {
+                                            value: "{index}",
+                                            selected: *method == method_state(),
+                                            "{method.label()}"
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    if method_state() == Method::SpecificLots {
+                        div { class: "field",
+                            for item in candidates().into_iter() {
+                                div { class: "control",
+                                    label { class: "checkbox",
+                                        input { type: "checkbox",
+                                            checked: chosen_state().contains(&item.to_eid()),
+                                            onchange: {
+                                                let eid = item.to_eid();
+                                                move |e: FormEvent| {
+                                                    let mut chosen = chosen_state();
+                                                    chosen.retain(|chosen| chosen != &eid);
+                                                    if e.checked() {
+                                                        chosen.push(eid.clone());
+                                                    }
+                                                    chosen_state.set(chosen);
+                                                }
+                                            }
+                                        }
+                                        " {item.0.time.format(\"%Y-%m-%d\")} · {item.0.quantity}"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                footer { class: "modal-card-foot",
+                    div { class: "buttons",
+                        button { class: "button is-primary",
+                            class: if save.pending() { "is-loading" },
+                            disabled: validated().is_err() || save.pending(),
+                            onclick: move |_| {
+                                if let Ok((sale, method)) = validated() {
+                                    save_error.set(None);
+                                    save.call(sale, method);
+                                }
+                            },
+                            "Sell"
+                        }
+                        button { class: "button is-light",
+                            onclick: move |_| on_end.call(()),
+                            "Cancel"
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
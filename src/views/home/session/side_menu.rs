@@ -13,15 +13,21 @@ pub fn SideMenu(user_name: String, active_tab: Signal<Tab>) -> Element {
         ul { class: "menu-list",
             TabListItem { tab: Tab::Holdings, active: active_tab }
             TabListItem { tab: Tab::NetWorth, active: active_tab }
+            TabListItem { tab: Tab::RealizedGains, active: active_tab }
         }
         p { class: "menu-label", "Data"}
         ul { class: "menu-list",
             TabListItem { tab: Tab::Lots, active: active_tab }
+            TabListItem { tab: Tab::Accounts, active: active_tab }
             TabListItem { tab: Tab::Products, active: active_tab }
+            TabListItem { tab: Tab::TaxSettings, active: active_tab }
+            TabListItem { tab: Tab::Currencies, active: active_tab }
         }
         p { class: "menu-label", "Actions"}
         ul { class: "menu-list",
             TabListItem { tab: Tab::ImportPrices, active: active_tab }
+            TabListItem { tab: Tab::ImportLots, active: active_tab }
+            TabListItem { tab: Tab::History, active: active_tab }
         }
     }
 }
@@ -32,8 +38,14 @@ fn TabListItem(tab: Tab, active: Signal<Tab>) -> Element {
         Tab::Holdings => "Holdings",
         Tab::Products => "Products",
         Tab::Lots => "Lots",
+        Tab::Accounts => "Accounts",
         Tab::NetWorth => "Net Worth",
+        Tab::RealizedGains => "Realized Gains",
         Tab::ImportPrices => "Import Prices",
+        Tab::ImportLots => "Import Lots",
+        Tab::History => "History",
+        Tab::TaxSettings => "Tax Settings",
+        Tab::Currencies => "Currencies",
     };
     rsx! {
         li {
@@ -0,0 +1,90 @@
+use crate::api::{query_tax_settings, update_tax_settings};
+use crate::data::tax::{TaxRates, TaxSettings};
+use dioxus::prelude::*;
+
+#[component]
+pub fn TaxSettingsPage() -> Element {
+    let settings = use_loader(|| async move { query_tax_settings().await })?;
+    rsx! {
+        TaxSettingsForm { settings: settings() }
+    }
+}
+
+#[component]
+fn TaxSettingsForm(settings: TaxSettings) -> Element {
+    let federal_short = use_signal(|| settings.federal.short_term.to_string());
+    let federal_long = use_signal(|| settings.federal.long_term.to_string());
+    let state_short = use_signal(|| settings.state.short_term.to_string());
+    let state_long = use_signal(|| settings.state.long_term.to_string());
+    let validated = use_memo(move || {
+        let federal = parse_rates(&federal_short(), &federal_long())?;
+        let state = parse_rates(&state_short(), &state_long())?;
+        Ok::<_, String>(TaxSettings {
+            federal,
+            state,
+            ..Default::default()
+        })
+    });
+    let mut save =
+        use_action(move |settings: TaxSettings| async move { update_tax_settings(settings).await });
+    let message = match (save.value(), validated()) {
+        (_, Err(e)) => e,
+        (Some(Err(e)), _) => e.to_string(),
+        (Some(Ok(_)), _) => "Saved".to_string(),
+        (None, Ok(_)) => "Rates are percentages, e.g. 24 for 24%".to_string(),
+    };
+    rsx! {
+        h1 { class: "title", "Tax Settings" }
+        p { class: "block has-text-grey", "{message}" }
+        h5 { class: "title is-5", "Rates" }
+        div { class: "columns",
+            RateField { label: "Federal short term", value: federal_short }
+            RateField { label: "Federal long term", value: federal_long }
+            RateField { label: "State short term", value: state_short }
+            RateField { label: "State long term", value: state_long }
+        }
+        p { class: "block",
+            "How each account is taxed follows its kind, set on the Accounts page."
+        }
+        button { class: "button is-primary",
+            class: if save.pending() { "is-loading" },
+            disabled: validated().is_err() || save.pending(),
+            onclick: move |_| {
+                if let Ok(settings) = validated() {
+                    save.call(settings);
+                }
+            },
+            "Save"
+        }
+    }
+}
+
+#[component]
+fn RateField(label: String, value: Signal<String>) -> Element {
+    rsx! {
+        div { class: "field column",
+            label { class: "label", "{label}" }
+            div { class: "control",
+                input { class: "input",
+                    r#type: "text",
+                    value: "{value}",
+                    oninput: move |e| value.set(e.value())
+                }
+            }
+        }
+    }
+}
+
+fn parse_rates(short_term: &str, long_term: &str) -> Result<TaxRates, String> {
+    Ok(TaxRates {
+        short_term: parse_percent(short_term)?,
+        long_term: parse_percent(long_term)?,
+    })
+}
+
+fn parse_percent(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(percent),
+        _ => Err(format!("'{}' is not a rate between 0 and 100", value)),
+    }
+}